@@ -0,0 +1,64 @@
+//! Engine micro-benchmarks
+//!
+//! Prints the results as JSON, or writes them to the file given by
+//! `--output`. Set the number of iterations with `--iterations`, or the
+//! environment variable `XX_BENCH_ITERATIONS`
+//!
+//! To compare the built-in resolvers, pass a host name to look up with
+//! `--resolve-host`. Each lookup goes to the network, so the resolver
+//! benchmarks run a hundredth of the iterations
+//!
+//! ```sh
+//! cargo bench --features bench-internal --bench engine -- --output engine.json
+//! ```
+
+#![allow(unused_crate_dependencies, clippy::print_stdout)]
+
+use std::env;
+use std::fs;
+
+use xx_core::error::*;
+use xx_pulse::bench::*;
+use xx_pulse::net::dns::DnsResolver;
+use xx_pulse::net::ThreadPoolResolver;
+use xx_pulse::*;
+
+fn option(name: &str) -> Option<String> {
+	let flag = format!("--{}", name);
+	let value = env::args().skip_while(|arg| *arg != flag).nth(1);
+
+	value.or_else(|| env::var(format!("XX_BENCH_{}", name.to_uppercase())).ok())
+}
+
+#[main]
+async fn main() -> Result<()> {
+	let iterations = option("iterations")
+		.and_then(|iterations| iterations.parse().ok())
+		.unwrap_or(100_000);
+
+	let mut results = vec![
+		op_builder(iterations),
+		op_template(iterations),
+		timer_insert_cancel(iterations).await,
+		wake_latency(iterations).await?,
+		completion_dispatch(iterations, 1).await?,
+		completion_dispatch(iterations, 64).await?
+	];
+
+	if let Some(host) = option("resolve-host") {
+		let lookups = (iterations / 100).max(1);
+		let dns = DnsResolver::from_system_config().await?;
+
+		results.push(resolve("resolve_threadpool", &ThreadPoolResolver, &host, lookups).await?);
+		results.push(resolve("resolve_dns", &dns, &host, lookups).await?);
+	}
+
+	let json = to_json(&results);
+
+	match option("output") {
+		Some(path) => fs::write(path, json)?,
+		None => println!("{}", json)
+	}
+
+	Ok(())
+}
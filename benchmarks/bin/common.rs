@@ -0,0 +1,107 @@
+//! Shared configuration and reporting for the benchmark binaries
+//!
+//! Options are passed as `--key value` or `--key=value`, and fall back to the
+//! environment variable `XX_BENCH_<KEY>` before using the default.
+
+#![allow(dead_code, unreachable_pub, clippy::print_stdout)]
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use xx_pulse::*;
+
+pub struct Config {
+	args: HashMap<String, String>
+}
+
+impl Config {
+	#[must_use]
+	pub fn parse() -> Self {
+		let mut args = HashMap::new();
+		let mut iter = env::args().skip(1);
+
+		while let Some(arg) = iter.next() {
+			let Some(arg) = arg.strip_prefix("--") else {
+				continue;
+			};
+
+			match arg.split_once('=') {
+				Some((key, value)) => args.insert(key.to_owned(), value.to_owned()),
+				None => args.insert(arg.to_owned(), iter.next().unwrap_or_default())
+			};
+		}
+
+		Self { args }
+	}
+
+	/// Get the option `key`, or `default` if it is not set
+	///
+	/// # Panics
+	/// If the option is set but cannot be parsed
+	#[allow(clippy::panic)]
+	pub fn get<T: FromStr>(&self, key: &str, default: T) -> T {
+		let env_key = format!("XX_BENCH_{}", key.to_uppercase().replace('-', "_"));
+		let value = match self.args.get(key) {
+			Some(value) => value.clone(),
+			None => match env::var(env_key) {
+				Ok(value) => value,
+				Err(_) => return default
+			}
+		};
+
+		match value.parse() {
+			Ok(value) => value,
+			Err(_) => panic!("Invalid value for option `{}`: {}", key, value)
+		}
+	}
+
+	pub fn duration(&self) -> Duration {
+		Duration::from_secs_f64(self.get("duration", 10.0))
+	}
+}
+
+/// Counters for a benchmark run
+#[derive(Default)]
+pub struct Stats {
+	ops: Cell<u64>,
+	bytes: Cell<u64>
+}
+
+#[asynchronous]
+impl Stats {
+	#[allow(clippy::arithmetic_side_effects)]
+	pub fn record(&self, bytes: usize) {
+		self.ops.set(self.ops.get() + 1);
+		self.bytes.set(self.bytes.get() + bytes as u64);
+	}
+
+	/// Print the counters, followed by the engine and timer stats of the
+	/// current runtime
+	#[allow(clippy::cast_precision_loss)]
+	pub async fn report(&self, name: &str, start: Instant) {
+		let elapsed = start.elapsed().as_secs_f64();
+		let ops = self.ops.get() as f64;
+		let bytes = self.bytes.get() as f64;
+
+		println!(
+			"{}: {:.0} ops/s, {:.2} MiB/s ({} ops, {} bytes in {:.2}s)",
+			name,
+			ops / elapsed,
+			bytes / elapsed / 1_048_576.0,
+			self.ops.get(),
+			self.bytes.get(),
+			elapsed
+		);
+
+		let engine = io::engine_stats().await;
+		let timers = timer_stats().await;
+
+		println!(
+			"{}: {} enters ({} for task work), {} spin hits, {} timers pending",
+			name, engine.enters, engine.taskrun_enters, engine.spin_hits, timers.pending
+		);
+	}
+}
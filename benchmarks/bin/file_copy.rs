@@ -0,0 +1,60 @@
+//! Sequential file copy benchmark
+//!
+//! Options: `--src`, `--dst`, `--block-size`, `--limit`
+
+#![allow(unused_crate_dependencies)]
+
+use std::os::fd::AsFd;
+use std::time::Instant;
+
+use xx_core::async_std::io::*;
+use xx_core::error::*;
+use xx_core::os::fcntl::OpenFlag;
+use xx_pulse::fs::File;
+use xx_pulse::*;
+
+mod common;
+
+use common::*;
+
+#[main]
+async fn main() -> Result<()> {
+	let config = Config::parse();
+	let src: String = config.get("src", "/dev/zero".to_owned());
+	let dst: String = config.get("dst", "/dev/null".to_owned());
+	let block_size: usize = config.get("block-size", 0x20000);
+	let limit: u64 = config.get("limit", 0x4000_0000);
+
+	let mut input = File::open(&src).await?;
+	let output = io::open(
+		&dst,
+		OpenFlag::Create | OpenFlag::WriteOnly | OpenFlag::Truncate,
+		0o644
+	)
+	.await?;
+
+	let stats = Stats::default();
+	let mut buf = vec![0; block_size];
+	let mut offset = 0u64;
+	let start = Instant::now();
+
+	while offset < limit {
+		let read = input.read(&mut buf).await?;
+
+		if read == 0 {
+			break;
+		}
+
+		#[allow(clippy::cast_possible_wrap)]
+		io::write(output.as_fd(), &buf[0..read], offset as i64).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(offset += read as u64);
+
+		stats.record(read);
+	}
+
+	stats.report("file_copy", start).await;
+
+	Ok(())
+}
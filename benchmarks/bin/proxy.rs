@@ -0,0 +1,119 @@
+//! TCP proxy benchmark. Clients connect to an echo server through a proxy,
+//! which forwards data in both directions.
+//!
+//! Options: `--clients`, `--size`, `--duration`
+
+#![allow(unused_crate_dependencies)]
+
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::time::Instant;
+
+use xx_core::async_std::io::*;
+use xx_core::error::*;
+use xx_pulse::net::*;
+use xx_pulse::*;
+
+mod common;
+
+use common::*;
+
+#[asynchronous]
+async fn echo(listener: TcpListener) -> Result<()> {
+	loop {
+		let (mut client, _) = listener.accept().await?;
+
+		spawn(async move {
+			let mut buf = vec![0; 0x4000];
+
+			loop {
+				let read = client.recv(&mut buf, Default::default()).await?;
+
+				if read == 0 {
+					break Ok::<_, Error>(());
+				}
+
+				client.write_all(&buf[0..read]).await?;
+			}
+		})
+		.await;
+	}
+}
+
+#[asynchronous]
+async fn forward(mut from: SocketHalf<'_>, mut to: SocketHalf<'_>) -> Result<()> {
+	let mut buf = vec![0; 0x4000];
+
+	loop {
+		let read = from.recv(&mut buf, Default::default()).await?;
+
+		if read == 0 {
+			break Ok(());
+		}
+
+		to.write_all(&buf[0..read]).await?;
+	}
+}
+
+#[asynchronous]
+async fn proxy(listener: TcpListener, upstream: SocketAddr) -> Result<()> {
+	loop {
+		let (mut client, _) = listener.accept().await?;
+
+		spawn(async move {
+			let mut server = Tcp::connect(upstream).await?;
+			let (client_reader, client_writer) = client.try_split()?;
+			let (server_reader, server_writer) = server.try_split()?;
+
+			join(
+				forward(client_reader, server_writer),
+				forward(server_reader, client_writer)
+			)
+			.await
+			.flatten()?;
+
+			Ok::<_, Error>(())
+		})
+		.await;
+	}
+}
+
+#[asynchronous]
+async fn client(addr: SocketAddr, size: usize, stats: Rc<Stats>) -> Result<()> {
+	let mut socket = Tcp::connect(addr).await?;
+	let message = vec![0x55; size];
+	let mut buf = vec![0; size];
+
+	loop {
+		socket.write_all(&message).await?;
+		socket.read_exact(&mut buf).await?;
+		stats.record(size);
+	}
+}
+
+#[main]
+async fn main() -> Result<()> {
+	let config = Config::parse();
+	let clients: usize = config.get("clients", 64);
+	let size: usize = config.get("size", 64);
+
+	let server = Tcp::bind("127.0.0.1:0").await?;
+	let upstream = server.local_addr().await?;
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let addr = listener.local_addr().await?;
+	let stats = Rc::new(Stats::default());
+
+	spawn(echo(server)).await;
+	spawn(proxy(listener, upstream)).await;
+
+	for _ in 0..clients {
+		spawn(client(addr, size, stats.clone())).await;
+	}
+
+	let start = Instant::now();
+
+	sleep(config.duration()).await?;
+	stats.report("proxy", start).await;
+
+	Ok(())
+}
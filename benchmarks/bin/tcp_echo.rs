@@ -0,0 +1,77 @@
+//! TCP echo server benchmark
+//!
+//! Options: `--addr`, `--clients`, `--size`, `--duration`
+
+#![allow(unused_crate_dependencies)]
+
+use std::rc::Rc;
+use std::time::Instant;
+
+use xx_core::async_std::io::*;
+use xx_core::error::*;
+use xx_pulse::net::*;
+use xx_pulse::*;
+
+mod common;
+
+use common::*;
+
+#[asynchronous]
+async fn serve(listener: TcpListener) -> Result<()> {
+	loop {
+		let (mut client, _) = listener.accept().await?;
+
+		spawn(async move {
+			let mut buf = vec![0; 0x4000];
+
+			loop {
+				let read = client.recv(&mut buf, Default::default()).await?;
+
+				if read == 0 {
+					break Ok::<_, Error>(());
+				}
+
+				client.write_all(&buf[0..read]).await?;
+			}
+		})
+		.await;
+	}
+}
+
+#[asynchronous]
+async fn client(addr: std::net::SocketAddr, size: usize, stats: Rc<Stats>) -> Result<()> {
+	let mut socket = Tcp::connect(addr).await?;
+	let message = vec![0x55; size];
+	let mut buf = vec![0; size];
+
+	socket.set_tcp_nodelay(true).await?;
+
+	loop {
+		socket.write_all(&message).await?;
+		socket.read_exact(&mut buf).await?;
+		stats.record(size);
+	}
+}
+
+#[main]
+async fn main() -> Result<()> {
+	let config = Config::parse();
+	let clients: usize = config.get("clients", 64);
+	let size: usize = config.get("size", 64);
+	let listener = Tcp::bind(config.get("addr", "127.0.0.1:0".to_owned())).await?;
+	let addr = listener.local_addr().await?;
+	let stats = Rc::new(Stats::default());
+
+	spawn(serve(listener)).await;
+
+	for _ in 0..clients {
+		spawn(client(addr, size, stats.clone())).await;
+	}
+
+	let start = Instant::now();
+
+	sleep(config.duration()).await?;
+	stats.report("tcp_echo", start).await;
+
+	Ok(())
+}
@@ -0,0 +1,73 @@
+//! UDP echo server benchmark
+//!
+//! Options: `--addr`, `--clients`, `--size`, `--duration`, `--timeout-ms`
+
+#![allow(unused_crate_dependencies)]
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use xx_core::error::*;
+use xx_pulse::net::*;
+use xx_pulse::*;
+
+mod common;
+
+use common::*;
+
+#[asynchronous]
+async fn serve(mut socket: DatagramSocket) -> Result<()> {
+	let mut buf = vec![0; 0x10000];
+
+	loop {
+		let (read, addr) = socket.recvfrom(&mut buf, Default::default()).await?;
+
+		socket
+			.sendto(&buf[0..read], Default::default(), &addr)
+			.await?;
+	}
+}
+
+#[asynchronous]
+async fn client(
+	addr: std::net::SocketAddr, size: usize, timeout: Duration, stats: Rc<Stats>
+) -> Result<()> {
+	let mut socket = Udp::connect(addr).await?;
+	let message = vec![0x55; size];
+	let mut buf = vec![0; size];
+
+	loop {
+		socket.send(&message, Default::default()).await?;
+
+		/* a lost datagram would stall the client forever, so send again */
+		match io::recv_timeout(socket.fd(), &mut buf, Default::default(), timeout).await {
+			Ok(read) => stats.record(read),
+			Err(err) if err.kind() == ErrorKind::TimedOut => (),
+			Err(err) => return Err(err)
+		}
+	}
+}
+
+#[main]
+async fn main() -> Result<()> {
+	let config = Config::parse();
+	let clients: usize = config.get("clients", 64);
+	let size: usize = config.get("size", 64);
+	let timeout = Duration::from_millis(config.get("timeout-ms", 100));
+	let server = Udp::bind(config.get("addr", "127.0.0.1:0".to_owned())).await?;
+	let addr = server.local_addr().await?;
+	let stats = Rc::new(Stats::default());
+
+	spawn(serve(server)).await;
+
+	for _ in 0..clients {
+		spawn(client(addr, size, timeout, stats.clone())).await;
+	}
+
+	let start = Instant::now();
+
+	sleep(config.duration()).await?;
+	stats.report("udp_echo", start).await;
+
+	Ok(())
+}
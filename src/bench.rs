@@ -0,0 +1,188 @@
+//! Internal micro-benchmarks for the engine, run by `benches/engine.rs`
+//!
+//! Each benchmark returns a [`Measurement`] with the time taken and the
+//! number of times the engine entered the kernel, so that changes to the
+//! submission and completion paths can be compared consistently.
+//!
+//! This module is not a stable API, and is only available with the
+//! `bench-internal` feature.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::hint::black_box;
+use std::os::fd::AsFd;
+use std::rc::Rc;
+
+use super::*;
+use crate::engine::bench;
+use crate::net::Resolve;
+
+/// The result of a benchmark
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+	pub name: &'static str,
+	pub iterations: u64,
+	pub elapsed: Duration,
+
+	/// The number of times the engine entered the kernel
+	pub enters: u64
+}
+
+impl Measurement {
+	fn new(name: &'static str, iterations: u64, elapsed: Duration, enters: u64) -> Self {
+		Self { name, iterations, elapsed, enters }
+	}
+
+	#[must_use]
+	#[allow(clippy::cast_precision_loss)]
+	pub fn nanos_per_iter(&self) -> f64 {
+		self.elapsed.as_nanos() as f64 / self.iterations.max(1) as f64
+	}
+}
+
+/// Format `measurements` as a JSON array
+#[must_use]
+pub fn to_json(measurements: &[Measurement]) -> String {
+	let mut json = String::from("[");
+
+	for (index, measurement) in measurements.iter().enumerate() {
+		if index != 0 {
+			json.push(',');
+		}
+
+		let _ = write!(
+			json,
+			"\n\t{{\"name\": \"{}\", \"iterations\": {}, \"elapsed_ns\": {}, \"ns_per_iter\": \
+			 {:.2}, \"enters\": {}}}",
+			measurement.name,
+			measurement.iterations,
+			measurement.elapsed.as_nanos(),
+			measurement.nanos_per_iter(),
+			measurement.enters
+		);
+	}
+
+	json.push_str("\n]");
+	json
+}
+
+/// Build submission entries with the `Op` builders
+#[must_use]
+pub fn op_builder(iterations: u64) -> Measurement {
+	Measurement::new("op_builder", iterations, bench::op_builder(iterations), 0)
+}
+
+/// Copy a prepared submission entry, patching only the fields that change
+/// between operations
+#[must_use]
+pub fn op_template(iterations: u64) -> Measurement {
+	Measurement::new("op_template", iterations, bench::op_template(iterations), 0)
+}
+
+/// Tracks the time and engine enters from the start of a benchmark
+struct Timer {
+	start: u64,
+	enters: u64
+}
+
+#[asynchronous]
+impl Timer {
+	async fn start() -> Self {
+		let enters = io::engine_stats().await.enters;
+
+		Self { start: nanotime(), enters }
+	}
+
+	async fn finish(self, name: &'static str, iterations: u64) -> Measurement {
+		let elapsed = Duration::from_nanos(nanotime().saturating_sub(self.start));
+		let enters = io::engine_stats().await.enters.saturating_sub(self.enters);
+
+		Measurement::new(name, iterations, elapsed, enters)
+	}
+}
+
+/// Insert a timer and cancel it before it expires
+#[asynchronous]
+pub async fn timer_insert_cancel(iterations: u64) -> Measurement {
+	let timer = Timer::start().await;
+
+	for _ in 0..iterations {
+		let result = select(sleep(Duration::from_secs(3600)), async {}).await;
+
+		black_box(result);
+	}
+
+	timer.finish("timer_insert_cancel", iterations).await
+}
+
+/// Run an empty function on the thread pool. The thread pool wakes the
+/// runtime from another thread to complete each call
+#[asynchronous]
+pub async fn wake_latency(iterations: u64) -> Result<Measurement> {
+	let timer = Timer::start().await;
+
+	for _ in 0..iterations {
+		run_blocking(|_| ()).await?;
+	}
+
+	Ok(timer.finish("wake_latency", iterations).await)
+}
+
+/// Read from `/dev/zero` with `batch` reads in flight at once, measuring how
+/// fast completions are dispatched to their tasks
+#[asynchronous]
+pub async fn completion_dispatch(iterations: u64, batch: u64) -> Result<Measurement> {
+	let zero = Rc::new(File::open("/dev/zero")?);
+	let timer = Timer::start().await;
+	let mut remaining = iterations;
+
+	while remaining != 0 {
+		let count = remaining.min(batch.max(1));
+		let mut handles = Vec::new();
+
+		for _ in 0..count {
+			let zero = zero.clone();
+
+			handles.push(
+				spawn(async move {
+					let mut buf = [0u8; 64];
+
+					io::read(zero.as_fd(), &mut buf, 0).await
+				})
+				.await
+			);
+		}
+
+		for handle in handles {
+			handle.await?;
+		}
+
+		remaining = remaining.saturating_sub(count);
+	}
+
+	let name = if batch > 1 {
+		"completion_dispatch_batched"
+	} else {
+		"completion_dispatch"
+	};
+
+	Ok(timer.finish(name, iterations).await)
+}
+
+/// Resolve `host` with `resolver`, to compare the built-in resolvers. See
+/// [`BuiltinResolver`](crate::net::BuiltinResolver)
+#[asynchronous]
+pub async fn resolve<R>(
+	name: &'static str, resolver: &R, host: &str, iterations: u64
+) -> Result<Measurement>
+where
+	R: Resolve
+{
+	let timer = Timer::start().await;
+
+	for _ in 0..iterations {
+		black_box(resolver.resolve(host, 0).await?);
+	}
+
+	Ok(timer.finish(name, iterations).await)
+}
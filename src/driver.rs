@@ -1,7 +1,10 @@
 #![allow(unreachable_pub)]
 
+use std::cell::OnceCell;
 use std::collections::BTreeSet;
-use std::os::fd::RawFd;
+use std::os::fd::{OwnedFd, RawFd};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use enumflags2::BitFlags;
 use xx_core::cell::*;
@@ -16,6 +19,7 @@ use xx_core::pointer::*;
 use xx_core::threadpool::*;
 
 use super::*;
+use crate::ops::fd_budget::FdBudget;
 
 /// # Safety
 /// valid pointer
@@ -23,9 +27,7 @@ unsafe fn prepare(ptr: Ptr<()>) {
 	let driver = ptr.cast::<Driver>();
 
 	/* Safety: ptr is valid */
-	let result = unsafe { ptr!(driver=>io_engine.prepare_wake()) };
-
-	result.expect_nounwind("Fatal error: failed to prepare wake on I/O engine");
+	unsafe { ptr!(driver=>prepare_wake()) };
 }
 
 /// # Safety
@@ -33,12 +35,11 @@ unsafe fn prepare(ptr: Ptr<()>) {
 ///
 /// See [`Request::complete`]
 unsafe fn wake(ptr: Ptr<()>, request: ReqPtr<()>) {
-	let driver = ptr.cast::<Driver>();
-
-	/* Safety: this function call is thread safe */
-	let result = unsafe { ptr!(driver=>io_engine.wake(request)) };
+	/* Safety: ptr is valid */
+	let driver = unsafe { ptr.cast::<Driver>().as_ref() };
 
-	result.expect_nounwind("Fatal error: failed to wake I/O engine");
+	/* Safety: guaranteed by caller. this function call is thread safe */
+	unsafe { driver.wake(request) };
 }
 
 static WAKER: WakerVTable = unsafe { WakerVTable::new(prepare, wake) };
@@ -50,24 +51,72 @@ fn shutdown() -> Error {
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 struct Timeout {
 	expire: u64,
-	request: ReqPtr<Result<()>>
+	request: ReqPtr<Result<()>>,
+
+	/// The owner of the timer, or zero if it has none
+	owner: u64
+}
+
+/// A snapshot of the timers queued on a driver
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct TimerStats {
+	/// The number of timers waiting to expire
+	pub pending: usize,
+
+	/// The number of pending timers registered to a [`TimerOwner`]
+	pub owned: usize,
+
+	/// The expiry of the earliest timer, from the [`nanotime`] clock
+	pub next_deadline: Option<u64>
+}
+
+impl TimerStats {
+	/// The time remaining until the earliest timer expires, or zero if it
+	/// already has. Returns `None` if there are no timers
+	#[must_use]
+	pub fn until_next(&self) -> Option<Duration> {
+		self.next_deadline
+			.map(|deadline| Duration::from_nanos(deadline.saturating_sub(nanotime())))
+	}
 }
 
 pub struct Driver {
+	id: u64,
 	timers: UnsafeCell<BTreeSet<Timeout>>,
+	owned: UnsafeCell<BTreeSet<(u64, Timeout)>>,
 	exiting: Cell<bool>,
+	alive: Rc<()>,
+	fd_budget: Rc<FdBudget>,
+	shutdown_fd: OnceCell<Rc<OwnedFd>>,
 	io_engine: Engine
 }
 
 impl Driver {
-	pub fn new() -> Result<Self> {
+	pub fn new(options: &EngineOptions) -> Result<Self> {
+		static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
 		Ok(Self {
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
 			timers: UnsafeCell::new(BTreeSet::new()),
+			owned: UnsafeCell::new(BTreeSet::new()),
 			exiting: Cell::new(false),
-			io_engine: Engine::new()?
+			alive: Rc::new(()),
+			fd_budget: Rc::new(FdBudget::new()),
+			shutdown_fd: OnceCell::new(),
+			io_engine: Engine::new(options)?
 		})
 	}
 
+	/// A unique id for this driver, which identifies its runtime
+	pub const fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// A handle that can no longer be upgraded once this driver is dropped
+	pub fn alive(&self) -> Weak<()> {
+		Rc::downgrade(&self.alive)
+	}
+
 	#[inline(always)]
 	fn time() -> u64 {
 		time::nanotime(ClockId::Monotonic).expect_nounwind("Failed to read the clock")
@@ -83,6 +132,19 @@ impl Driver {
 	fn queue_timer(&self, timer: Timeout) {
 		/* Safety: exclusive unsafe cell access */
 		unsafe { ptr!(self.timers=>insert(timer)) };
+
+		if timer.owner != 0 {
+			/* Safety: exclusive unsafe cell access */
+			unsafe { ptr!(self.owned=>insert((timer.owner, timer))) };
+		}
+	}
+
+	/// Remove a timer that was taken from `timers` from its owner
+	fn disown_timer(&self, timer: &Timeout) {
+		if timer.owner != 0 {
+			/* Safety: exclusive unsafe cell access */
+			unsafe { ptr!(self.owned=>remove(&(timer.owner, *timer))) };
+		}
 	}
 
 	fn cancel_timer(&self, timer: Timeout) -> Result<()> {
@@ -92,7 +154,11 @@ impl Driver {
 			None => return Err(fmt_error!("Timer not found" @ ErrorKind::NotFound))
 		};
 
-		xx_core::trace!(target: self, "## cancel_timer(request = {:?}) = Ok(reason = cancel)", timeout.request);
+		self.disown_timer(&timeout);
+
+		if trace_enabled(TraceSubsystem::Driver) {
+			xx_core::trace!(target: self, "## cancel_timer(request = {:?}) = Ok(reason = cancel)", timeout.request);
+		}
 
 		/* Safety: complete the future */
 		unsafe {
@@ -105,30 +171,57 @@ impl Driver {
 		Ok(())
 	}
 
+	/// Cancel all pending timers registered by `owner`, returning the number
+	/// of timers cancelled
+	///
+	/// Timers queued by the owner while its timers are being cancelled are
+	/// left pending
+	pub fn cancel_owned_timers(&self, owner: u64) -> usize {
+		let first = (owner, Timeout { expire: 0, request: ReqPtr::null(), owner: 0 });
+
+		/* Safety: exclusive unsafe cell access. no timers complete while the
+		 * set is borrowed
+		 */
+		let timers: Vec<_> = unsafe { &ptr!(*self.owned) }
+			.range(first..)
+			.take_while(|(timer_owner, _)| *timer_owner == owner)
+			.map(|&(_, timer)| timer)
+			.collect();
+
+		/* completing a timer may cancel other timers from this owner */
+		timers
+			.into_iter()
+			.filter(|timer| self.cancel_timer(*timer).is_ok())
+			.count()
+	}
+
 	#[future]
-	pub fn timeout(&self, mut expire: u64, flags: BitFlags<TimeoutFlag>, request: _) -> Result<()> {
+	pub fn timeout(
+		&self, mut expire: u64, flags: BitFlags<TimeoutFlag>, owner: u64, request: _
+	) -> Result<()> {
 		#[cancel]
-		fn cancel(&self, expire: u64) -> Result<()> {
-			self.cancel_timer(Timeout { expire, request })
+		fn cancel(&self, expire: u64, owner: u64) -> Result<()> {
+			self.cancel_timer(Timeout { expire, request, owner })
 		}
 
 		if let Err(err) = self.check_exiting() {
 			return Progress::Done(Err(err));
 		}
 
-		#[allow(clippy::expect_used)]
+		/* an expiry of u64::MAX never fires */
 		if !flags.intersects(TimeoutFlag::Abs) {
-			expire = expire.checked_add(nanotime()).expect("Timeout overflow");
+			expire = expire.saturating_add(nanotime());
 		}
 
-		xx_core::trace!(target: self, "## timeout(expire = {}, request = {:?}) = Ok(())", expire, request);
+		if trace_enabled(TraceSubsystem::Driver) {
+			xx_core::trace!(target: self, "## timeout(expire = {}, request = {:?}) = Ok(())", expire, request);
+		}
 
-		self.queue_timer(Timeout { expire, request });
+		self.queue_timer(Timeout { expire, request, owner });
 
-		Progress::Pending(cancel(self, expire))
+		Progress::Pending(cancel(self, expire, owner))
 	}
 
-	#[allow(clippy::missing_panics_doc)]
 	fn run_timers(&self) -> u64 {
 		#[allow(clippy::cast_possible_truncation)]
 		let mut timeout = duration!(1 hour).as_nanos() as u64;
@@ -155,10 +248,15 @@ impl Driver {
 
 			ran = true;
 
-			xx_core::trace!(target: self, "## run_timers: complete(request = {:?}, reason = timeout)", timer.request);
+			if trace_enabled(TraceSubsystem::Driver) {
+				xx_core::trace!(target: self, "## run_timers: complete(request = {:?}, reason = timeout)", timer.request);
+			}
+
+			let Some(timer) = timers.pop_first() else {
+				break;
+			};
 
-			#[allow(clippy::unwrap_used)]
-			let timer = timers.pop_first().unwrap();
+			self.disown_timer(&timer);
 
 			/* Safety: complete the future */
 			unsafe { Self::timer_complete(timer, Ok(())) };
@@ -192,7 +290,6 @@ impl Driver {
 		}
 	}
 
-	#[allow(clippy::missing_panics_doc)]
 	pub fn exit(&self) {
 		self.exiting.set(true);
 
@@ -200,12 +297,11 @@ impl Driver {
 			/* Safety: we have exclusive access until expire */
 			let timers = unsafe { &mut ptr!(*self.timers) };
 
-			if timers.is_empty() {
+			let Some(timeout) = timers.pop_first() else {
 				break;
-			}
+			};
 
-			#[allow(clippy::unwrap_used)]
-			let timeout = timers.pop_first().unwrap();
+			self.disown_timer(&timeout);
 
 			/* Safety: complete the future */
 			unsafe { Self::timer_complete(timeout, Err(shutdown())) };
@@ -236,6 +332,60 @@ impl Driver {
 	pub fn waker(&self) -> Waker {
 		Waker::new(ptr!(self).cast(), &WAKER)
 	}
+
+	/// Expect a call to [`Driver::wake`], which may come from another thread
+	pub fn prepare_wake(&self) {
+		let result = self.io_engine.prepare_wake();
+
+		result.expect_nounwind("Fatal error: failed to prepare wake on I/O engine");
+	}
+
+	/// Complete `request` on the driver's thread. This function is thread
+	/// safe, and must be preceded by a call to [`Driver::prepare_wake`]
+	///
+	/// # Safety
+	/// See [`Request::complete`]
+	pub unsafe fn wake(&self, request: ReqPtr<()>) {
+		let result = self.io_engine.wake(request);
+
+		result.expect_nounwind("Fatal error: failed to wake I/O engine");
+	}
+
+	pub fn engine_stats(&self) -> EngineStats {
+		self.io_engine.stats()
+	}
+
+	pub fn engine_feature_supported(&self, feature: EngineFeature) -> bool {
+		self.io_engine.feature_supported(feature)
+	}
+
+	pub const fn is_compatibility_mode(&self) -> bool {
+		self.io_engine.is_compatibility_mode()
+	}
+
+	pub const fn fd_budget(&self) -> &Rc<FdBudget> {
+		&self.fd_budget
+	}
+
+	/// The signalfd shared by every [`shutdown_signal`](crate::shutdown_signal)
+	/// on this runtime
+	pub const fn shutdown_fd(&self) -> &OnceCell<Rc<OwnedFd>> {
+		&self.shutdown_fd
+	}
+
+	pub fn timer_stats(&self) -> TimerStats {
+		/* Safety: exclusive unsafe cell access */
+		let timers = unsafe { &ptr!(*self.timers) };
+
+		/* Safety: exclusive unsafe cell access */
+		let owned = unsafe { &ptr!(*self.owned) };
+
+		TimerStats {
+			pending: timers.len(),
+			owned: owned.len(),
+			next_deadline: timers.first().map(|timer| timer.expire)
+		}
+	}
 }
 
 macro_rules! engine_task {
@@ -290,8 +440,13 @@ impl Driver {
 
 	engine_task!(statx(dirfd: RawFd, path: Ptr<()>, flags: u32, mask: u32, statx: MutPtr<Statx>));
 
+	engine_task!(fadvise(fd: RawFd, offset: u64, len: u32, advice: u32));
+
 	engine_task!(poll(fd: RawFd, mask: u32));
 
+	#[cfg(feature = "uring-cmd")]
+	engine_task!(uring_cmd(fd: RawFd, cmd_op: u32, cmd: Ptr<()>, len: usize, extra: MutPtr<[u64; 2]>));
+
 	#[future]
 	pub unsafe fn run_work(&self, work: MutPtr<Work<'_>>, request: _) -> bool {
 		#[cancel]
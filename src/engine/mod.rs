@@ -2,6 +2,7 @@
 
 use std::os::fd::{IntoRawFd, OwnedFd, RawFd};
 
+use enumflags2::{bitflags, BitFlags};
 use xx_core::error::*;
 use xx_core::future::*;
 use xx_core::macros::paste;
@@ -13,7 +14,11 @@ use xx_core::os::unistd::close_raw;
 use xx_core::pointer::*;
 use xx_core::threadpool::*;
 
+use crate::runtime::{trace_enabled, TraceSubsystem};
+
 mod uring;
+#[cfg(feature = "bench-internal")]
+pub use uring::bench;
 use uring::IoUring;
 
 #[allow(dead_code)]
@@ -245,6 +250,18 @@ pub unsafe trait EngineImpl: Pin {
 		unimplemented!();
 	}
 
+	fn fadvise_kind(&self) -> OperationKind {
+		OperationKind::SyncOffload
+	}
+
+	/// # Safety
+	/// See [`Future::run`]
+	unsafe fn fadvise(
+		&self, _fd: RawFd, _offset: u64, _len: u32, _advice: u32, _request: ReqPtr<isize>
+	) -> Option<isize> {
+		unimplemented!();
+	}
+
 	fn poll_kind(&self) -> OperationKind {
 		OperationKind::SyncOffload
 	}
@@ -254,6 +271,22 @@ pub unsafe trait EngineImpl: Pin {
 	unsafe fn poll(&self, _fd: RawFd, _mask: u32, _request: ReqPtr<isize>) -> Option<isize> {
 		unimplemented!();
 	}
+
+	#[cfg(feature = "uring-cmd")]
+	fn uring_cmd_kind(&self) -> OperationKind {
+		OperationKind::Async
+	}
+
+	/// # Safety
+	/// See [`Future::run`]. `cmd` must be valid for `len` bytes, and `extra`
+	/// must be valid for writes until the request completes
+	#[cfg(feature = "uring-cmd")]
+	unsafe fn uring_cmd(
+		&self, _fd: RawFd, _cmd_op: u32, _cmd: Ptr<()>, _len: usize, _extra: MutPtr<[u64; 2]>,
+		_request: ReqPtr<isize>
+	) -> Option<isize> {
+		unimplemented!();
+	}
 }
 
 pub struct SyncEngine {}
@@ -344,6 +377,124 @@ unsafe impl EngineImpl for SyncEngine {
 	}
 }
 
+/// The order in which completed operations resume their tasks
+#[cfg(feature = "test-util")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ScheduleOrder {
+	/// Whatever order is fastest for the engine
+	#[default]
+	Default,
+
+	/// Resume tasks in the order their operations completed
+	Fifo,
+
+	/// Shuffle each batch of completions using a pseudo-random sequence
+	/// generated from the seed. The same seed produces the same order for the
+	/// same sequence of completions
+	Seeded(u64)
+}
+
+/// Engine features which can be force disabled, so that the fallback code
+/// paths for older kernels can be tested and benchmarked on any machine
+#[bitflags]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum EngineFeature {
+	/// Passing a timeout directly to `io_uring_enter`. Without it, a timeout
+	/// operation is submitted before each wait
+	ExtArg    = 1 << 0,
+
+	/// Creating sockets asynchronously. Without it, `socket(2)` is called
+	/// directly
+	SocketOp  = 1 << 1,
+
+	/// Closing files asynchronously. Without it, `close(2)` is called
+	/// directly
+	CloseOp   = 1 << 2,
+
+	/// Continuing submission after an operation fails inline
+	SubmitAll = 1 << 3
+}
+
+/// When the engine runs deferred task work, the kernel side processing that
+/// posts completions for some operations
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TaskRunPolicy {
+	/// Run task work once per iteration of the event loop, when waiting for
+	/// completions. Fewer syscalls, at the cost of completion latency when the
+	/// submission queue fills up mid iteration
+	#[default]
+	Batched,
+
+	/// Run task work on every enter into the kernel, including the flushes of
+	/// a full submission queue
+	EveryEnter
+}
+
+/// Counters for the syscalls made by the engine
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct EngineStats {
+	/// The number of times the engine entered the kernel
+	pub enters: u64,
+
+	/// The number of enters made only because the kernel had task work
+	/// pending, with nothing to submit or wait for
+	pub taskrun_enters: u64,
+
+	/// The number of times spinning before a wait found completions, saving
+	/// an enter. See [`EngineOptions::spin`]
+	pub spin_hits: u64
+}
+
+/// Options for creating an [`Engine`]
+#[derive(Clone, Copy, Debug)]
+pub struct EngineOptions {
+	pub disabled: BitFlags<EngineFeature>,
+	pub sq_entries: u32,
+	pub cq_entries: u32,
+
+	/// The number of threads for blocking operations, or `None` for the
+	/// default
+	pub threads: Option<usize>,
+
+	pub task_run: TaskRunPolicy,
+
+	/// The longest time to poll for completions before waiting in the kernel,
+	/// in nanoseconds, or zero to never spin. The time spent spinning adapts
+	/// to how often spinning finds completions
+	pub spin: u64,
+
+	/// Do not log warnings about missing kernel support. Each warning is only
+	/// logged once per process regardless
+	pub quiet: bool,
+
+	#[cfg(feature = "test-util")]
+	pub schedule: ScheduleOrder
+}
+
+impl EngineOptions {
+	pub const fn new() -> Self {
+		Self {
+			disabled: BitFlags::EMPTY,
+			sq_entries: 0x100,
+			cq_entries: 0x2000,
+			threads: None,
+			task_run: TaskRunPolicy::Batched,
+			spin: 0,
+			quiet: false,
+
+			#[cfg(feature = "test-util")]
+			schedule: ScheduleOrder::Default
+		}
+	}
+}
+
+impl Default for EngineOptions {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 /// I/O Backend
 ///
 /// Could be one of io_uring, epoll, kqueue, iocp, etc
@@ -353,9 +504,9 @@ pub struct Engine {
 }
 
 impl Engine {
-	pub fn new() -> Result<Self> {
+	pub fn new(options: &EngineOptions) -> Result<Self> {
 		#[cfg(target_os = "linux")]
-		let inner = IoUring::new()?;
+		let inner = IoUring::new(options)?;
 
 		Ok(Self { inner })
 	}
@@ -370,6 +521,18 @@ impl Engine {
 		self.inner.work(timeout)
 	}
 
+	pub fn stats(&self) -> EngineStats {
+		self.inner.stats()
+	}
+
+	pub fn feature_supported(&self, feature: EngineFeature) -> bool {
+		self.inner.feature_supported(feature)
+	}
+
+	pub const fn is_compatibility_mode(&self) -> bool {
+		self.inner.is_compatibility_mode()
+	}
+
 	pub fn prepare_wake(&self) -> Result<()> {
 		self.inner.prepare_wake()
 	}
@@ -439,8 +602,13 @@ impl Engine {
 
 	engine_task!(statx(dirfd: RawFd, path: Ptr<()>, flags: u32, mask: u32, statx: MutPtr<Statx>) -> OsResult<()>);
 
+	engine_task!(fadvise(fd: RawFd, offset: u64, len: u32, advice: u32) -> OsResult<()>);
+
 	engine_task!(poll(fd: RawFd, mask: u32) -> OsResult<u32>);
 
+	#[cfg(feature = "uring-cmd")]
+	engine_task!(uring_cmd(fd: RawFd, cmd_op: u32, cmd: Ptr<()>, len: usize, extra: MutPtr<[u64; 2]>) -> OsResult<u32>);
+
 	#[future]
 	pub unsafe fn run_work(&self, work: MutPtr<Work<'_>>, request: _) -> bool {
 		#[cancel]
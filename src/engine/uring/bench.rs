@@ -0,0 +1,48 @@
+//! Submission path micro-benchmarks. See [`crate::bench`]
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use super::*;
+
+const BUF_LEN: u32 = 0x1000;
+
+/// Build a read entry from scratch `iterations` times
+#[allow(clippy::cast_possible_wrap)]
+pub fn op_builder(iterations: u64) -> Duration {
+	let mut buf = [0u8; BUF_LEN as usize];
+	let start = Instant::now();
+
+	for offset in 0..iterations {
+		let entry = Op::read(
+			black_box(0),
+			ptr!(buf.as_mut_ptr()).cast(),
+			BUF_LEN,
+			black_box(offset as i64),
+			0
+		);
+
+		black_box(&entry);
+	}
+
+	start.elapsed()
+}
+
+/// Copy a prepared read entry and patch the per operation fields
+/// `iterations` times
+pub fn op_template(iterations: u64) -> Duration {
+	let mut buf = [0u8; BUF_LEN as usize];
+	let template = Op::read(0, ptr!(buf.as_mut_ptr()).cast(), BUF_LEN, 0, 0);
+	let start = Instant::now();
+
+	for offset in 0..iterations {
+		let mut entry = template;
+
+		entry.fd = black_box(0);
+		entry.off.off = black_box(offset);
+
+		black_box(&entry);
+	}
+
+	start.elapsed()
+}
@@ -1,9 +1,14 @@
 #![allow(clippy::multiple_unsafe_ops_per_block)]
 
+#[cfg(feature = "uring-cmd")]
+use std::cell::RefCell;
+#[cfg(feature = "uring-cmd")]
+use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::hint::spin_loop;
 use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
-use std::sync::atomic::{compiler_fence, AtomicU32, Ordering};
-use std::sync::Mutex;
+use std::sync::atomic::{compiler_fence, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
 
 use enumflags2::{make_bitflags, BitFlags};
 use xx_core::cell::Cell;
@@ -15,11 +20,16 @@ use xx_core::os::eventfd::*;
 use xx_core::os::mman::*;
 use xx_core::os::openat::*;
 use xx_core::os::poll::PollFlag;
+use xx_core::os::time::{self, ClockId};
 use xx_core::threadpool::*;
 use xx_core::{debug, error, trace, warn};
 
 use super::*;
 
+/* wide entries are twice these sizes, which the kernel expects to be 128 and 32 */
+const _: () = assert!(size_of::<SubmissionEntry>() == 64);
+const _: () = assert!(size_of::<CompletionEntry>() == 16);
+
 struct Rings<'mem> {
 	ring: Map<'mem>,
 	separate_completion_ring: Option<Map<'mem>>,
@@ -27,13 +37,15 @@ struct Rings<'mem> {
 }
 
 impl<'mem> Rings<'mem> {
-	#[allow(clippy::arithmetic_side_effects)]
-	const fn scale<T>(mut count: u32, offset: u32, wide: bool) -> usize {
-		if wide {
-			count *= 2;
-		}
-
-		offset as usize + size_of::<T>() * count as usize
+	/// The size of `count` entries of `T` starting at `offset`. Wide entries
+	/// take up two slots each
+	fn scale<T>(count: u32, offset: u32, wide: bool) -> Result<usize> {
+		let slots = if wide { count.checked_mul(2) } else { Some(count) };
+
+		slots
+			.and_then(|slots| size_of::<T>().checked_mul(slots as usize))
+			.and_then(|size| size.checked_add(offset as usize))
+			.ok_or_else(|| fmt_error!("Ring size overflows" @ ErrorKind::InvalidInput))
 	}
 
 	fn map_memory(size: usize, offset: MmapOffsets, fd: BorrowedFd<'_>) -> OsResult<Map<'mem>> {
@@ -56,12 +68,12 @@ impl<'mem> Rings<'mem> {
 
 	fn new(fd: BorrowedFd<'_>, params: &Parameters) -> Result<Self> {
 		let ring_sizes = (
-			Self::scale::<u32>(params.sq_entries, params.sq_off.array, false),
+			Self::scale::<u32>(params.sq_entries, params.sq_off.array, false)?,
 			Self::scale::<CompletionEntry>(
 				params.cq_entries,
 				params.cq_off.cqes,
 				params.flags().intersects(SetupFlag::CompletionEntryWide)
-			)
+			)?
 		);
 
 		let (ring, separate_completion_ring) = if params.features().intersects(Feature::SingleMmap)
@@ -89,7 +101,38 @@ impl<'mem> Rings<'mem> {
 			params.sq_entries,
 			0,
 			params.flags().intersects(SetupFlag::SubmissionEntryWide)
-		);
+		)?;
+
+		let (submission_ring, completion_ring) = if separate_completion_ring.is_some() {
+			ring_sizes
+		} else {
+			let size = ring_sizes.0.max(ring_sizes.1);
+
+			(size, size)
+		};
+
+		/* the queues index this far into the mappings. see `SubmissionQueue::new` */
+		let sq_shift = u32::from(params.flags().intersects(SetupFlag::SubmissionEntryWide));
+		let cq_shift = u32::from(params.flags().intersects(SetupFlag::CompletionEntryWide));
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let extents = [
+			(array_end::<u32>(params.sq_off.array, params.sq_entries), submission_ring),
+			(
+				array_end::<SubmissionEntry>(0, params.sq_entries << sq_shift),
+				submission_entries_size
+			),
+			(
+				array_end::<CompletionEntry>(params.cq_off.cqes, params.cq_entries << cq_shift),
+				completion_ring
+			)
+		];
+
+		if extents.iter().any(|(end, mapped)| end > mapped) {
+			return Err(fmt_error!(
+				"Ring mappings are too small for the setup flags" @ ErrorKind::InvalidData
+			));
+		}
 
 		Ok(Self {
 			ring,
@@ -111,6 +154,7 @@ struct SubmissionQueue<'mem> {
 
 	tail: Cell<u32>,
 	mask: u32,
+	shift: u32,
 	entries: MutPtr<[SubmissionEntry]>,
 
 	capacity: u32,
@@ -128,6 +172,7 @@ struct CompletionQueue<'mem> {
 	ktail: &'mem AtomicU32,
 	entries: MutPtr<[CompletionEntry]>,
 	mask: u32,
+	shift: u32,
 
 	/* unused */
 	kflags: &'mem AtomicU32,
@@ -157,6 +202,11 @@ unsafe fn get_ref<'mem, T>(map: &Map<'mem>, off: u32) -> &'mem T {
 	unsafe { get_ptr::<T>(map, off).as_ref() }
 }
 
+/// The end of an array of `len` elements of `T` at `off`, in bytes
+const fn array_end<T>(off: u32, len: u32) -> usize {
+	(off as usize).saturating_add(size_of::<T>().saturating_mul(len as usize))
+}
+
 /// # Safety
 /// valid offset and len
 unsafe fn get_array<T>(map: &Map<'_>, off: u32, len: u32) -> MutPtr<[T]> {
@@ -172,6 +222,10 @@ impl<'mem> SubmissionQueue<'mem> {
 	#[allow(unsafe_op_in_unsafe_fn)]
 	unsafe fn new(maps: &Rings<'mem>, params: &Parameters) -> Self {
 		let ring = maps.submission_ring();
+
+		/* wide entries take up two slots each */
+		let shift = u32::from(params.flags().intersects(SetupFlag::SubmissionEntryWide));
+
 		let sq = SubmissionQueue {
 			khead: get_ref(ring, params.sq_off.head),
 			ktail: get_ref(ring, params.sq_off.tail),
@@ -179,10 +233,11 @@ impl<'mem> SubmissionQueue<'mem> {
 			kdropped: get_ref(ring, params.sq_off.dropped),
 
 			array: get_array(ring, params.sq_off.array, params.sq_entries),
-			entries: get_array(&maps.submission_entries, 0, params.sq_entries),
+			entries: get_array(&maps.submission_entries, 0, params.sq_entries << shift),
 
 			#[allow(clippy::arithmetic_side_effects)]
 			mask: params.sq_entries - 1,
+			shift,
 			capacity: params.sq_entries,
 
 			tail: Cell::new(0)
@@ -214,14 +269,52 @@ impl<'mem> SubmissionQueue<'mem> {
 		unsafe { ptr!(self.entries=>[index as usize] = entry) };
 	}
 
-	fn push(&self, entry: SubmissionEntry) {
+	fn next_index(&self) -> u32 {
 		let tail = self.tail.get();
 
 		#[allow(clippy::arithmetic_side_effects)]
 		self.tail.update(|tail| tail + 1);
 
+		#[allow(clippy::arithmetic_side_effects)]
+		((tail & self.mask) << self.shift)
+	}
+
+	fn push(&self, entry: SubmissionEntry) {
+		let index = self.next_index();
+
 		/* Safety: tail is masked */
-		unsafe { self.write(tail & self.mask, entry) };
+		unsafe { self.write(index, entry) };
+	}
+
+	/// Push an entry, followed by `cmd` in its command area
+	///
+	/// # Safety
+	/// the ring must have been set up with wide entries, and `cmd` must fit
+	/// in [`URING_CMD_LEN`] bytes
+	#[cfg(feature = "uring-cmd")]
+	unsafe fn push_cmd(&self, entry: SubmissionEntry, cmd: &[u8]) {
+		let mut wide = [entry, SubmissionEntry::default()];
+
+		/* Safety: guaranteed by caller. the command area spans both halves */
+		unsafe { assert_unsafe_precondition!(cmd.len() <= URING_CMD_LEN) };
+
+		/* Safety: the area is in bounds of `wide` */
+		let area = unsafe { wide.as_mut_ptr().cast::<u8>().add(URING_CMD_OFFSET) };
+
+		/* Safety: both pointers are valid and do not overlap */
+		unsafe { std::ptr::copy_nonoverlapping(cmd.as_ptr(), area, cmd.len()) };
+
+		let index = self.next_index();
+		let [first, second] = wide;
+
+		/* Safety: tail is masked, and wide entries take up two slots */
+		unsafe { self.write(index, first) };
+
+		/* Safety: see above */
+		#[allow(clippy::arithmetic_side_effects)]
+		unsafe {
+			self.write(index + 1, second);
+		}
 	}
 
 	fn sync(&self) {
@@ -236,6 +329,7 @@ impl<'mem> CompletionQueue<'mem> {
 	#[allow(unsafe_op_in_unsafe_fn)]
 	unsafe fn new(maps: &Rings<'mem>, params: &Parameters) -> Self {
 		let ring = maps.completion_ring();
+		let shift = u32::from(params.flags().intersects(SetupFlag::CompletionEntryWide));
 
 		CompletionQueue {
 			khead: get_ref(ring, params.cq_off.head),
@@ -243,10 +337,11 @@ impl<'mem> CompletionQueue<'mem> {
 			kflags: get_ref(ring, params.cq_off.flags),
 			koverflow: get_ref(ring, params.cq_off.overflow),
 
-			entries: get_array(ring, params.cq_off.cqes, params.cq_entries),
+			entries: get_array(ring, params.cq_off.cqes, params.cq_entries << shift),
 
 			#[allow(clippy::arithmetic_side_effects)]
 			mask: params.cq_entries - 1,
+			shift,
 			capacity: params.cq_entries
 		}
 	}
@@ -260,6 +355,9 @@ impl<'mem> CompletionQueue<'mem> {
 	/// # Safety
 	/// index must be in bounds
 	unsafe fn read(&self, index: u32) -> CompletionEntry {
+		#[allow(clippy::arithmetic_side_effects)]
+		let index = index << self.shift;
+
 		/* Safety: guaranteed by caller */
 		unsafe { assert_unsafe_precondition!((index as usize) < self.entries.len()) };
 
@@ -267,6 +365,25 @@ impl<'mem> CompletionQueue<'mem> {
 		unsafe { ptr!(self.entries=>[index as usize]) }
 	}
 
+	/// Read the second half of the wide entry at `index`
+	///
+	/// # Safety
+	/// index must be in bounds, and the ring must have wide entries
+	#[cfg(feature = "uring-cmd")]
+	unsafe fn read_extra(&self, index: u32) -> [u64; 2] {
+		#[allow(clippy::arithmetic_side_effects)]
+		let index = (index << self.shift) + 1;
+
+		/* Safety: guaranteed by caller */
+		unsafe { assert_unsafe_precondition!((index as usize) < self.entries.len()) };
+
+		/* Safety: guaranteed by caller */
+		let entry = unsafe { ptr!(self.entries=>[index as usize]) };
+
+		/* Safety: the entry is plain old data of the same size */
+		unsafe { std::mem::transmute::<CompletionEntry, [u64; 2]>(entry) }
+	}
+
 	fn read_ring(&self) -> (u32, u32) {
 		let result = (
 			self.khead.load(Ordering::Relaxed),
@@ -302,15 +419,97 @@ impl Queue {
 			.flags()
 			.intersects(SubmissionRingFlag::CqOverflow | SubmissionRingFlag::TaskRun)
 	}
+
+	fn needs_task_run(&self) -> bool {
+		self.submission
+			.flags()
+			.intersects(SubmissionRingFlag::TaskRun)
+	}
+}
+
+/// The features detected for the kernel, minus any features disabled by the
+/// user with [`EngineOptions::disabled`]
+struct Features {
+	inner: IoRingFeatures,
+	disabled: BitFlags<EngineFeature>
+}
+
+impl Features {
+	fn opcode_supported(&self, op: OpCode) -> bool {
+		let disabled = match op {
+			OpCode::Socket => self.disabled.intersects(EngineFeature::SocketOp),
+			OpCode::Close => self.disabled.intersects(EngineFeature::CloseOp),
+			_ => false
+		};
+
+		!disabled && self.inner.opcode_supported(op)
+	}
+
+	fn feature_supported(&self, feature: Feature) -> bool {
+		let disabled = match feature {
+			Feature::ExtArg => self.disabled.intersects(EngineFeature::ExtArg),
+			_ => false
+		};
+
+		!disabled && self.inner.feature_supported(feature)
+	}
+
+	fn setup_flag_supported(&self, flag: SetupFlag) -> bool {
+		let disabled = match flag {
+			SetupFlag::SubmitAll => self.disabled.intersects(EngineFeature::SubmitAll),
+			_ => false
+		};
+
+		!disabled && self.inner.setup_flag_supported(flag)
+	}
 }
 
-fn create_io_uring() -> Result<(IoRingFeatures, OwnedFd, Parameters)> {
+/// Warnings about the kernel's support, which are only logged the first time
+/// they apply in the process, so that creating many runtimes does not flood
+/// the log
+#[derive(Clone, Copy)]
+enum SetupWarning {
+	UnsupportedOp(OpCode),
+	Disabled(BitFlags<EngineFeature>),
+	CompatibilityMode
+}
+
+impl SetupWarning {
+	/// Returns `true` if this warning was not logged before
+	fn first(self) -> bool {
+		/* one bit for each of the 256 op codes */
+		static UNSUPPORTED_OPS: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+		static DISABLED: AtomicU32 = AtomicU32::new(0);
+		static COMPATIBILITY_MODE: AtomicU32 = AtomicU32::new(0);
+
+		match self {
+			Self::UnsupportedOp(op) => {
+				let op = op as u8;
+
+				#[allow(clippy::arithmetic_side_effects)]
+				let bit = 1u64 << (op % 64);
+
+				UNSUPPORTED_OPS[usize::from(op / 64)].fetch_or(bit, Ordering::Relaxed) & bit == 0
+			}
+
+			Self::Disabled(features) => {
+				let bits = features.bits();
+
+				DISABLED.fetch_or(bits, Ordering::Relaxed) & bits != bits
+			}
+
+			Self::CompatibilityMode => COMPATIBILITY_MODE.swap(1, Ordering::Relaxed) == 0
+		}
+	}
+}
+
+fn create_io_uring(options: &EngineOptions) -> Result<(Features, OwnedFd, Parameters, bool)> {
 	struct IoUringSetup {}
 
 	let ring = IoUringSetup {};
 
 	let features = match io_uring_detect_features()? {
-		Some(features) => features,
+		Some(inner) => Features { inner, disabled: options.disabled },
 		None => {
 			error!(target: &ring,
 				"== Failed to setup io_uring.\n\
@@ -341,11 +540,14 @@ fn create_io_uring() -> Result<(IoRingFeatures, OwnedFd, Parameters)> {
 		),
 		(OpCode::FileSync, Some("files"), None),
 		(OpCode::Statx, Some("files"), None),
+		(OpCode::FileAdvise, Some("file prefetching"), None),
 		(OpCode::PollAdd, None, None)
 	];
 
+	let warn = |warning: SetupWarning| !options.quiet && warning.first();
+
 	for (op, feature, message) in &ops {
-		if !features.opcode_supported(*op) {
+		if !features.opcode_supported(*op) && warn(SetupWarning::UnsupportedOp(*op)) {
 			let feature = feature
 				.map(|feat| format!("(like {})", feat))
 				.unwrap_or(String::new());
@@ -364,6 +566,14 @@ fn create_io_uring() -> Result<(IoRingFeatures, OwnedFd, Parameters)> {
 		}
 	}
 
+	if !options.disabled.is_empty() && warn(SetupWarning::Disabled(options.disabled)) {
+		warn!(
+			target: &ring,
+			"== Features {:?} are disabled by the runtime options",
+			options.disabled
+		);
+	}
+
 	let mut setup_flags = BitFlags::default();
 	let mut params = Parameters::default();
 
@@ -377,21 +587,32 @@ fn create_io_uring() -> Result<(IoRingFeatures, OwnedFd, Parameters)> {
 		}
 	}
 
-	params.sq_entries = 0x100;
-	params.cq_entries = 0x2000;
+	/* passthrough commands, such as NVMe's, need the larger entries */
+	#[cfg(feature = "uring-cmd")]
+	for flag in [SetupFlag::SubmissionEntryWide, SetupFlag::CompletionEntryWide] {
+		if features.setup_flag_supported(flag) {
+			setup_flags |= flag;
+		}
+	}
+
+	params.sq_entries = options.sq_entries;
+	params.cq_entries = options.cq_entries;
 	params.set_flags(setup_flags);
 
 	if !setup_flags.intersects(SetupFlag::Clamp) {
 		params.cq_entries = 0;
 	}
 
-	if !setup_flags.contains(flags) || !features.feature_supported(Feature::ExtArg) {
+	let compatibility_mode =
+		!setup_flags.contains(flags) || !features.feature_supported(Feature::ExtArg);
+
+	if compatibility_mode && warn(SetupWarning::CompatibilityMode) {
 		warn!(
 			target: &ring,
 			"== Running in compatibility mode on an estimated linux kernel version of {}.\n\
 			:: The preferred version is atleast 6.1. Some features may not be available.\n\
 			:: Performance may be degraded.",
-			features.version()
+			features.inner.version()
 		);
 	}
 
@@ -404,7 +625,7 @@ fn create_io_uring() -> Result<(IoRingFeatures, OwnedFd, Parameters)> {
 				params.cq_entries
 			);
 
-			Ok((features, fd, params))
+			Ok((features, fd, params, compatibility_mode))
 		}
 
 		Err(err) => {
@@ -429,7 +650,8 @@ pub struct IoUring {
 	queue: Queue,
 	to_complete: Cell<u64>,
 
-	features: IoRingFeatures,
+	features: Features,
+	compatibility_mode: bool,
 
 	expected_wakes: Cell<usize>,
 	wake_queue: Mutex<VecDeque<ReqPtr<()>>>,
@@ -437,7 +659,26 @@ pub struct IoUring {
 	event_fd: EventFd,
 	event_request: Request<isize>,
 
-	thread_pool: ThreadPool
+	thread_pool: ThreadPool,
+
+	task_run: TaskRunPolicy,
+	enters: Cell<u64>,
+	taskrun_enters: Cell<u64>,
+
+	spin_max: u64,
+	spin_budget: Cell<u64>,
+	spin_hits: Cell<u64>,
+
+	/// Where to store the second half of the completion of each pending
+	/// passthrough command, keyed by user data
+	#[cfg(feature = "uring-cmd")]
+	cmd_extra: RefCell<HashMap<u64, MutPtr<[u64; 2]>>>,
+
+	#[cfg(feature = "test-util")]
+	schedule: ScheduleOrder,
+
+	#[cfg(feature = "test-util")]
+	random: Cell<u64>
 }
 
 static NO_OP: Request<isize> = Request::no_op();
@@ -474,8 +715,7 @@ impl IoUring {
 		loop {
 			const MAX_RESUME: usize = 4;
 
-			#[allow(clippy::unwrap_used)]
-			let mut queue = this.wake_queue.lock().unwrap();
+			let mut queue = this.wake_queue.lock().unwrap_or_else(PoisonError::into_inner);
 
 			if queue.is_empty() {
 				/* finish up reading the event fd with the lock held. resuming woken tasks
@@ -495,7 +735,9 @@ impl IoUring {
 
 			drop(queue);
 
-			trace!(target: this, ">> {} Wakes", amount);
+			if trace_enabled(TraceSubsystem::Engine) {
+				trace!(target: this, ">> {} Wakes", amount);
+			}
 
 			/* we expect completing the requests to be costly, so we don't hold the lock */
 			for request in requests.iter().take(amount) {
@@ -510,16 +752,22 @@ impl IoUring {
 		#[allow(clippy::arithmetic_side_effects)]
 		let wakes = this.expected_wakes.update(|count| count - woken);
 
-		trace!(target: this, "== Woke up {} tasks, {} more expected", woken, wakes);
+		if trace_enabled(TraceSubsystem::Engine) {
+			trace!(target: this, "== Woke up {} tasks, {} more expected", woken, wakes);
+		}
 
 		if wakes != 0 {
 			this.poll_wake();
 		}
 	}
 
-	pub fn new() -> Result<Self> {
-		let thread_pool = ThreadPool::new_with_default_count()?;
-		let (features, ring_fd, params) = create_io_uring()?;
+	pub fn new(options: &EngineOptions) -> Result<Self> {
+		let thread_pool = match options.threads {
+			Some(threads) => ThreadPool::new(threads)?,
+			None => ThreadPool::new_with_default_count()?
+		};
+
+		let (features, ring_fd, params, compatibility_mode) = create_io_uring(options)?;
 		let rings = Rings::new(ring_fd.as_fd(), &params)?;
 
 		/* Safety: params was just initialized by io_uring_setup */
@@ -527,6 +775,7 @@ impl IoUring {
 
 		Ok(Self {
 			features,
+			compatibility_mode,
 			ring_fd,
 			queue,
 
@@ -540,7 +789,27 @@ impl IoUring {
 			/* Safety: events does not unwind */
 			event_request: unsafe { Request::new(Ptr::null(), Self::process_wake) },
 
-			thread_pool
+			thread_pool,
+
+			task_run: options.task_run,
+			enters: Cell::new(0),
+			taskrun_enters: Cell::new(0),
+
+			spin_max: options.spin,
+			spin_budget: Cell::new(options.spin),
+			spin_hits: Cell::new(0),
+
+			#[cfg(feature = "uring-cmd")]
+			cmd_extra: RefCell::new(HashMap::new()),
+
+			#[cfg(feature = "test-util")]
+			schedule: options.schedule,
+
+			#[cfg(feature = "test-util")]
+			random: Cell::new(match options.schedule {
+				ScheduleOrder::Seeded(seed) => seed,
+				_ => 0
+			})
 		})
 	}
 
@@ -553,7 +822,7 @@ impl IoUring {
 
 		let mut to_submit = self.to_submit.replace(0);
 
-		if to_submit != 0 {
+		if to_submit != 0 && trace_enabled(TraceSubsystem::Engine) {
 			trace!(target: self, "<< {} Operations", to_submit);
 		}
 
@@ -561,6 +830,9 @@ impl IoUring {
 		self.to_complete.update(|count| count + to_submit as u64);
 
 		loop {
+			#[allow(clippy::arithmetic_side_effects)]
+			self.enters.update(|count| count + 1);
+
 			match func(self, to_submit) {
 				#[allow(clippy::arithmetic_side_effects, clippy::cast_sign_loss)]
 				Ok(submitted) => {
@@ -591,8 +863,10 @@ impl IoUring {
 	fn flush(&self) -> Result<()> {
 		let mut flags = BitFlags::<EnterFlag>::default();
 
-		/* we want to flush cqring if possible, but not run any task work */
-		if self.queue.needs_flush() {
+		/* we want to flush cqring if possible, but not run any task work, unless
+		 * the policy asks for it on every enter
+		 */
+		if self.queue.needs_flush() || self.task_run == TaskRunPolicy::EveryEnter {
 			flags |= EnterFlag::GetEvents;
 		}
 
@@ -626,8 +900,7 @@ impl IoUring {
 		 */
 		timeout = timeout.min(1_000_000_000);
 
-		#[allow(clippy::unwrap_used)]
-		let ts = TimeSpec { nanos: timeout.try_into().unwrap(), sec: 0 };
+		let ts = TimeSpec { nanos: timeout.try_into().unwrap_or_default(), sec: 0 };
 
 		let op = Op::timeout(ptr!(&ts), 1, 0);
 
@@ -649,9 +922,72 @@ impl IoUring {
 		Ok(())
 	}
 
+	fn time() -> u64 {
+		time::nanotime(ClockId::Monotonic).expect_nounwind("Failed to read the clock")
+	}
+
+	/// Poll the completion ring before waiting in the kernel, for up to the
+	/// spin budget. The budget doubles when spinning finds completions, and
+	/// halves when it does not, down to a small fraction of the maximum
+	fn spin(&self, timeout: u64) -> Result<Option<(u32, u32)>> {
+		const CLOCK_CHECK_INTERVAL: u32 = 64;
+
+		let budget = self.spin_budget.get();
+		let limit = budget.min(timeout);
+
+		if limit == 0 || self.to_complete == 0 || self.queue.needs_enter() {
+			return Ok(None);
+		}
+
+		/* operations must be submitted before they can complete */
+		if self.to_submit != 0 {
+			self.flush()?;
+		}
+
+		let start = Self::time();
+		let mut spins = 0u32;
+
+		loop {
+			let ring = self.queue.completion.read_ring();
+
+			if ring.0 != ring.1 {
+				self.spin_budget
+					.set(budget.saturating_mul(2).min(self.spin_max));
+
+				#[allow(clippy::arithmetic_side_effects)]
+				self.spin_hits.update(|count| count + 1);
+
+				return Ok(Some(ring));
+			}
+
+			/* completions are waiting on task work, which needs an enter */
+			if self.queue.needs_enter() {
+				break;
+			}
+
+			spin_loop();
+			spins = spins.wrapping_add(1);
+
+			if spins % CLOCK_CHECK_INTERVAL == 0 && Self::time().saturating_sub(start) >= limit {
+				break;
+			}
+		}
+
+		self.spin_budget
+			.set((budget / 2).max(self.spin_max / u64::from(CLOCK_CHECK_INTERVAL)));
+
+		Ok(None)
+	}
+
 	fn submit_and_wait(&self, timeout: u64) -> Result<(u32, u32)> {
 		let wait = timeout != 0;
 
+		if wait && self.spin_max != 0 {
+			if let Some(ring) = self.spin(timeout)? {
+				return Ok(ring);
+			}
+		}
+
 		if unlikely(self.to_submit == 0) {
 			let ring = self.queue.completion.read_ring();
 
@@ -664,6 +1000,11 @@ impl IoUring {
 				/* no pending completions, no submissions, nothing to wait for, nothing to */
 				return Ok(ring);
 			}
+
+			if !wait && self.queue.needs_task_run() {
+				#[allow(clippy::arithmetic_side_effects)]
+				self.taskrun_enters.update(|count| count + 1);
+			}
 		}
 
 		if likely(self.features.feature_supported(Feature::ExtArg)) {
@@ -688,6 +1029,27 @@ impl IoUring {
 		Ok(self.queue.completion.read_ring())
 	}
 
+	pub fn feature_supported(&self, feature: EngineFeature) -> bool {
+		match feature {
+			EngineFeature::ExtArg => self.features.feature_supported(Feature::ExtArg),
+			EngineFeature::SocketOp => self.features.opcode_supported(OpCode::Socket),
+			EngineFeature::CloseOp => self.features.opcode_supported(OpCode::Close),
+			EngineFeature::SubmitAll => self.features.setup_flag_supported(SetupFlag::SubmitAll)
+		}
+	}
+
+	pub const fn is_compatibility_mode(&self) -> bool {
+		self.compatibility_mode
+	}
+
+	pub fn stats(&self) -> EngineStats {
+		EngineStats {
+			enters: self.enters.get(),
+			taskrun_enters: self.taskrun_enters.get(),
+			spin_hits: self.spin_hits.get()
+		}
+	}
+
 	#[inline(always)]
 	fn run_events(&self, (mut head, mut tail): (u32, u32)) {
 		let mask = self.queue.completion.mask;
@@ -697,16 +1059,29 @@ impl IoUring {
 			return;
 		}
 
-		trace!(target: self, ">> {} Completions", count);
+		if trace_enabled(TraceSubsystem::Engine) {
+			trace!(target: self, ">> {} Completions", count);
+		}
 
 		#[allow(clippy::arithmetic_side_effects)]
 		self.to_complete.update(|complete| complete - count as u64);
 
+		#[cfg(feature = "test-util")]
+		if self.schedule != ScheduleOrder::Default {
+			self.run_events_ordered(head, tail);
+
+			return;
+		}
+
 		let complete = |index, update_head: Option<&mut u32>| {
 			let CompletionEntry { user_data, result, .. } =
 				/* Safety: masked */
 				unsafe { self.queue.completion.read(index & mask) };
 
+			/* must be read before the entry is handed back to the kernel */
+			#[cfg(feature = "uring-cmd")]
+			self.complete_cmd(user_data, index & mask);
+
 			if let Some(head) = update_head {
 				/*
 				 * more requests may be queued in callback, so
@@ -742,6 +1117,82 @@ impl IoUring {
 			.store(head.wrapping_add(1), Ordering::Release);
 	}
 
+	/// Copy the second half of the completion at `index` to the passthrough
+	/// command that submitted it, if any
+	#[cfg(feature = "uring-cmd")]
+	fn complete_cmd(&self, user_data: u64, index: u32) {
+		let mut pending = self.cmd_extra.borrow_mut();
+
+		if likely(pending.is_empty()) {
+			return;
+		}
+
+		let Some(extra) = pending.remove(&user_data) else {
+			return;
+		};
+
+		/* Safety: masked, and commands are only submitted with wide entries */
+		let words = unsafe { self.queue.completion.read_extra(index) };
+
+		/* Safety: the command's request is still pending, so extra is valid */
+		unsafe { ptr!(*extra) = words };
+	}
+
+	/// splitmix64, which is good enough for shuffling completions
+	#[cfg(feature = "test-util")]
+	fn next_random(&self) -> u64 {
+		let state = self.random.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+		self.random.set(state);
+
+		let mut z = state;
+
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	/// Complete the events in a deterministic order
+	#[cfg(feature = "test-util")]
+	#[cold]
+	#[inline(never)]
+	fn run_events_ordered(&self, mut head: u32, tail: u32) {
+		let mask = self.queue.completion.mask;
+		let mut entries = Vec::new();
+
+		while head != tail {
+			let CompletionEntry { user_data, result, .. } =
+				/* Safety: masked */
+				unsafe { self.queue.completion.read(head & mask) };
+
+			#[cfg(feature = "uring-cmd")]
+			self.complete_cmd(user_data, head & mask);
+
+			entries.push((user_data, result));
+			head = head.wrapping_add(1);
+		}
+
+		/* all entries are copied out, so the ring can be reused by callbacks */
+		self.queue.completion.khead.store(tail, Ordering::Release);
+
+		if let ScheduleOrder::Seeded(_) = self.schedule {
+			#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+			for i in (1..entries.len()).rev() {
+				let j = (self.next_random() % (i as u64 + 1)) as usize;
+
+				entries.swap(i, j);
+			}
+		}
+
+		for (user_data, result) in entries {
+			#[allow(clippy::cast_possible_truncation)]
+			let request = Ptr::from_addr(user_data as usize);
+
+			/* Safety: complete the future */
+			unsafe { Request::complete(request, result as isize) };
+		}
+	}
+
 	#[cold]
 	#[inline(never)]
 	fn push_flush(&self) {
@@ -752,7 +1203,11 @@ impl IoUring {
 	#[inline(always)]
 	fn push(&self, request: SubmissionEntry) {
 		self.queue.submission.push(request);
+		self.pushed();
+	}
 
+	#[inline(always)]
+	fn pushed(&self) {
 		#[allow(clippy::arithmetic_side_effects)]
 		self.to_submit.update(|count| count + 1);
 
@@ -817,8 +1272,7 @@ unsafe impl EngineImpl for IoUring {
 	}
 
 	fn wake(&self, request: ReqPtr<()>) -> Result<()> {
-		#[allow(clippy::unwrap_used)]
-		let mut queue = self.wake_queue.lock().unwrap();
+		let mut queue = self.wake_queue.lock().unwrap_or_else(PoisonError::into_inner);
 		let wake = queue.is_empty();
 
 		queue.push_back(request);
@@ -844,7 +1298,9 @@ unsafe impl EngineImpl for IoUring {
 
 	unsafe fn cancel(&self, request: ReqPtr<()>) -> Result<()> {
 		#[cfg(feature = "tracing")]
-		trace!(target: self, "## cancel(request = {:?})", request);
+		if trace_enabled(TraceSubsystem::Engine) {
+			trace!(target: self, "## cancel(request = {:?})", request);
+		}
 
 		let mut op = Op::cancel(0);
 
@@ -997,9 +1453,55 @@ unsafe impl EngineImpl for IoUring {
 		self.start_async(op, request)
 	}
 
+	unsafe fn fadvise(
+		&self, fd: RawFd, offset: u64, len: u32, advice: u32, request: ReqPtr<isize>
+	) -> Option<isize> {
+		let op = Op::fadvise(fd, offset, len, advice);
+
+		self.start_async(op, request)
+	}
+
 	unsafe fn poll(&self, fd: RawFd, mask: u32, request: ReqPtr<isize>) -> Option<isize> {
 		let op = Op::poll(fd, mask);
 
 		self.start_async(op, request)
 	}
+
+	#[cfg(feature = "uring-cmd")]
+	fn uring_cmd_kind(&self) -> OperationKind {
+		OperationKind::Async
+	}
+
+	#[cfg(feature = "uring-cmd")]
+	unsafe fn uring_cmd(
+		&self, fd: RawFd, cmd_op: u32, cmd: Ptr<()>, len: usize, extra: MutPtr<[u64; 2]>,
+		request: ReqPtr<isize>
+	) -> Option<isize> {
+		if unlikely(
+			!self.features.opcode_supported(OpCode::UringCmd) ||
+				self.queue.submission.shift == 0 ||
+				self.queue.completion.shift == 0
+		) {
+			return Some(SyncEngine::sync_result(Err(OsError::NoSys)));
+		}
+
+		if unlikely(len > URING_CMD_LEN) {
+			return Some(SyncEngine::sync_result(Err(OsError::Inval)));
+		}
+
+		/* Safety: guaranteed by caller */
+		let cmd = unsafe { std::slice::from_raw_parts(cmd.as_ptr().cast::<u8>(), len) };
+		let mut op = Op::uring_cmd(fd, cmd_op);
+
+		op.user_data = request.addr() as u64;
+
+		self.cmd_extra.borrow_mut().insert(op.user_data, extra);
+
+		/* Safety: the ring has wide entries, and the command fits */
+		unsafe { self.queue.submission.push_cmd(op, cmd) };
+
+		self.pushed();
+
+		None
+	}
 }
@@ -6,6 +6,8 @@ use xx_core::os::io_uring::*;
 use xx_core::os::stat::*;
 use xx_core::os::time::*;
 
+#[cfg(feature = "bench-internal")]
+pub mod bench;
 mod engine;
 mod op;
 
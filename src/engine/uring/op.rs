@@ -1,8 +1,4 @@
-#![allow(
-	clippy::unwrap_used,
-	clippy::cast_sign_loss,
-	clippy::cast_possible_wrap
-)]
+#![allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
 
 use xx_core::macros::const_assert;
 use xx_core::os::epoll::*;
@@ -555,8 +551,7 @@ impl Op {
 		Self::sendto_zc(fd, buf, len, flags, Ptr::null(), 0, buf_index)
 	}
 
-	/// # Panics
-	/// if `addrlen` is larger than `u16::MAX`
+	/// An `addrlen` larger than `u16::MAX` is clamped, which the kernel rejects
 	pub fn sendto_zc(
 		fd: i32, buf: usize, len: u32, flags: u32, addr: Ptr<()>, addrlen: u32, buf_index: u16
 	) -> SubmissionEntry {
@@ -566,7 +561,7 @@ impl Op {
 		entry.addr.addr = buf as u64;
 		entry.len = len;
 		entry.off.addr = addr.addr() as u64;
-		entry.file.addr_len.len = addrlen.try_into().unwrap();
+		entry.file.addr_len.len = addrlen.try_into().unwrap_or(u16::MAX);
 		entry.file.addr_len.pad = [0u16; 1];
 		entry.rw_flags = flags;
 		entry.addr3.addr = 0;
@@ -685,4 +680,26 @@ impl Op {
 
 		entry
 	}
+
+	/// The command itself is not part of the entry. It is copied into the
+	/// trailing command area when the entry is pushed, see
+	/// [`URING_CMD_OFFSET`]
+	#[cfg(feature = "uring-cmd")]
+	pub fn uring_cmd(fd: i32, cmd_op: u32) -> SubmissionEntry {
+		let mut entry = new_op(OpCode::UringCmd);
+
+		/* cmd_op occupies the low half of the offset */
+		rw_fixed(&mut entry, fd, 0, 0, cmd_op.into(), 0, 0);
+
+		entry
+	}
 }
+
+/// The byte offset of the command area within a 128 byte submission entry.
+/// The area overlaps `addr3` and spans the rest of the wide entry
+#[cfg(feature = "uring-cmd")]
+pub const URING_CMD_OFFSET: usize = 48;
+
+/// The size of the command area within a 128 byte submission entry
+#[cfg(feature = "uring-cmd")]
+pub const URING_CMD_LEN: usize = 80;
@@ -1,19 +1,26 @@
-#![allow(clippy::unwrap_used)]
 //! The implementation for [`File`]
 
 use std::io::SeekFrom;
+use std::mem::MaybeUninit;
+use std::ops::Range;
 use std::path::Path;
 
 use xx_core::os::fcntl::*;
 use xx_core::os::stat::*;
 
 use super::*;
+use crate::ops::fd_budget::FdRelease;
 use crate::io::{read, *};
 
+fn invalid_offset() -> Error {
+	fmt_error!("File offset overflow")
+}
+
 /// A file handle for reading and writing files.
 pub struct File {
 	fd: OwnedFd,
-	offset: u64
+	offset: u64,
+	release: FdRelease
 }
 
 #[asynchronous]
@@ -21,19 +28,19 @@ impl File {
 	/// Open the file specified by `path` for reading
 	#[allow(clippy::impl_trait_in_params)]
 	pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
-		Ok(Self {
-			fd: open(path.as_ref(), BitFlags::default(), 0).await?,
-			offset: 0
-		})
+		Ok(open(path.as_ref(), BitFlags::default(), 0).await?.into())
 	}
 
 	/// Open and possibly create the file specified by `path` for writing
 	#[allow(clippy::impl_trait_in_params)]
 	pub async fn create(path: impl AsRef<Path>) -> Result<Self> {
-		Ok(Self {
-			fd: open(path.as_ref(), OpenFlag::Create | OpenFlag::WriteOnly, 0).await?,
-			offset: 0
-		})
+		Ok(open(path.as_ref(), OpenFlag::Create | OpenFlag::WriteOnly, 0)
+			.await?
+			.into())
+	}
+
+	fn signed_offset(&self) -> Result<i64> {
+		self.offset.try_into().map_err(|_| invalid_offset())
 	}
 
 	/// Read from the file into the buffer `buf`
@@ -47,7 +54,7 @@ impl File {
 	pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
 		read_into!(buf);
 
-		let read = read(self.fd.as_fd(), buf, self.offset.try_into().unwrap()).await?;
+		let read = read(self.fd.as_fd(), buf, self.signed_offset()?).await?;
 		let read = check_interrupt_if_zero(read).await?;
 
 		#[allow(clippy::arithmetic_side_effects)]
@@ -56,6 +63,32 @@ impl File {
 		Ok(read)
 	}
 
+	/// Read from the file into the possibly uninitialized buffer `buf`,
+	/// avoiding the need to zero it beforehand
+	///
+	/// Returns the part of the buffer that was read into.
+	///
+	/// # Cancel safety.
+	///
+	/// This function is cancel safe. Advance the buffer by the number of bytes
+	/// read and resume by calling this function with the new buffer.
+	pub async fn read_uninit<'buf>(
+		&mut self, buf: &'buf mut [MaybeUninit<u8>]
+	) -> Result<&'buf mut [u8]> {
+		if buf.is_empty() {
+			return Ok(&mut []);
+		}
+
+		let read = read_uninit(self.fd.as_fd(), buf, self.signed_offset()?).await?;
+		let read = check_interrupt_if_zero(read).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.offset += read as u64);
+
+		/* Safety: the kernel initialized `read` bytes */
+		Ok(unsafe { assume_init_prefix(buf, read) })
+	}
+
 	/// Write to the file from the buffer `buf`
 	///
 	/// Returns the number of bytes written.
@@ -67,7 +100,7 @@ impl File {
 	pub async fn write(&mut self, buf: &[u8]) -> Result<usize> {
 		write_from!(buf);
 
-		let wrote = write(self.fd.as_fd(), buf, self.offset.try_into().unwrap()).await?;
+		let wrote = write(self.fd.as_fd(), buf, self.signed_offset()?).await?;
 		let wrote = check_interrupt_if_zero(wrote).await?;
 
 		#[allow(clippy::arithmetic_side_effects)]
@@ -96,10 +129,11 @@ impl File {
 	/// function again with the same arguments if it previously failed.
 	pub async fn seek(&mut self, seek: SeekFrom) -> Result<u64> {
 		self.offset = match seek {
-			SeekFrom::Start(pos) => pos,
-			SeekFrom::Current(rel) => self.offset.checked_add_signed(rel).unwrap(),
-			SeekFrom::End(rel) => self.stream_len().await?.checked_add_signed(rel).unwrap()
-		};
+			SeekFrom::Start(pos) => Some(pos),
+			SeekFrom::Current(rel) => self.offset.checked_add_signed(rel),
+			SeekFrom::End(rel) => self.stream_len().await?.checked_add_signed(rel)
+		}
+		.ok_or_else(invalid_offset)?;
 
 		Ok(self.offset)
 	}
@@ -107,38 +141,135 @@ impl File {
 	/// Close the file asynchronously. Dropping this `File` will close the file
 	/// synchronously, which may not be ideal.
 	pub async fn close(self) -> Result<()> {
+		drop(self.release);
 		close(self.fd).await
 	}
 
+	#[must_use]
+	pub fn fd(&self) -> BorrowedFd<'_> {
+		self.fd.as_fd()
+	}
+
+	/// Label this file with `name` in trace output. See
+	/// [`io::set_trace_name`]
+	pub fn set_trace_name(&self, name: &str) {
+		set_trace_name(self.fd.as_fd(), Some(name));
+	}
+
 	/// Get the current position in the file
 	#[must_use]
 	pub const fn pos(&self) -> u64 {
 		self.offset
 	}
 
-	/// Get the metadata for this file. See [`Metadata`] for more information
+	/// Tell the kernel to start reading the byte `ranges` of this file into
+	/// the page cache. If `ranges` is empty, the whole file is prefetched.
+	///
+	/// If `populate` is set, the ranges are also read through the ring, which
+	/// fills the page cache even on file systems that ignore the advice. The
+	/// data read is discarded.
+	///
+	/// Returns once the kernel has been told to prefetch, or when all of the
+	/// data has been read if `populate` is set.
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	pub async fn prefetch(&self, ranges: &[Range<u64>], populate: bool) -> Result<()> {
+		const CHUNK_SIZE: usize = 0x20000;
+
+		let whole = [0..u64::MAX];
+		let ranges = if ranges.is_empty() {
+			/* a length of zero advises until the end of the file */
+			fadvise(self.fd.as_fd(), 0, 0, Advice::WillNeed).await?;
+
+			&whole[..]
+		} else {
+			for range in ranges {
+				let mut offset = range.start;
+
+				while offset < range.end {
+					#[allow(clippy::arithmetic_side_effects)]
+					let len = u32::try_from(range.end - offset).unwrap_or(u32::MAX);
+
+					fadvise(self.fd.as_fd(), offset, len, Advice::WillNeed).await?;
+
+					#[allow(clippy::arithmetic_side_effects)]
+					(offset += u64::from(len));
+				}
+			}
+
+			ranges
+		};
+
+		if !populate {
+			return Ok(());
+		}
+
+		let mut buf = vec![0; CHUNK_SIZE];
+
+		for range in ranges {
+			let mut offset = range.start;
+
+			while offset < range.end {
+				#[allow(clippy::arithmetic_side_effects)]
+				let remaining = range.end - offset;
+				let len = usize::try_from(remaining).map_or(CHUNK_SIZE, |len| len.min(CHUNK_SIZE));
+				let offset_arg = offset.try_into().map_err(|_| invalid_offset())?;
+				let read = read(self.fd.as_fd(), &mut buf[0..len], offset_arg).await?;
+
+				if read == 0 {
+					break;
+				}
+
+				#[allow(clippy::arithmetic_side_effects)]
+				(offset += read as u64);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Get the metadata for this file, with the fields in
+	/// [`StatxPreset::BASIC`]. See [`Metadata`] for more information
 	pub async fn metadata(&self) -> Result<Metadata> {
+		self.metadata_with(StatxPreset::BASIC).await
+	}
+
+	/// Get the metadata for this file, requesting the fields in `mask`
+	pub async fn metadata_with(&self, mask: BitFlags<StatxMask>) -> Result<Metadata> {
 		let mut statx = Statx::default();
 
-		io::statx_fd(
-			self.fd.as_fd(),
-			BitFlags::default(),
-			BitFlags::default(),
-			&mut statx
-		)
-		.await?;
+		io::statx_fd(self.fd.as_fd(), BitFlags::default(), mask, &mut statx).await?;
 
 		Ok(Metadata(statx))
 	}
 }
 
 #[asynchronous]
+impl From<OwnedFd> for File {
+	fn from(fd: OwnedFd) -> Self {
+		let release = FdRelease::new(fd.as_fd());
+
+		Self { fd, offset: 0, release }
+	}
+}
+
 impl Read for File {
 	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
 		self.read(buf).await
 	}
 }
 
+#[asynchronous]
+impl ReadUninit for File {
+	async fn read_uninit<'buf>(
+		&mut self, buf: &'buf mut [MaybeUninit<u8>]
+	) -> Result<&'buf mut [u8]> {
+		self.read_uninit(buf).await
+	}
+}
+
 #[asynchronous]
 impl Write for File {
 	async fn write(&mut self, buf: &[u8]) -> Result<usize> {
@@ -157,13 +288,10 @@ impl Seek for File {
 	}
 
 	async fn stream_len(&mut self) -> Result<u64> {
-		let stat = self.metadata().await?.0;
-
-		if stat.mask().intersects(StatxMask::Size) {
-			Ok(stat.size)
-		} else {
-			Err(fmt_error!("Failed to query file size"))
-		}
+		self.metadata_with(StatxPreset::SIZE_ONLY)
+			.await?
+			.try_len()
+			.ok_or_else(|| fmt_error!("Failed to query file size"))
 	}
 
 	fn stream_position_fast(&self) -> bool {
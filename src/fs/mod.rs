@@ -1,42 +1,124 @@
 //! File-system operations.
 
-use std::os::fd::{AsFd, OwnedFd};
+use std::ops::Range;
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use enumflags2::make_bitflags;
 use xx_core::async_std::io::{ReadExt, SeekExt, *};
 use xx_core::error::*;
 use xx_core::os::dirent;
+use xx_core::os::error::OsError;
 use xx_core::os::stat::*;
 
 use super::*;
+use crate::io::ReadUninit;
 
 pub mod file;
 pub mod readdir;
+pub mod tail;
+pub mod virtual_fs;
+pub mod watch;
 
 #[doc(inline)]
-pub use {file::*, readdir::*};
+pub use {file::*, readdir::*, tail::*, virtual_fs::*, watch::*};
 
 /// The type of a file, obtained from a file's [`Metadata`]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct FileType(dirent::FileType);
+pub struct FileType(Option<dirent::FileType>);
 
 impl FileType {
 	/// Returns `true` if this file is a directory
 	#[must_use]
 	pub fn is_dir(&self) -> bool {
-		self.0 == dirent::FileType::Directory
+		self.0 == Some(dirent::FileType::Directory)
 	}
 
 	/// Returns `true` if this file is a regular file
 	#[must_use]
 	pub fn is_file(&self) -> bool {
-		self.0 == dirent::FileType::Regular
+		self.0 == Some(dirent::FileType::Regular)
 	}
 
 	/// Returns `true` if this file is a symlink
 	#[must_use]
 	pub fn is_symlink(&self) -> bool {
-		self.0 == dirent::FileType::Link
+		self.0 == Some(dirent::FileType::Link)
+	}
+
+	/// Returns `true` if the type was not reported, such as by a file system
+	/// that does not fill in the type of directory entries
+	#[must_use]
+	pub const fn is_unknown(&self) -> bool {
+		self.0.is_none()
+	}
+}
+
+/// Common sets of [`StatxMask`] fields to request from `statx`
+#[derive(Clone, Copy, Debug)]
+pub struct StatxPreset;
+
+impl StatxPreset {
+	/// The fields of a traditional `stat`
+	pub const BASIC: BitFlags<StatxMask> = make_bitflags!(StatxMask::{
+		Type | Mode | NLink | Uid | Gid | ATime | MTime | CTime | Ino | Size | Blocks
+	});
+
+	/// All of the fields in [`StatxPreset::BASIC`], and the file creation time
+	pub const ALL: BitFlags<StatxMask> = make_bitflags!(StatxMask::{
+		Type | Mode | NLink | Uid | Gid | ATime | MTime | CTime | Ino | Size | Blocks | BTime
+	});
+
+	/// Only the file size
+	pub const SIZE_ONLY: BitFlags<StatxMask> = make_bitflags!(StatxMask::{ Size });
+}
+
+fn to_system_time(sec: i64, nsec: u32) -> Option<SystemTime> {
+	let offset = Duration::new(sec.unsigned_abs(), nsec);
+
+	if sec >= 0 {
+		UNIX_EPOCH.checked_add(offset)
+	} else {
+		/* nsec counts forwards from sec */
+		UNIX_EPOCH
+			.checked_sub(Duration::from_secs(sec.unsigned_abs()))?
+			.checked_add(Duration::from_nanos(nsec.into()))
+	}
+}
+
+/// The timestamps of a file. Each time is `None` if it was not requested or
+/// is not supported by the file system
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FileTimes {
+	/// The last access time
+	pub accessed: Option<SystemTime>,
+
+	/// The last modification time
+	pub modified: Option<SystemTime>,
+
+	/// The last status change time
+	pub changed: Option<SystemTime>,
+
+	/// The creation time
+	pub created: Option<SystemTime>
+}
+
+/// The permission bits of a file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Permissions(u32);
+
+impl Permissions {
+	/// The permission bits, including the setuid, setgid and sticky bits
+	#[must_use]
+	pub const fn mode(&self) -> u32 {
+		self.0 & 0o7777
+	}
+
+	/// Returns `true` if none of the write bits are set
+	#[must_use]
+	pub const fn readonly(&self) -> bool {
+		self.0 & 0o222 == 0
 	}
 }
 
@@ -45,32 +127,265 @@ impl FileType {
 #[derive(Clone)]
 pub struct Metadata(Statx);
 
-#[allow(clippy::len_without_is_empty, clippy::missing_panics_doc)]
+#[allow(clippy::len_without_is_empty)]
 impl Metadata {
+	fn has(&self, mask: BitFlags<StatxMask>) -> bool {
+		self.0.mask().contains(mask)
+	}
+
+	/// The fields that were filled in by the kernel. Accessors for fields that
+	/// are not in the mask return `None`, unless documented otherwise
+	#[must_use]
+	pub fn mask(&self) -> BitFlags<StatxMask> {
+		self.0.mask()
+	}
+
 	/// Get the type of this file
-	#[allow(clippy::unwrap_used)]
 	#[must_use]
 	pub fn file_type(&self) -> FileType {
-		FileType(self.0.file_type().unwrap())
+		FileType(self.0.file_type())
 	}
 
 	/// Get the file length
+	///
+	/// # Panics
+	/// If the size was not requested. See [`Metadata::try_len`]
 	#[must_use]
+	#[cfg_attr(
+		feature = "no-panic",
+		deprecated(note = "panics if the size was not requested, use `try_len` instead")
+	)]
 	pub fn len(&self) -> u64 {
-		assert!(self.0.mask().intersects(StatxMask::Size));
+		assert!(self.has(StatxMask::Size.into()), "The size was not requested");
 
 		self.0.size
 	}
+
+	/// Get the file length, or `None` if the size was not requested
+	#[must_use]
+	pub fn try_len(&self) -> Option<u64> {
+		self.has(StatxMask::Size.into()).then_some(self.0.size)
+	}
+
+	/// Get the inode number of the file, or `None` if it was not requested
+	#[must_use]
+	pub fn ino(&self) -> Option<u64> {
+		self.has(StatxMask::Ino.into()).then_some(self.0.ino)
+	}
+
+	/// Get the permissions of the file, or `None` if the mode was not
+	/// requested
+	#[must_use]
+	pub fn permissions(&self) -> Option<Permissions> {
+		self.has(StatxMask::Mode.into())
+			.then(|| Permissions(self.0.mode.into()))
+	}
+
+	/// Get the timestamps of the file
+	#[must_use]
+	pub fn times(&self) -> FileTimes {
+		let time = |mask: StatxMask, time: &StatxTimestamp| {
+			self.has(mask.into())
+				.then(|| to_system_time(time.sec, time.nsec))
+				.flatten()
+		};
+
+		FileTimes {
+			accessed: time(StatxMask::ATime, &self.0.atime),
+			modified: time(StatxMask::MTime, &self.0.mtime),
+			changed: time(StatxMask::CTime, &self.0.ctime),
+			created: time(StatxMask::BTime, &self.0.btime)
+		}
+	}
+
+	/// Get the last modification time, or `None` if it was not requested
+	#[must_use]
+	pub fn modified(&self) -> Option<SystemTime> {
+		self.times().modified
+	}
+}
+
+/// Get the metadata for the file at `path`, requesting the fields in `mask`.
+/// Symlinks are followed. See [`StatxPreset`] for common masks
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn metadata_with(path: impl AsRef<Path>, mask: BitFlags<StatxMask>) -> Result<Metadata> {
+	let mut statx = Statx::default();
+
+	io::statx(None, path, BitFlags::default(), mask, &mut statx).await?;
+
+	Ok(Metadata(statx))
+}
+
+/// Get the metadata for the file at `path`, with the fields in
+/// [`StatxPreset::BASIC`]
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn metadata(path: impl AsRef<Path>) -> Result<Metadata> {
+	metadata_with(path, StatxPreset::BASIC).await
+}
+
+/// Returns `Ok(true)` if the file at `path` exists, `Ok(false)` if it does
+/// not, or an error if the existence could not be determined, such as when
+/// a parent directory cannot be searched. Symlinks are followed.
+///
+/// Unlike [`Path::exists`], this function does not block the runtime thread
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn try_exists(path: impl AsRef<Path>) -> Result<bool> {
+	let mut statx = Statx::default();
+
+	match io::statx(None, path, BitFlags::default(), BitFlags::default(), &mut statx).await {
+		Ok(()) => Ok(true),
+		Err(err) if err.os_error() == Some(OsError::NoEnt) => Ok(false),
+		Err(err) => Err(err)
+	}
+}
+
+/// Check whether the calling process has the permissions in `mode` for the
+/// file at `path`. Returns `Ok(false)` if access is denied, or an error if
+/// the file does not exist or the check failed. See [`io::access`] for more
+/// information.
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn access(path: impl AsRef<Path>, mode: BitFlags<io::AccessMode>) -> Result<bool> {
+	match io::access(None, path, mode, BitFlags::default()).await {
+		Ok(()) => Ok(true),
+		Err(err) if matches!(err.os_error(), Some(OsError::Access | OsError::ReadOnlyFs)) => Ok(false),
+		Err(err) => Err(err)
+	}
+}
+
+/// The amount of data read at once by [`read_to_end_resumable`]
+const READ_CHUNK_SIZE: usize = 0x10000;
+
+/// The progress of a [`read_to_end_resumable`] call, preserved across
+/// interruptions
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReadProgress {
+	read: usize,
+	limit: Option<usize>,
+	done: bool
+}
+
+impl ReadProgress {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self { read: 0, limit: None, done: false }
+	}
+
+	/// Fail the read once more than `limit` bytes have been read
+	#[must_use]
+	pub const fn with_limit(limit: usize) -> Self {
+		Self { read: 0, limit: Some(limit), done: false }
+	}
+
+	/// The number of bytes appended to the buffer so far
+	#[must_use]
+	pub const fn read(&self) -> usize {
+		self.read
+	}
+
+	/// Returns `true` if the end of the stream was reached
+	#[must_use]
+	pub const fn is_done(&self) -> bool {
+		self.done
+	}
+}
+
+/// Read all data from `reader` until EOF, appending it to the buffer `vec`.
+/// The data is read straight into the spare capacity of `vec`, without zeroing
+/// it first. The number of bytes appended is recorded in `progress`.
+///
+/// Returns the total number of bytes appended across all calls with the same
+/// `progress`.
+///
+/// # Errors
+/// If the read fails, or if more than the limit set with
+/// [`ReadProgress::with_limit`] would be read. In the latter case, `vec`
+/// contains exactly the limit's worth of appended data.
+///
+/// # Cancel safety
+///
+/// This function is cancel safe. Only complete reads are appended to `vec`
+/// and recorded in `progress`. Resume the operation by calling this function
+/// again with the same `vec` and `progress`.
+#[asynchronous]
+pub async fn read_to_end_resumable<R: ReadUninit>(
+	reader: &mut R, vec: &mut Vec<u8>, progress: &mut ReadProgress
+) -> Result<usize> {
+	while !progress.done {
+		/* read one byte past the limit to tell if the stream is too large */
+		#[allow(clippy::arithmetic_side_effects)]
+		let chunk = match progress.limit {
+			Some(limit) => READ_CHUNK_SIZE.min(limit.saturating_sub(progress.read) + 1),
+			None => READ_CHUNK_SIZE
+		};
+
+		let len = vec.len();
+
+		vec.try_reserve(chunk).map_err(|_| ErrorKind::OutOfMemory)?;
+
+		let read = reader
+			.read_uninit(&mut vec.spare_capacity_mut()[0..chunk])
+			.await?
+			.len();
+
+		#[allow(clippy::arithmetic_side_effects)]
+		/* Safety: the reader initialized `read` bytes past the end of the vec */
+		unsafe { vec.set_len(len + read) };
+
+		if read == 0 {
+			progress.done = true;
+
+			break;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let total = progress.read + read;
+
+		if let Some(limit) = progress.limit {
+			if total > limit {
+				#[allow(clippy::arithmetic_side_effects)]
+				vec.truncate(len + limit - progress.read);
+				progress.read = limit;
+
+				return Err(fmt_error!("Stream exceeds the read limit" @ ErrorKind::OutOfMemory));
+			}
+		}
+
+		progress.read = total;
+	}
+
+	Ok(progress.read)
+}
+
+/// Load all the data in the file at `path` into a `Vec<u8>`, failing if the
+/// file is larger than `limit` bytes
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn read_with_limit(path: impl AsRef<Path>, limit: usize) -> Result<Vec<u8>> {
+	let mut file = File::open(path).await?;
+	let mut vec = Vec::new();
+	let mut progress = ReadProgress::with_limit(limit);
+
+	if let Ok(len) = file.stream_len().await {
+		vec.reserve(usize::try_from(len).unwrap_or(limit).min(limit));
+	}
+
+	read_to_end_resumable(&mut file, &mut vec, &mut progress).await?;
+
+	Ok(vec)
 }
 
 /// Read all data from the file at `path`, appending it to the buffer `vec`
 #[asynchronous]
-#[allow(clippy::impl_trait_in_params, clippy::unwrap_used)]
+#[allow(clippy::impl_trait_in_params)]
 pub async fn read_to_end(path: impl AsRef<Path>, vec: &mut Vec<u8>) -> Result<usize> {
 	let mut file = File::open(path).await?;
 
 	if let Ok(len) = file.stream_len().await {
-		vec.reserve(len.try_into().unwrap());
+		vec.reserve(len.try_into().unwrap_or_default());
 	}
 
 	file.read_to_end(vec).await
@@ -93,3 +408,17 @@ pub async fn read(path: impl AsRef<Path>) -> Result<Vec<u8>> {
 pub async fn read_to_string(path: impl AsRef<Path>) -> Result<String> {
 	Ok(String::from_utf8(read(path).await?)?)
 }
+
+/// Tell the kernel to start reading the byte `ranges` of the file at `path`
+/// into the page cache, for latency sensitive startup paths such as loading
+/// indexes or assets. See [`File::prefetch`] for more information.
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn prefetch(
+	path: impl AsRef<Path>, ranges: &[Range<u64>], populate: bool
+) -> Result<()> {
+	let file = File::open(path).await?;
+
+	file.prefetch(ranges, populate).await?;
+	file.close().await
+}
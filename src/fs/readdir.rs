@@ -16,10 +16,12 @@ use xx_core::os::fcntl::*;
 use xx_core::os::stat::*;
 
 use super::*;
+use crate::ops::fd_budget::FdRelease;
 
 struct Dir {
 	path: PathBuf,
-	fd: OwnedFd
+	fd: OwnedFd,
+	_release: FdRelease
 }
 
 /// An entry of the directory
@@ -47,7 +49,8 @@ impl DirEntry {
 		self.ent.ino
 	}
 
-	/// Get the metadata for this file. See [`Metadata`] for more information
+	/// Get the metadata for this file, with the fields in
+	/// [`StatxPreset::BASIC`]. See [`Metadata`] for more information
 	pub async fn metadata(&self) -> Result<Metadata> {
 		let mut statx = Statx::default();
 
@@ -55,7 +58,7 @@ impl DirEntry {
 			Some(self.dir.fd.as_fd()),
 			self.file_name(),
 			BitFlags::default(),
-			BitFlags::default(),
+			StatxPreset::BASIC,
 			&mut statx
 		)
 		.await?;
@@ -63,11 +66,11 @@ impl DirEntry {
 		Ok(Metadata(statx))
 	}
 
-	/// Get the file type
-	#[allow(clippy::unwrap_used, clippy::missing_panics_doc)]
+	/// Get the file type. The type is unknown if the file system does not
+	/// report it, see [`FileType::is_unknown`]
 	#[must_use]
 	pub fn file_type(&self) -> FileType {
-		FileType(self.ent.file_type().unwrap())
+		FileType(self.ent.file_type())
 	}
 }
 
@@ -113,8 +116,9 @@ impl ReadDir {
 				continue;
 			}
 
-			#[allow(clippy::unwrap_used)]
-			let entry = self.entries.next_entry().unwrap();
+			let Some(entry) = self.entries.next_entry() else {
+				continue;
+			};
 
 			if entry.name == c"." || entry.name == c".." {
 				continue;
@@ -193,8 +197,10 @@ pub async fn read_dir(path: impl AsRef<Path>) -> Result<ReadDir> {
 
 	let entries = DirEnts::new_from_block_size(statx.block_size as usize);
 
+	let release = FdRelease::new(fd.as_fd());
+
 	Ok(ReadDir {
-		dir: Arc::new(Dir { path: path.to_owned(), fd }),
+		dir: Arc::new(Dir { path: path.to_owned(), fd, _release: release }),
 		entries
 	})
 }
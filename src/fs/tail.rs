@@ -0,0 +1,244 @@
+//! The implementation for [`tail`]
+
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use xx_core::async_std::AsyncIterator;
+
+use super::*;
+
+/// How often the file is checked for new data and rotation without a
+/// [`Watcher`], by default
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The size of each read from the file
+const TAIL_CHUNK_SIZE: usize = 0x2000;
+
+/// An iterator over the lines appended to a file, following the path across
+/// rotation like `tail -F`. See [`tail`] for more information
+pub struct Tail {
+	path: PathBuf,
+	file: Option<(File, u64)>,
+	partial: Vec<u8>,
+	watcher: Option<Watcher>,
+	interval: Duration
+}
+
+/// Watch the directory containing `path` for changes that may mean there is
+/// new data to read, or that the file was rotated. Returns `None` if inotify
+/// is not available
+fn watch(path: &Path) -> Option<Watcher> {
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir,
+		_ => Path::new(".")
+	};
+
+	let kinds = WatchKind::Modify |
+		WatchKind::Create |
+		WatchKind::Delete |
+		WatchKind::MovedFrom |
+		WatchKind::MovedTo;
+	let mut watcher = Watcher::new().ok()?;
+
+	watcher.add(dir, kinds).ok()?;
+
+	Some(watcher)
+}
+
+#[asynchronous]
+impl Tail {
+	/// Set how often the file is checked for new data and rotation when
+	/// there is nothing to read and inotify is not available. Defaults to 250
+	/// milliseconds
+	pub fn set_poll_interval(&mut self, interval: Duration) {
+		self.interval = interval;
+	}
+
+	/// Returns `true` if changes to the file are reported by a [`Watcher`],
+	/// instead of being checked for every poll interval
+	#[must_use]
+	pub const fn is_watching(&self) -> bool {
+		self.watcher.is_some()
+	}
+
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Open the file at the path, returning `None` if it does not exist
+	async fn open(&self) -> Result<Option<(File, u64)>> {
+		let file = match File::open(&self.path).await {
+			Ok(file) => file,
+			Err(err) if err.os_error() == Some(OsError::NoEnt) => return Ok(None),
+			Err(err) => return Err(err)
+		};
+
+		let ino = file.metadata_with(StatxMask::Ino.into()).await?.ino();
+
+		Ok(Some((file, ino.unwrap_or(0))))
+	}
+
+	/// Check whether the file was replaced or truncated, after all of its
+	/// data was read. Returns `true` if there may be new data to read
+	async fn check_rotation(&mut self) -> Result<bool> {
+		let Some((file, ino)) = &mut self.file else {
+			self.file = self.open().await?;
+
+			return Ok(self.file.is_some());
+		};
+
+		let current = match metadata_with(&self.path, StatxMask::Ino.into()).await {
+			Ok(current) => current.ino(),
+			Err(err) if err.os_error() == Some(OsError::NoEnt) => return Ok(false),
+			Err(err) => return Err(err)
+		};
+
+		if current != Some(*ino) {
+			/* the old file was renamed or deleted, and all of its data was read */
+			self.file = self.open().await?;
+			self.partial.clear();
+
+			return Ok(self.file.is_some());
+		}
+
+		let len = file.metadata_with(StatxPreset::SIZE_ONLY).await?.len();
+		let offset = file.seek(SeekFrom::Current(0)).await?;
+
+		if len >= offset {
+			return Ok(false);
+		}
+
+		file.seek(SeekFrom::Start(0)).await?;
+		self.partial.clear();
+
+		Ok(true)
+	}
+
+	/// Wait for a change to the file, or for the poll interval without a
+	/// watcher
+	async fn wait(&mut self) -> Result<()> {
+		let Some(watcher) = &mut self.watcher else {
+			return sleep(self.interval).await;
+		};
+
+		loop {
+			let event = watcher.next_event().await?;
+
+			if event.kinds.contains(WatchKind::Ignored) {
+				/* the directory was removed, so fall back to polling */
+				self.watcher = None;
+
+				return Ok(());
+			}
+
+			/* events were lost if there is no path, so check anyway */
+			let Some(path) = event.path else {
+				return Ok(());
+			};
+
+			if path.file_name() == self.path.file_name() {
+				return Ok(());
+			}
+		}
+	}
+
+	fn take_line(&mut self) -> Option<String> {
+		let end = self.partial.iter().position(|&byte| byte == b'\n')?;
+		let mut line: Vec<_> = self.partial.drain(..=end).collect();
+
+		line.pop();
+
+		if line.last() == Some(&b'\r') {
+			line.pop();
+		}
+
+		Some(String::from_utf8_lossy(&line).into_owned())
+	}
+
+	/// Get the next complete line, waiting for one to be appended. The line
+	/// ending is removed, and invalid UTF-8 is replaced
+	///
+	/// # Errors
+	/// If reading the file fails, or if the task was interrupted while
+	/// waiting
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe. Data that was read is kept for the next
+	/// call.
+	pub async fn next_line(&mut self) -> Result<String> {
+		let mut buf = [0u8; TAIL_CHUNK_SIZE];
+
+		loop {
+			if let Some(line) = self.take_line() {
+				return Ok(line);
+			}
+
+			if let Some((file, _)) = &mut self.file {
+				let read = file.read(&mut buf).await?;
+
+				if read != 0 {
+					self.partial.extend_from_slice(&buf[0..read]);
+
+					continue;
+				}
+			}
+
+			if !self.check_rotation().await? {
+				self.wait().await?;
+			}
+		}
+	}
+}
+
+#[asynchronous]
+impl AsyncIterator for Tail {
+	type Item = Result<String>;
+
+	/// Get the next line appended to the file. Never returns `None`
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	async fn next(&mut self) -> Option<Self::Item> {
+		Some(self.next_line().await)
+	}
+}
+
+/// Follow the file at `path`, like `tail -F`. Returns an iterator over the
+/// complete lines appended to the file after this call
+///
+/// When the file is renamed or deleted and a new file is created at `path`,
+/// the rest of the old file is read and the new file is followed from its
+/// start. An incomplete last line of the old file is discarded. When the file
+/// is truncated, reading restarts from the beginning of the file.
+///
+/// Changes are reported by a [`Watcher`] on the directory containing the
+/// file. If inotify is not available, the file is checked for changes every
+/// poll interval instead. See [`Tail::set_poll_interval`]
+///
+/// # Errors
+/// If the file exists but could not be opened
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn tail(path: impl AsRef<Path>) -> Result<Tail> {
+	let mut tail = Tail {
+		path: path.as_ref().to_owned(),
+		file: None,
+		partial: Vec::new(),
+		watcher: None,
+		interval: DEFAULT_POLL_INTERVAL
+	};
+
+	/* watch before opening, so that no change after opening is missed */
+	tail.watcher = watch(&tail.path);
+
+	tail.file = tail.open().await?;
+
+	if let Some((file, _)) = &mut tail.file {
+		file.seek(SeekFrom::End(0)).await?;
+	}
+
+	Ok(tail)
+}
@@ -0,0 +1,144 @@
+//! Reading files in virtual file systems, such as `/proc` and `/sys`
+//!
+//! The contents of these files are generated when they are read, so they
+//! report a size of zero or a page, and may return different data for reads
+//! at different offsets. [`read_virtual`] reads them sequentially from the
+//! start in one pass, without querying the size.
+//!
+//! Some sysfs attributes notify pollers when their value changes. Use an
+//! [`AttributeWatcher`] to wait for those changes.
+//!
+//! # Examples
+//!
+//! ```
+//! let stat = fs::read_virtual_to_string("/proc/self/stat").await?;
+//!
+//! let mut carrier = AttributeWatcher::open("/sys/class/net/eth0/carrier").await?;
+//!
+//! loop {
+//! 	let value = carrier.changed().await?;
+//! }
+//! ```
+
+use xx_core::os::epoll::PollFlag;
+use xx_core::os::fcntl::*;
+
+use super::*;
+use crate::ops::fd_budget::FdRelease;
+
+/// The most data read by [`read_virtual`]
+pub const VIRTUAL_READ_LIMIT: usize = 0x100000;
+
+/// The size of each read from a virtual file. Most virtual files generate at
+/// most a page of data per read
+const VIRTUAL_CHUNK_SIZE: usize = 0x1000;
+
+#[asynchronous]
+async fn read_from_start(fd: BorrowedFd<'_>, limit: usize, seekable: bool) -> Result<Vec<u8>> {
+	let mut data = Vec::new();
+
+	loop {
+		let len = data.len();
+
+		/* read one byte past the limit to tell if the file is too large */
+		#[allow(clippy::arithmetic_side_effects)]
+		let chunk = VIRTUAL_CHUNK_SIZE.min(limit.saturating_sub(len) + 1);
+
+		data.resize(len.checked_add(chunk).ok_or(ErrorKind::OutOfMemory)?, 0);
+
+		/* use the file position for files that are not seekable */
+		let offset = if seekable { len.try_into().unwrap_or(i64::MAX) } else { -1 };
+		let read = io::read(fd, &mut data[len..], offset).await;
+
+		let read = match read {
+			Ok(read) => read,
+			Err(err) => {
+				data.truncate(len);
+
+				return Err(err);
+			}
+		};
+
+		#[allow(clippy::arithmetic_side_effects)]
+		data.truncate(len + read);
+
+		if read == 0 {
+			break Ok(data);
+		}
+
+		if data.len() > limit {
+			break Err(fmt_error!("Virtual file exceeds the read limit" @ ErrorKind::OutOfMemory));
+		}
+	}
+}
+
+/// Read all data from a virtual file, such as a file in `/proc` or `/sys`.
+/// The file is read sequentially from its current position in one pass, and
+/// the size of the file is not queried
+///
+/// # Errors
+/// If the read fails, or if the file is larger than [`VIRTUAL_READ_LIMIT`]
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn read_virtual(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+	let fd = io::open(path, OpenFlag::CloseOnExec.into(), 0).await?;
+	let data = read_from_start(fd.as_fd(), VIRTUAL_READ_LIMIT, false).await;
+
+	io::close(fd).await?;
+	data
+}
+
+/// Read all data from a virtual file into a string. See [`read_virtual`]
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn read_virtual_to_string(path: impl AsRef<Path>) -> Result<String> {
+	Ok(String::from_utf8(read_virtual(path).await?)?)
+}
+
+/// Watches a sysfs attribute for changes to its value
+///
+/// Only attributes that notify pollers support this, such as the `carrier`
+/// and `operstate` attributes of network interfaces. Waiting on other
+/// attributes never finishes.
+pub struct AttributeWatcher {
+	fd: OwnedFd,
+	_release: FdRelease
+}
+
+#[asynchronous]
+impl AttributeWatcher {
+	/// Open the attribute at `path`. The attribute is read once, which arms
+	/// the notification
+	#[allow(clippy::impl_trait_in_params)]
+	pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let fd = io::open(path, OpenFlag::CloseOnExec.into(), 0).await?;
+		let this = Self { _release: FdRelease::new(fd.as_fd()), fd };
+
+		this.read().await?;
+
+		Ok(this)
+	}
+
+	/// Read the current value of the attribute
+	pub async fn read(&self) -> Result<Vec<u8>> {
+		read_from_start(self.fd.as_fd(), VIRTUAL_CHUNK_SIZE, true).await
+	}
+
+	/// Wait for the attribute to change, returning its new value
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe. A change that happens while not waiting
+	/// is reported by the next call.
+	pub async fn changed(&mut self) -> Result<Vec<u8>> {
+		/* sysfs notifies with POLLPRI | POLLERR */
+		io::poll(self.fd.as_fd(), PollFlag::Priority | PollFlag::Error).await?;
+
+		self.read().await
+	}
+
+	#[must_use]
+	pub fn fd(&self) -> BorrowedFd<'_> {
+		self.fd.as_fd()
+	}
+}
@@ -0,0 +1,242 @@
+//! The implementation for [`Watcher`]
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use enumflags2::{bitflags, BitFlags};
+use xx_core::async_std::AsyncIterator;
+use xx_core::os;
+use xx_core::os::syscall::*;
+
+use super::*;
+
+const IN_CLOEXEC: i32 = 0o2_000_000;
+
+/// The size of `struct inotify_event`, without the name
+const EVENT_HEADER_LEN: usize = 16;
+
+/// Large enough for at least one event with the longest file name
+const EVENT_BUF_LEN: usize = 0x1000;
+
+/// The kinds of changes to watch for, and that events report
+#[bitflags]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatchKind {
+	/// The file was read
+	Access       = 1 << 0,
+
+	/// The file was written to
+	Modify       = 1 << 1,
+
+	/// The metadata of the file changed
+	Attrib       = 1 << 2,
+
+	/// The file was closed after being opened for writing
+	CloseWrite   = 1 << 3,
+
+	/// The file was closed after being opened read only
+	CloseNoWrite = 1 << 4,
+
+	/// The file was opened
+	Open         = 1 << 5,
+
+	/// A file was moved out of the watched directory
+	MovedFrom    = 1 << 6,
+
+	/// A file was moved into the watched directory
+	MovedTo      = 1 << 7,
+
+	/// A file was created in the watched directory
+	Create       = 1 << 8,
+
+	/// A file was deleted from the watched directory
+	Delete       = 1 << 9,
+
+	/// The watched file was deleted
+	DeleteSelf   = 1 << 10,
+
+	/// The watched file was moved
+	MoveSelf     = 1 << 11,
+
+	/// The file system containing the watched file was unmounted. Only
+	/// reported
+	Unmount      = 1 << 13,
+
+	/// Too many events were queued, and some were lost. Only reported
+	Overflow     = 1 << 14,
+
+	/// The watch was removed, explicitly or because the file was deleted. Only
+	/// reported
+	Ignored      = 1 << 15,
+
+	/// The subject of the event is a directory. Only reported
+	IsDir        = 1 << 30
+}
+
+/// A watch added with [`Watcher::add`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WatchId(i32);
+
+/// A change reported by a [`Watcher`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WatchEvent {
+	/// The watch that reported the change, or `None` for
+	/// [`WatchKind::Overflow`]
+	pub watch: Option<WatchId>,
+
+	/// What changed
+	pub kinds: BitFlags<WatchKind>,
+
+	/// The watched path, joined with the name of the file that changed when
+	/// the watch is on a directory. `None` for [`WatchKind::Overflow`]
+	pub path: Option<PathBuf>
+}
+
+/// An iterator over changes to watched files and directories, using
+/// `inotify(7)`
+///
+/// # Examples
+///
+/// ```
+/// let mut watcher = Watcher::new()?;
+///
+/// watcher.add("/etc/service", WatchKind::CloseWrite | WatchKind::MovedTo)?;
+///
+/// while let Some(event) = watcher.next().await {
+/// 	println!("changed: {:?}", event?.path);
+/// }
+/// ```
+pub struct Watcher {
+	fd: OwnedFd,
+	paths: HashMap<i32, PathBuf>,
+	buf: Vec<u8>,
+	start: usize,
+	end: usize
+}
+
+#[asynchronous]
+impl Watcher {
+	/// Create a watcher with no watches
+	///
+	/// # Errors
+	/// If the inotify instance could not be created, such as when the
+	/// per-user limit is reached
+	pub fn new() -> Result<Self> {
+		/* Safety: no pointers are passed */
+		let fd = unsafe { syscall_int!(InotifyInit1, IN_CLOEXEC)? };
+
+		#[allow(clippy::cast_possible_truncation)]
+		/* Safety: the kernel gave us a new fd */
+		let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+		Ok(Self { fd, paths: HashMap::new(), buf: vec![0; EVENT_BUF_LEN], start: 0, end: 0 })
+	}
+
+	/// Watch `path` for the changes in `kinds`. Adding a path that is already
+	/// watched replaces its kinds and returns the same id
+	///
+	/// # Errors
+	/// If the path does not exist, or the per-user watch limit is reached
+	#[allow(clippy::impl_trait_in_params)]
+	pub fn add(
+		&mut self, path: impl AsRef<Path>, kinds: BitFlags<WatchKind>
+	) -> Result<WatchId> {
+		let path = path.as_ref();
+		let fd = self.fd.as_raw_fd();
+
+		let watch = os::with_path_as_cstr(path, |cstr| {
+			/* Safety: cstr is a valid nul terminated path */
+			Ok(unsafe { syscall_int!(InotifyAddWatch, fd, cstr.as_ptr(), kinds.bits())? })
+		})?;
+
+		#[allow(clippy::cast_possible_truncation)]
+		let watch = watch as i32;
+
+		self.paths.insert(watch, path.to_owned());
+
+		Ok(WatchId(watch))
+	}
+
+	/// Stop watching. An event with [`WatchKind::Ignored`] is reported once
+	/// the watch is removed
+	///
+	/// # Errors
+	/// If the watch was already removed
+	pub fn remove(&mut self, watch: WatchId) -> Result<()> {
+		/* Safety: no pointers are passed */
+		unsafe { syscall_int!(InotifyRmWatch, self.fd.as_raw_fd(), watch.0)? };
+
+		Ok(())
+	}
+
+	/// Parse the next buffered event, if any
+	#[allow(clippy::arithmetic_side_effects)]
+	fn parse(&mut self) -> Option<WatchEvent> {
+		let buf = self.buf.get(self.start..self.end)?;
+		let word = |index: usize| {
+			let bytes = buf.get(index * 4..index * 4 + 4)?;
+
+			Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+		};
+
+		#[allow(clippy::cast_possible_wrap)]
+		let watch = word(0)? as i32;
+		let kinds = BitFlags::from_bits_truncate(word(1)?);
+		let len = usize::try_from(word(3)?).ok()?;
+		let name = buf.get(EVENT_HEADER_LEN..EVENT_HEADER_LEN + len)?;
+		let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+
+		self.start += EVENT_HEADER_LEN + len;
+
+		let path = self.paths.get(&watch).map(|path| {
+			if name.is_empty() {
+				path.clone()
+			} else {
+				path.join(OsStr::from_bytes(name))
+			}
+		});
+
+		if kinds.contains(WatchKind::Ignored) {
+			self.paths.remove(&watch);
+		}
+
+		Some(WatchEvent { watch: (watch >= 0).then_some(WatchId(watch)), kinds, path })
+	}
+
+	/// Wait for the next event
+	///
+	/// # Errors
+	/// If reading from the inotify instance failed
+	pub async fn next_event(&mut self) -> Result<WatchEvent> {
+		if self.start >= self.end {
+			self.start = 0;
+			self.end = 0;
+			self.end = io::read(self.fd.as_fd(), &mut self.buf[..], -1).await?;
+		}
+
+		self.parse().ok_or_else(|| {
+			/* drop the rest of the buffer, it cannot be parsed either */
+			self.start = self.end;
+
+			fmt_error!("Malformed inotify event" @ ErrorKind::InvalidData)
+		})
+	}
+}
+
+#[asynchronous]
+impl AsyncIterator for Watcher {
+	type Item = Result<WatchEvent>;
+
+	/// Get the next event. Never returns `None`
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	async fn next(&mut self) -> Option<Self::Item> {
+		Some(self.next_event().await)
+	}
+}
@@ -1,4 +1,6 @@
 use super::*;
+use crate::fs::ReadProgress;
+use crate::io::ReadUninit;
 
 /// Extensions for an async task
 #[asynchronous(traitext)]
@@ -18,3 +20,37 @@ pub trait TaskExt: Task + Sized {
 }
 
 impl<T: Task> TaskExt for T {}
+
+/// Extensions for an async iterator
+#[asynchronous(traitext)]
+pub trait AsyncIteratorExt: AsyncIterator {
+	/// Get the next item, waiting at most `duration` for it to arrive. Returns
+	/// `Ok(None)` if the iterator ended.
+	///
+	/// # Errors
+	/// With [`ErrorKind::TimedOut`] if no item arrived within `duration`
+	async fn next_timeout(&mut self, duration: Duration) -> Result<Option<Self::Item>> {
+		match next_or(self, sleep(duration)).await {
+			NextOr::Next(item) => Ok(item),
+			NextOr::Other(_) => Err(fmt_error!(
+				"Timed out waiting for the next item" @ ErrorKind::TimedOut
+			))
+		}
+	}
+}
+
+impl<I: AsyncIterator> AsyncIteratorExt for I {}
+
+/// Extensions for a stream that can read into uninitialized buffers
+#[asynchronous(traitext)]
+pub trait ReadUninitExt: ReadUninit + Sized {
+	/// Read all data until EOF, appending it to `vec` without zeroing the
+	/// space first. See [`read_to_end_resumable`](crate::fs::read_to_end_resumable)
+	async fn read_to_end_resumable(
+		&mut self, vec: &mut Vec<u8>, progress: &mut ReadProgress
+	) -> Result<usize> {
+		fs::read_to_end_resumable(self, vec, progress).await
+	}
+}
+
+impl<R: ReadUninit> ReadUninitExt for R {}
@@ -1,5 +1,7 @@
 //! Contains the implementation for [`Interval`]
 
+use xx_core::async_std::AsyncIterator;
+
 use super::*;
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
@@ -19,18 +21,32 @@ pub struct Interval {
 }
 
 impl Interval {
-	/// # Panics
-	/// if the delay cannot fit into a u64
+	/// Create an interval that ticks every `delay`. Like [`sleep`], delays
+	/// longer than `u64::MAX` nanoseconds (~585 years) are clamped. See
+	/// [`Interval::try_new`] to reject them instead
 	#[must_use]
 	pub fn new(delay: Duration) -> Self {
-		#[allow(clippy::unwrap_used)]
-		let delay = delay.as_nanos().try_into().unwrap();
-		let expire = 0;
+		Self::from_nanos(delay.as_nanos().try_into().unwrap_or(u64::MAX))
+	}
+
+	/// Like [`Interval::new`], but rejects delays that are too long
+	///
+	/// # Errors
+	/// if the delay in nanoseconds cannot fit into a u64
+	pub fn try_new(delay: Duration) -> Result<Self> {
+		let delay = delay
+			.as_nanos()
+			.try_into()
+			.map_err(|_| fmt_error!("Interval delay overflow"))?;
+
+		Ok(Self::from_nanos(delay))
+	}
 
+	pub(crate) const fn from_nanos(delay: u64) -> Self {
 		Self {
-			expire,
+			expire: 0,
 			delay,
-			missed_tick_behavior: Default::default()
+			missed_tick_behavior: MissedTickBehavior::Burst
 		}
 	}
 
@@ -38,10 +54,10 @@ impl Interval {
 		self.missed_tick_behavior = behavior;
 	}
 
-	#[asynchronous]
-	pub async fn next(&mut self) -> Result<()> {
+	/// Advance to the next tick, returning its expiry if it has not passed
+	fn advance(&mut self) -> Option<u64> {
 		if self.delay == 0 {
-			return Ok(());
+			return None;
 		}
 
 		let now = nanotime();
@@ -50,15 +66,16 @@ impl Interval {
 			self.expire = now;
 		}
 
-		#[allow(clippy::unwrap_used, clippy::arithmetic_side_effects)]
+		/* an expiry of u64::MAX never fires, so saturating is fine */
+		#[allow(clippy::arithmetic_side_effects)]
 		match self.missed_tick_behavior {
-			MissedTickBehavior::Burst => self.expire = self.expire.checked_add(self.delay).unwrap(),
-			MissedTickBehavior::Delay => self.expire = now.checked_add(self.delay).unwrap(),
+			MissedTickBehavior::Burst => self.expire = self.expire.saturating_add(self.delay),
+			MissedTickBehavior::Delay => self.expire = now.saturating_add(self.delay),
 			MissedTickBehavior::Skip => {
-				let mut next = self.expire.checked_add(self.delay).unwrap();
+				let mut next = self.expire.saturating_add(self.delay);
 
 				if now > next {
-					next = now.checked_add(self.delay).unwrap();
+					next = now.saturating_add(self.delay);
 
 					/* subsequent iteration, align to next delay boundary */
 					let align = (now - self.expire) % self.delay;
@@ -70,10 +87,37 @@ impl Interval {
 			}
 		}
 
-		if self.expire > now {
-			timeout(self.expire, TimeoutFlag::Abs.into()).await
-		} else {
-			Ok(())
+		(self.expire > now).then_some(self.expire)
+	}
+
+	#[asynchronous]
+	pub async fn next(&mut self) -> Result<()> {
+		match self.advance() {
+			Some(expire) => timeout(expire, TimeoutFlag::Abs.into()).await,
+			None => Ok(())
+		}
+	}
+
+	/// Like [`Interval::next`], but the timer is registered to `owner`
+	///
+	/// # Errors
+	/// If the owner was created on a different runtime, or its timers were
+	/// cancelled. See [`TimerOwner`]
+	#[asynchronous]
+	pub async fn next_owned(&mut self, owner: &TimerOwner) -> Result<()> {
+		match self.advance() {
+			Some(expire) => owner.timeout(expire, TimeoutFlag::Abs.into()).await,
+			None => Ok(())
 		}
 	}
 }
+
+#[asynchronous]
+impl AsyncIterator for Interval {
+	type Item = Result<()>;
+
+	/// Wait for the next tick. The iterator never ends.
+	async fn next(&mut self) -> Option<Self::Item> {
+		Some(self.next().await)
+	}
+}
@@ -1,3 +1,10 @@
+/* with `no-panic`, errors are returned instead of panicking. fatal errors in
+ * the runtime itself, such as failing to read the clock, abort instead */
+#![cfg_attr(
+	feature = "no-panic",
+	deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)
+)]
+
 use std::time::Duration;
 
 use enumflags2::{bitflags, BitFlags};
@@ -5,17 +12,29 @@ use xx_core::coroutines::{self, Context, Environment, Executor, Task};
 use xx_core::error::*;
 use xx_core::future::{self, future, Future, Progress, ReqPtr, Request};
 
+#[cfg(feature = "bench-internal")]
+pub mod bench;
 mod driver;
 mod engine;
 pub mod fs;
 pub mod impls;
 pub mod interval;
 pub mod macros;
+#[cfg(target_os = "linux")]
+pub mod mem;
+pub mod metrics;
 pub mod net;
 pub mod ops;
+pub mod prelude;
 mod runtime;
+#[cfg(target_os = "linux")]
+pub mod storage;
 
-pub use runtime::Runtime;
+pub use driver::TimerStats;
+pub use engine::{EngineFeature, EngineStats, TaskRunPolicy};
+#[cfg(feature = "test-util")]
+pub use engine::ScheduleOrder;
+pub use runtime::{DropPolicy, Runtime, RuntimeBuilder, TraceSubsystem};
 pub use xx_core::coroutines::{
 	acquire_budget, asynchronous, block_on, check_interrupt, check_interrupt_take, current_budget,
 	get_context, interrupt_guard, is_interrupted, scoped, take_interrupt
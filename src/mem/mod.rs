@@ -0,0 +1,11 @@
+//! Memory management
+
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+
+use xx_core::error::*;
+use xx_core::os::error::OsError;
+use xx_core::os::syscall::*;
+
+use super::*;
+
+pub mod userfault;
@@ -0,0 +1,410 @@
+//! Userspace page fault handling with `userfaultfd(2)`
+//!
+//! Page faults in registered memory ranges are delivered as [`Event`]s, and
+//! resolved by copying or zeroing pages with [`UserFault::copy`] and
+//! [`UserFault::zero_page`]. The faulting thread sleeps until the fault is
+//! resolved.
+//!
+//! Faults must be handled by a different thread than the one that faults,
+//! otherwise the runtime deadlocks.
+//!
+//! Events other than page faults are only reported if their [`Feature`] was
+//! requested with [`UserFault::with_features`].
+//!
+//! # Examples
+//!
+//! ```
+//! let uffd = UserFault::new()?;
+//!
+//! /* Safety: the region is mapped and faults are handled below */
+//! unsafe { uffd.register(region, len, RegisterMode::Missing.into())? };
+//!
+//! while let Some(event) = uffd.next().await {
+//! 	if let Event::PageFault { address, .. } = event? {
+//! 		/* Safety: the page is in the registered region */
+//! 		unsafe { uffd.copy(page_start(address), &page_data, true)? };
+//! 	}
+//! }
+//! ```
+
+use std::mem::size_of;
+
+use xx_core::async_std::AsyncIterator;
+use xx_core::os::epoll::PollFlag;
+use xx_core::pointer::*;
+
+use super::*;
+
+const UFFD_API: u64 = 0xaa;
+
+const UFFDIO_API: u64 = 0xc018_aa3f;
+const UFFDIO_REGISTER: u64 = 0xc020_aa00;
+const UFFDIO_UNREGISTER: u64 = 0x8010_aa01;
+const UFFDIO_WAKE: u64 = 0x8010_aa02;
+const UFFDIO_COPY: u64 = 0xc028_aa03;
+const UFFDIO_ZEROPAGE: u64 = 0xc020_aa04;
+
+const UFFDIO_COPY_MODE_DONTWAKE: u64 = 1 << 0;
+const UFFDIO_ZEROPAGE_MODE_DONTWAKE: u64 = 1 << 0;
+
+const UFFD_EVENT_PAGEFAULT: u8 = 0x12;
+const UFFD_EVENT_FORK: u8 = 0x13;
+const UFFD_EVENT_REMAP: u8 = 0x14;
+const UFFD_EVENT_REMOVE: u8 = 0x15;
+const UFFD_EVENT_UNMAP: u8 = 0x16;
+
+const O_NONBLOCK: u32 = 0o4000;
+const O_CLOEXEC: u32 = 0o2_000_000;
+const UFFD_USER_MODE_ONLY: u32 = 1;
+
+#[repr(C)]
+#[derive(Default)]
+struct Api {
+	api: u64,
+	features: u64,
+	ioctls: u64
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Range {
+	start: u64,
+	len: u64
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Register {
+	range: Range,
+	mode: u64,
+	ioctls: u64
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CopyRange {
+	dst: u64,
+	src: u64,
+	len: u64,
+	mode: u64,
+	copy: i64
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ZeroPage {
+	range: Range,
+	mode: u64,
+	zeropage: i64
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Message {
+	event: u8,
+	reserved1: u8,
+	reserved2: u16,
+	reserved3: u32,
+	arg: [u64; 3]
+}
+
+/// # Safety
+/// `arg` must be the type expected by `request`
+unsafe fn ioctl<T>(fd: BorrowedFd<'_>, request: u64, arg: &mut T) -> Result<isize> {
+	/* Safety: guaranteed by caller */
+	let result = unsafe { syscall_int!(Ioctl, fd.as_raw_fd(), request, ptr!(arg).as_ptr()) };
+
+	result.map_err(Into::into)
+}
+
+/// The kinds of faults to report for a registered range
+#[bitflags]
+#[repr(u64)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum RegisterMode {
+	/// Report faults on pages which are not present
+	Missing      = 1 << 0,
+
+	/// Report writes to write protected pages
+	WriteProtect = 1 << 1,
+
+	/// Report faults on pages which are present in the page cache, but not
+	/// mapped
+	Minor        = 1 << 2
+}
+
+/// Optional features of a [`UserFault`], negotiated when it is created
+#[bitflags]
+#[repr(u64)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Feature {
+	/// Report [`Event::Fork`]
+	EventFork   = 1 << 1,
+
+	/// Report [`Event::Remap`]
+	EventRemap  = 1 << 2,
+
+	/// Report [`Event::Remove`]
+	EventRemove = 1 << 3,
+
+	/// Report [`Event::Unmap`]
+	EventUnmap  = 1 << 6,
+
+	/// Report the faulting thread in [`Event::PageFault`]
+	ThreadId    = 1 << 8
+}
+
+/// Details about a page fault
+#[bitflags]
+#[repr(u64)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum FaultFlag {
+	/// The fault was a write
+	Write        = 1 << 0,
+
+	/// The fault was a write to a write protected page
+	WriteProtect = 1 << 1,
+
+	/// The fault was a minor fault
+	Minor        = 1 << 2
+}
+
+/// An event from a [`UserFault`]
+#[derive(Debug)]
+pub enum Event {
+	/// A thread faulted on `address`. `thread` is zero unless
+	/// [`Feature::ThreadId`] was requested
+	PageFault {
+		address: u64,
+		flags: BitFlags<FaultFlag>,
+		thread: u32
+	},
+
+	/// The process forked. Faults in the child are reported on `fd`. Requires
+	/// [`Feature::EventFork`]
+	Fork { fd: OwnedFd },
+
+	/// A registered range was moved with `mremap(2)`. Requires
+	/// [`Feature::EventRemap`]
+	Remap { from: u64, to: u64, len: u64 },
+
+	/// A registered range was released with `madvise(2)`. Requires
+	/// [`Feature::EventRemove`]
+	Remove { start: u64, end: u64 },
+
+	/// A registered range was unmapped. Requires [`Feature::EventUnmap`]
+	Unmap { start: u64, end: u64 },
+
+	/// An event not known to this version of the crate
+	Unknown(u8)
+}
+
+impl Event {
+	#[allow(clippy::cast_possible_truncation)]
+	fn from_message(message: &Message) -> Self {
+		let [first, second, third] = message.arg;
+
+		match message.event {
+			UFFD_EVENT_PAGEFAULT => Self::PageFault {
+				address: second,
+				flags: BitFlags::from_bits_truncate(first),
+				thread: third as u32
+			},
+
+			/* Safety: the kernel gave us a new fd */
+			UFFD_EVENT_FORK => Self::Fork { fd: unsafe { OwnedFd::from_raw_fd(first as i32) } },
+			UFFD_EVENT_REMAP => Self::Remap { from: first, to: second, len: third },
+			UFFD_EVENT_REMOVE => Self::Remove { start: first, end: second },
+			UFFD_EVENT_UNMAP => Self::Unmap { start: first, end: second },
+			event => Self::Unknown(event)
+		}
+	}
+}
+
+/// A userfaultfd, driven by the runtime's poll machinery
+pub struct UserFault {
+	fd: OwnedFd,
+	features: BitFlags<Feature>
+}
+
+#[asynchronous]
+impl UserFault {
+	/// Create a new userfaultfd which only handles faults from user mode, and
+	/// only reports page faults
+	///
+	/// # Errors
+	/// If the kernel does not support userfaultfd or handling only user mode
+	/// faults, or the process lacks permission to use it (see
+	/// `vm.unprivileged_userfaultfd`)
+	pub fn new() -> Result<Self> {
+		Self::with_features(BitFlags::default())
+	}
+
+	/// Create a new userfaultfd which only handles faults from user mode, with
+	/// the optional `features`
+	///
+	/// # Errors
+	/// See [`UserFault::new`]. Also fails with [`OsError::Inval`] if the
+	/// kernel does not support one of the `features`
+	pub fn with_features(features: BitFlags<Feature>) -> Result<Self> {
+		/* Safety: no pointers are passed */
+		let fd =
+			unsafe { syscall_int!(UserfaultFd, O_NONBLOCK | O_CLOEXEC | UFFD_USER_MODE_ONLY)? };
+
+		#[allow(clippy::cast_possible_truncation)]
+		/* Safety: the kernel gave us a new fd */
+		let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+		let mut api = Api { api: UFFD_API, features: features.bits(), ioctls: 0 };
+
+		/* Safety: arg is the correct type */
+		unsafe { ioctl(fd.as_fd(), UFFDIO_API, &mut api)? };
+
+		Ok(Self { fd, features })
+	}
+
+	/// The features requested when this userfaultfd was created
+	#[must_use]
+	pub const fn features(&self) -> BitFlags<Feature> {
+		self.features
+	}
+
+	#[must_use]
+	pub fn fd(&self) -> BorrowedFd<'_> {
+		self.fd.as_fd()
+	}
+
+	/// Report faults on the `len` bytes starting at `start`, according to
+	/// `mode`. Both must be page aligned
+	///
+	/// # Safety
+	/// Any thread that faults on the range blocks until the fault is
+	/// resolved. The faults must be handled for as long as the range is
+	/// registered
+	pub unsafe fn register(
+		&self, start: MutPtr<()>, len: usize, mode: BitFlags<RegisterMode>
+	) -> Result<()> {
+		let mut register = Register {
+			range: Range { start: start.addr() as u64, len: len as u64 },
+			mode: mode.bits(),
+			ioctls: 0
+		};
+
+		/* Safety: arg is the correct type */
+		unsafe { ioctl(self.fd(), UFFDIO_REGISTER, &mut register)? };
+
+		Ok(())
+	}
+
+	/// Stop reporting faults on the range
+	pub fn unregister(&self, start: MutPtr<()>, len: usize) -> Result<()> {
+		let mut range = Range { start: start.addr() as u64, len: len as u64 };
+
+		/* Safety: arg is the correct type */
+		unsafe { ioctl(self.fd(), UFFDIO_UNREGISTER, &mut range)? };
+
+		Ok(())
+	}
+
+	/// Resolve a fault by atomically copying `src` to `dst`, which must be
+	/// page aligned. If `wake` is set, the faulting threads are woken.
+	///
+	/// Returns the number of bytes copied.
+	///
+	/// # Safety
+	/// `dst` must be in a registered range
+	pub unsafe fn copy(&self, dst: MutPtr<()>, src: &[u8], wake: bool) -> Result<usize> {
+		let mut copy = CopyRange {
+			dst: dst.addr() as u64,
+			src: ptr!(src.as_ptr()).addr() as u64,
+			len: src.len() as u64,
+			mode: if wake { 0 } else { UFFDIO_COPY_MODE_DONTWAKE },
+			copy: 0
+		};
+
+		/* Safety: arg is the correct type */
+		unsafe { ioctl(self.fd(), UFFDIO_COPY, &mut copy)? };
+
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+		Ok(copy.copy as usize)
+	}
+
+	/// Resolve a fault by mapping zeroed pages to the `len` bytes at `dst`.
+	/// If `wake` is set, the faulting threads are woken.
+	///
+	/// Returns the number of bytes zeroed.
+	///
+	/// # Safety
+	/// `dst` must be in a registered range
+	pub unsafe fn zero_page(&self, dst: MutPtr<()>, len: usize, wake: bool) -> Result<usize> {
+		let mut zero = ZeroPage {
+			range: Range { start: dst.addr() as u64, len: len as u64 },
+			mode: if wake { 0 } else { UFFDIO_ZEROPAGE_MODE_DONTWAKE },
+			zeropage: 0
+		};
+
+		/* Safety: arg is the correct type */
+		unsafe { ioctl(self.fd(), UFFDIO_ZEROPAGE, &mut zero)? };
+
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+		Ok(zero.zeropage as usize)
+	}
+
+	/// Wake the threads faulting on the range, after resolving the faults
+	/// without waking
+	pub fn wake(&self, start: MutPtr<()>, len: usize) -> Result<()> {
+		let mut range = Range { start: start.addr() as u64, len: len as u64 };
+
+		/* Safety: arg is the correct type */
+		unsafe { ioctl(self.fd(), UFFDIO_WAKE, &mut range)? };
+
+		Ok(())
+	}
+
+	/// Wait for the next event
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	pub async fn next_event(&self) -> Result<Event> {
+		let mut message = Message::default();
+
+		loop {
+			/* Safety: message is plain old data */
+			let buf = unsafe {
+				std::slice::from_raw_parts_mut(
+					ptr!(&mut message).cast::<u8>().as_mut_ptr(),
+					size_of::<Message>()
+				)
+			};
+
+			match io::read(self.fd(), buf, -1).await {
+				Ok(_) => break Ok(Event::from_message(&message)),
+				Err(err) if err.os_error() == Some(OsError::WouldBlock) => {
+					io::poll(self.fd(), PollFlag::In.into()).await?;
+				}
+
+				Err(err) => break Err(err)
+			}
+		}
+	}
+
+	/// Close the userfaultfd. Registered ranges are unregistered, and any
+	/// faulting threads are woken
+	pub async fn close(self) -> Result<()> {
+		io::close(self.fd).await
+	}
+}
+
+#[asynchronous]
+impl AsyncIterator for UserFault {
+	type Item = Result<Event>;
+
+	/// Wait for the next event. The iterator never ends.
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	async fn next(&mut self) -> Option<Self::Item> {
+		Some(self.next_event().await)
+	}
+}
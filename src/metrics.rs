@@ -0,0 +1,239 @@
+//! A process-wide registry of runtimes, and an endpoint serving their metrics
+//!
+//! Runtimes built with [`RuntimeBuilder::register`] publish a
+//! [`RuntimeMetrics`] snapshot to the registry while they run, at most once
+//! every 100 milliseconds. Snapshots can be read from any thread with
+//! [`registered_runtimes`], or served in the Prometheus text format with
+//! [`serve`], without running a separate runtime for the exporter.
+//!
+//! # Examples
+//!
+//! ```
+//! let runtime = Runtime::builder().register("main").build()?;
+//!
+//! runtime.block_on(async {
+//! 	let exporter = Tcp::bind("127.0.0.1:9000").await?;
+//!
+//! 	spawn(async move { metrics::serve(&exporter).await }).await;
+//! 	/* ... */
+//! });
+//! ```
+
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::*;
+use crate::impls::TaskExt;
+use crate::io::{Read, WriteExt};
+use crate::net::{StreamSocket, TcpListener};
+
+/// How often a running runtime publishes its metrics, in nanoseconds
+const PUBLISH_INTERVAL: u64 = 100_000_000;
+
+/// The largest HTTP request accepted by [`serve`]
+const MAX_REQUEST: usize = 0x2000;
+
+/// A snapshot of the metrics of a runtime
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RuntimeMetrics {
+	pub engine: EngineStats,
+	pub timers: TimerStats,
+	pub fds: FdBudgetStats
+}
+
+/// A runtime in the registry, with its last published metrics
+#[derive(Clone, Copy, Debug)]
+pub struct RegisteredRuntime {
+	/// A unique id, distinguishing runtimes registered with the same name
+	pub id: u64,
+	pub name: &'static str,
+	pub metrics: RuntimeMetrics
+}
+
+struct Entry {
+	id: u64,
+	name: &'static str,
+	metrics: Mutex<RuntimeMetrics>
+}
+
+static REGISTRY: Mutex<Vec<Arc<Entry>>> = Mutex::new(Vec::new());
+
+fn registry() -> MutexGuard<'static, Vec<Arc<Entry>>> {
+	REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The registration of a runtime, which removes it from the registry when
+/// dropped
+pub(crate) struct Registration {
+	entry: Arc<Entry>,
+	last_publish: Cell<u64>
+}
+
+impl Registration {
+	pub(crate) fn new(name: &'static str) -> Self {
+		static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+		let entry = Arc::new(Entry {
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+			name,
+			metrics: Mutex::new(RuntimeMetrics::default())
+		});
+
+		registry().push(entry.clone());
+
+		Self { entry, last_publish: Cell::new(0) }
+	}
+
+	/// Publish the metrics now
+	pub(crate) fn publish(&self, metrics: RuntimeMetrics) {
+		self.last_publish.set(nanotime());
+
+		*self
+			.entry
+			.metrics
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner) = metrics;
+	}
+
+	/// Publish the metrics if the publish interval has elapsed
+	pub(crate) fn maybe_publish<F>(&self, metrics: F)
+	where
+		F: FnOnce() -> RuntimeMetrics
+	{
+		if nanotime().saturating_sub(self.last_publish.get()) >= PUBLISH_INTERVAL {
+			self.publish(metrics());
+		}
+	}
+}
+
+impl Drop for Registration {
+	fn drop(&mut self) {
+		registry().retain(|entry| !Arc::ptr_eq(entry, &self.entry));
+	}
+}
+
+/// Get the last published metrics of every registered runtime that is alive,
+/// in the order they were registered
+#[must_use]
+pub fn registered_runtimes() -> Vec<RegisteredRuntime> {
+	registry()
+		.iter()
+		.map(|entry| RegisteredRuntime {
+			id: entry.id,
+			name: entry.name,
+			metrics: *entry.metrics.lock().unwrap_or_else(PoisonError::into_inner)
+		})
+		.collect()
+}
+
+fn escape_label(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
+
+fn count(value: usize) -> u64 {
+	value.try_into().unwrap_or(u64::MAX)
+}
+
+/// Format `runtimes` in the Prometheus text exposition format
+#[must_use]
+pub fn render_prometheus(runtimes: &[RegisteredRuntime]) -> String {
+	type Metric = (&'static str, &'static str, fn(&RuntimeMetrics) -> u64);
+
+	const METRICS: [Metric; 8] = [
+		("xx_pulse_engine_enters_total", "counter", |metrics| metrics.engine.enters),
+		("xx_pulse_engine_taskrun_enters_total", "counter", |metrics| {
+			metrics.engine.taskrun_enters
+		}),
+		("xx_pulse_engine_spin_hits_total", "counter", |metrics| metrics.engine.spin_hits),
+		("xx_pulse_timers_pending", "gauge", |metrics| count(metrics.timers.pending)),
+		("xx_pulse_timers_owned", "gauge", |metrics| count(metrics.timers.owned)),
+		("xx_pulse_fd_budget_in_use", "gauge", |metrics| count(metrics.fds.in_use)),
+		("xx_pulse_fd_budget_waiting", "gauge", |metrics| count(metrics.fds.waiting)),
+		("xx_pulse_fd_budget_waits_total", "counter", |metrics| metrics.fds.waits)
+	];
+
+	let mut output = String::new();
+
+	for (name, kind, value) in METRICS {
+		let _ = writeln!(output, "# TYPE {} {}", name, kind);
+
+		for runtime in runtimes {
+			let _ = writeln!(
+				output,
+				"{}{{runtime=\"{}\",id=\"{}\"}} {}",
+				name,
+				escape_label(runtime.name),
+				runtime.id,
+				value(&runtime.metrics)
+			);
+		}
+	}
+
+	output
+}
+
+#[asynchronous]
+async fn respond(connection: &mut StreamSocket) -> Result<()> {
+	let mut request = Vec::new();
+	let mut buf = [0u8; 0x400];
+
+	while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+		if request.len() > MAX_REQUEST {
+			return Err(fmt_error!("Request too large" @ ErrorKind::InvalidData));
+		}
+
+		let read = connection.read(&mut buf).await?;
+
+		if read == 0 {
+			return Ok(());
+		}
+
+		request.extend_from_slice(&buf[0..read]);
+	}
+
+	let found = request.starts_with(b"GET /metrics ") || request.starts_with(b"GET / ");
+	let (status, body) = if found {
+		("200 OK", render_prometheus(&registered_runtimes()))
+	} else {
+		("404 Not Found", String::new())
+	};
+
+	let response = format!(
+		"HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+		 Connection: close\r\n\r\n{}",
+		status,
+		body.len(),
+		body
+	);
+
+	connection.write_all(response.as_bytes()).await?;
+
+	Ok(())
+}
+
+/// Serve the metrics of all registered runtimes over HTTP/1 on `listener`,
+/// in the Prometheus text format. Requests for `/` and `/metrics` are
+/// answered, and each connection is closed after one response
+///
+/// Connections are handled one at a time, and each has 5 seconds to send its
+/// request. Never returns unless accepting fails or the task is interrupted
+#[asynchronous]
+pub async fn serve(listener: &TcpListener) -> Result<()> {
+	loop {
+		let (mut connection, _) = listener.accept().await?;
+
+		/* a misbehaving client only affects its own connection */
+		let _ = respond(&mut connection)
+			.timeout(Duration::from_secs(5))
+			.await;
+
+		let _ = connection.close().await;
+
+		check_interrupt().await?;
+	}
+}
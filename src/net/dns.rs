@@ -0,0 +1,379 @@
+//! A minimal DNS stub resolver that runs on the runtime, as described in
+//! RFC 1035
+//!
+//! Queries for `A` and `AAAA` records are sent over UDP to the configured
+//! nameservers, which must perform recursion. Unlike the system resolver,
+//! lookups never block a thread, but `/etc/hosts`, search domains and
+//! `nsswitch.conf` are not consulted. Combine with a [`HostsResolver`] for
+//! local overrides.
+//!
+//! # Examples
+//!
+//! ```
+//! let resolver = DnsResolver::from_system_config().await?;
+//! let stream = Tcp::connect_with(&resolver, "example.com:443").await?;
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use super::*;
+use crate::fs;
+use crate::impls::TaskExt;
+
+const HEADER_LEN: usize = 12;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const RCODE_MASK: u16 = 0x000f;
+const RCODE_NAME_ERROR: u16 = 3;
+
+const CLASS_IN: u16 = 1;
+
+/// The largest name, in its encoded form
+const MAX_NAME_LEN: usize = 255;
+
+/// The largest label of a name
+const MAX_LABEL_LEN: usize = 63;
+
+/// The largest response accepted. Responses over UDP without extensions are
+/// limited to 512 bytes
+const MAX_RESPONSE_LEN: usize = 0x200;
+
+const DNS_PORT: u16 = 53;
+
+/// The type of an address record
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RecordType {
+	/// An IPv4 address
+	A = 1,
+
+	/// An IPv6 address
+	Aaaa = 28
+}
+
+fn malformed() -> Error {
+	fmt_error!("Malformed DNS message" @ ErrorKind::InvalidData)
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Result<u16> {
+	#[allow(clippy::arithmetic_side_effects)]
+	let bytes = buf.get(offset..offset + 2).ok_or_else(malformed)?;
+
+	Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Get the offset after the name at `offset`, which may be compressed
+fn skip_name(buf: &[u8], mut offset: usize) -> Result<usize> {
+	loop {
+		let len = usize::from(*buf.get(offset).ok_or_else(malformed)?);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		match len {
+			0 => break Ok(offset + 1),
+
+			/* a pointer ends the name */
+			0xc0.. => break Ok(offset + 2),
+			0x40.. => break Err(malformed()),
+			_ => offset += 1 + len
+		}
+	}
+}
+
+fn new_query_id() -> u16 {
+	let mut hasher = RandomState::new().build_hasher();
+
+	hasher.write_u64(nanotime());
+
+	let bytes = hasher.finish().to_ne_bytes();
+
+	u16::from_ne_bytes([bytes[0], bytes[1]])
+}
+
+/// Encode a recursive query for the `record` addresses of `name`
+///
+/// # Errors
+/// If `name` is not a valid domain name
+pub fn encode_query(id: u16, name: &str, record: RecordType) -> Result<Vec<u8>> {
+	let invalid = || fmt_error!("Invalid domain name" @ ErrorKind::InvalidInput);
+	let name = name.strip_suffix('.').unwrap_or(name);
+
+	if name.is_empty() {
+		return Err(invalid());
+	}
+
+	let mut query = Vec::with_capacity(HEADER_LEN + MAX_NAME_LEN + 4);
+
+	query.extend_from_slice(&id.to_be_bytes());
+	query.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+
+	/* one question, no other records */
+	query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+	for label in name.split('.') {
+		if label.is_empty() || label.len() > MAX_LABEL_LEN {
+			return Err(invalid());
+		}
+
+		#[allow(clippy::cast_possible_truncation)]
+		query.push(label.len() as u8);
+		query.extend_from_slice(label.as_bytes());
+	}
+
+	query.push(0);
+
+	#[allow(clippy::arithmetic_side_effects)]
+	if query.len() - HEADER_LEN > MAX_NAME_LEN {
+		return Err(invalid());
+	}
+
+	query.extend_from_slice(&(record as u16).to_be_bytes());
+	query.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+	Ok(query)
+}
+
+/// Parse a response to the query `id`, returning the addresses of type
+/// `record` in the answer. Returns `Ok(None)` if the message is not a response
+/// to this query. If the response was truncated, the addresses that fit are
+/// returned
+///
+/// # Errors
+/// If the response is malformed, or is an error response. A name that does
+/// not exist fails with [`ErrorKind::NotFound`]
+pub fn parse_response(
+	message: &[u8], id: u16, record: RecordType
+) -> Result<Option<Vec<IpAddr>>> {
+	if message.len() < HEADER_LEN || read_u16(message, 0)? != id {
+		return Ok(None);
+	}
+
+	let flags = read_u16(message, 2)?;
+
+	if flags & FLAG_RESPONSE == 0 {
+		return Ok(None);
+	}
+
+	match flags & RCODE_MASK {
+		0 => (),
+		RCODE_NAME_ERROR => {
+			return Err(fmt_error!("Domain name not found" @ ErrorKind::NotFound))
+		}
+
+		_ => return Err(fmt_error!("DNS server returned an error"))
+	}
+
+	let questions = read_u16(message, 4)?;
+	let answers = read_u16(message, 6)?;
+	let mut offset = HEADER_LEN;
+	let mut addrs = Vec::new();
+
+	for _ in 0..questions {
+		offset = skip_name(message, offset)?
+			.checked_add(4)
+			.ok_or_else(malformed)?;
+	}
+
+	for _ in 0..answers {
+		offset = skip_name(message, offset)?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let (kind, class, len) = (
+			read_u16(message, offset)?,
+			read_u16(message, offset + 2)?,
+			usize::from(read_u16(message, offset + 8)?)
+		);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let (start, end) = (offset + 10, offset + 10 + len);
+		let data = message.get(start..end).ok_or_else(malformed)?;
+
+		offset = end;
+
+		if class != CLASS_IN || kind != record as u16 {
+			/* such as the CNAME records leading to the addresses */
+			continue;
+		}
+
+		let addr = match record {
+			RecordType::A => IpAddr::V4(Ipv4Addr::from(
+				<[u8; 4]>::try_from(data).map_err(|_| malformed())?
+			)),
+
+			RecordType::Aaaa => IpAddr::V6(Ipv6Addr::from(
+				<[u8; 16]>::try_from(data).map_err(|_| malformed())?
+			))
+		};
+
+		addrs.push(addr);
+	}
+
+	if flags & FLAG_TRUNCATED != 0 && addrs.is_empty() {
+		return Err(fmt_error!("DNS response was truncated" @ ErrorKind::InvalidData));
+	}
+
+	Ok(Some(addrs))
+}
+
+/// Parse the nameservers from the contents of `/etc/resolv.conf`. Other
+/// options are ignored
+#[must_use]
+pub fn parse_resolv_conf(conf: &str) -> Vec<SocketAddr> {
+	conf.lines()
+		.filter_map(|line| {
+			let mut fields = line.split_whitespace();
+
+			if fields.next() != Some("nameserver") {
+				return None;
+			}
+
+			let addr: IpAddr = fields.next()?.parse().ok()?;
+
+			Some(SocketAddr::new(addr, DNS_PORT))
+		})
+		.collect()
+}
+
+/// Resolves names by querying nameservers over UDP. See the
+/// [module level documentation](self)
+#[derive(Clone, Debug)]
+pub struct DnsResolver {
+	nameservers: Vec<SocketAddr>,
+	timeout: Duration,
+	attempts: u32
+}
+
+#[asynchronous]
+impl DnsResolver {
+	/// Create a resolver that queries `nameservers` in order, until one
+	/// responds
+	#[must_use]
+	pub fn new(nameservers: Vec<SocketAddr>) -> Self {
+		Self { nameservers, timeout: Duration::from_secs(5), attempts: 2 }
+	}
+
+	/// Create a resolver for the nameservers in `/etc/resolv.conf`. If there
+	/// are none, the local nameserver is used
+	///
+	/// # Errors
+	/// If reading the file fails
+	pub async fn from_system_config() -> Result<Self> {
+		let conf = fs::read_to_string("/etc/resolv.conf").await?;
+		let mut nameservers = parse_resolv_conf(&conf);
+
+		if nameservers.is_empty() {
+			nameservers.push(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), DNS_PORT));
+		}
+
+		Ok(Self::new(nameservers))
+	}
+
+	/// Set how long to wait for each response before retrying. Defaults to 5
+	/// seconds
+	#[must_use]
+	pub const fn timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = timeout;
+		self
+	}
+
+	/// Set how many times a query is sent to each nameserver. Defaults to 2
+	#[must_use]
+	pub const fn attempts(mut self, attempts: u32) -> Self {
+		self.attempts = attempts;
+		self
+	}
+
+	#[must_use]
+	pub fn nameservers(&self) -> &[SocketAddr] {
+		&self.nameservers
+	}
+
+	async fn recv_response(
+		socket: &mut DatagramSocket, id: u16, record: RecordType
+	) -> Result<Vec<IpAddr>> {
+		let mut buf = [0u8; MAX_RESPONSE_LEN];
+
+		loop {
+			let len = socket.recv(&mut buf, BitFlags::default()).await?;
+
+			if let Some(addrs) = parse_response(&buf[0..len], id, record)? {
+				break Ok(addrs);
+			}
+		}
+	}
+
+	/// Query `server` for the `record` addresses of `name`
+	///
+	/// # Errors
+	/// If the query failed, or with [`ErrorKind::TimedOut`] if the server
+	/// never responded
+	pub async fn query(
+		&self, server: SocketAddr, name: &str, record: RecordType
+	) -> Result<Vec<IpAddr>> {
+		let id = new_query_id();
+		let query = encode_query(id, name, record)?;
+		let mut socket = Udp::connect(server).await?;
+
+		for _ in 0..self.attempts {
+			socket.send(&query, BitFlags::default()).await?;
+
+			let response = Self::recv_response(&mut socket, id, record)
+				.timeout(self.timeout)
+				.await;
+
+			if let Some(result) = response {
+				socket.close().await?;
+
+				return result;
+			}
+		}
+
+		socket.close().await?;
+
+		Err(fmt_error!("DNS query timed out" @ ErrorKind::TimedOut))
+	}
+
+	async fn lookup(&self, server: SocketAddr, host: &str) -> Result<Vec<IpAddr>> {
+		let mut addrs = self.query(server, host, RecordType::A).await?;
+
+		match self.query(server, host, RecordType::Aaaa).await {
+			Ok(v6) => addrs.extend(v6),
+
+			/* an IPv4 answer is enough */
+			Err(_) if !addrs.is_empty() => (),
+			Err(err) => return Err(err)
+		}
+
+		Ok(addrs)
+	}
+}
+
+#[asynchronous]
+impl Resolve for DnsResolver {
+	/// Resolve `host`, returning its IPv4 addresses followed by its IPv6
+	/// addresses. Nameservers are tried in order until one answers
+	async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+		let mut error = None;
+
+		for server in &self.nameservers {
+			match self.lookup(*server, host).await {
+				Ok(addrs) if !addrs.is_empty() => {
+					return Ok(addrs
+						.into_iter()
+						.map(|addr| SocketAddr::new(addr, port))
+						.collect())
+				}
+
+				Ok(_) => {
+					error = Some(fmt_error!("Domain name has no addresses" @ ErrorKind::NotFound));
+				}
+
+				Err(err) => error = Some(err)
+			}
+		}
+
+		Err(error.unwrap_or_else(|| common::NO_ADDRESSES.into()))
+	}
+}
@@ -9,7 +9,11 @@ use xx_core::os::iovec::*;
 
 use super::*;
 
+pub mod dns;
+pub mod resolve;
 pub mod socket;
+pub mod stun;
+pub mod write_queue;
 
 #[doc(inline)]
-pub use socket::*;
+pub use {resolve::*, socket::*, write_queue::*};
@@ -0,0 +1,234 @@
+//! Overridable name resolution for connecting sockets
+//!
+//! [`Tcp::connect`] resolves names with the system resolver. To control
+//! resolution instead, such as in tests or with split-horizon DNS, pass a
+//! [`Resolve`] implementation to [`Tcp::connect_with`].
+//!
+//! [`HostsResolver`] answers names from a hosts file or a fixed table, and
+//! falls back to another resolver for all other names.
+//!
+//! # Examples
+//!
+//! ```
+//! let mut resolver = HostsResolver::new(SystemResolver);
+//!
+//! resolver.insert("db.internal", "10.0.0.5".parse()?);
+//!
+//! let stream = Tcp::connect_with(&resolver, "db.internal:5432").await?;
+//! ```
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::Path;
+
+use super::*;
+use crate::fs;
+use crate::net::dns::DnsResolver;
+
+/// Resolves host names to socket addresses
+#[asynchronous]
+pub trait Resolve {
+	/// Resolve `host` to the addresses to try connecting to with `port`, in
+	/// the order they should be tried
+	async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>>;
+}
+
+/// Resolves names with the system resolver, the same as [`Tcp::connect`]
+///
+/// The lookup blocks the thread until it completes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemResolver;
+
+impl SystemResolver {
+	fn lookup(host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+		Ok((host, port).to_socket_addrs()?.collect())
+	}
+}
+
+#[asynchronous]
+impl Resolve for SystemResolver {
+	async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+		Self::lookup(host, port)
+	}
+}
+
+/// Resolves names with the system resolver on the thread pool, so that slow
+/// lookups do not block the runtime
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadPoolResolver;
+
+#[asynchronous]
+impl Resolve for ThreadPoolResolver {
+	async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+		run_blocking(|_| SystemResolver::lookup(host, port)).await?
+	}
+}
+
+/// The resolvers built into xx-pulse, so that one can be chosen at runtime.
+/// See [`BuiltinResolver::from_env`]
+#[derive(Clone, Debug)]
+pub enum BuiltinResolver {
+	ThreadPool(ThreadPoolResolver),
+	Dns(DnsResolver)
+}
+
+#[asynchronous]
+impl BuiltinResolver {
+	/// Choose the resolver with the `XX_PULSE_RESOLVER` environment variable
+	///
+	/// | Value | Resolver |
+	/// | - | - |
+	/// | `threadpool` | [`ThreadPoolResolver`]. The default if unset |
+	/// | `dns` | [`DnsResolver::from_system_config`] |
+	///
+	/// # Errors
+	/// If the variable has an unknown value, or if the DNS configuration could
+	/// not be read
+	pub async fn from_env() -> Result<Self> {
+		match std::env::var("XX_PULSE_RESOLVER").as_deref() {
+			Err(_) | Ok("threadpool") => Ok(Self::ThreadPool(ThreadPoolResolver)),
+			Ok("dns") => Ok(Self::Dns(DnsResolver::from_system_config().await?)),
+			Ok(_) => Err(fmt_error!(
+				"Unknown resolver in XX_PULSE_RESOLVER" @ ErrorKind::InvalidInput
+			))
+		}
+	}
+}
+
+#[asynchronous]
+impl Resolve for BuiltinResolver {
+	async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+		match self {
+			Self::ThreadPool(resolver) => resolver.resolve(host, port).await,
+			Self::Dns(resolver) => resolver.resolve(host, port).await
+		}
+	}
+}
+
+/// Answers names from a table of overrides, in the format of `/etc/hosts`.
+/// Names without an override are resolved by the fallback resolver
+///
+/// Names are matched case insensitively.
+#[derive(Clone, Debug)]
+pub struct HostsResolver<R> {
+	hosts: HashMap<String, Vec<IpAddr>>,
+	fallback: R
+}
+
+impl<R> HostsResolver<R> {
+	#[must_use]
+	pub fn new(fallback: R) -> Self {
+		Self { hosts: HashMap::new(), fallback }
+	}
+
+	/// Add `addr` to the addresses of `host`. Addresses are returned in the
+	/// order they were added
+	pub fn insert(&mut self, host: &str, addr: IpAddr) {
+		self.hosts
+			.entry(host.to_ascii_lowercase())
+			.or_default()
+			.push(addr);
+	}
+
+	/// Remove all overrides for `host`, so that it is resolved by the fallback
+	/// resolver
+	pub fn remove(&mut self, host: &str) {
+		self.hosts.remove(&host.to_ascii_lowercase());
+	}
+
+	/// Add the entries of a hosts file. Each line has an address followed by
+	/// the names for it, and text after a `#` is ignored
+	///
+	/// # Errors
+	/// If an address is invalid. Entries before the invalid line are kept
+	pub fn parse_hosts(&mut self, hosts: &str) -> Result<()> {
+		for line in hosts.lines() {
+			let line = line.split_once('#').map_or(line, |(line, _)| line);
+			let mut fields = line.split_whitespace();
+
+			let Some(addr) = fields.next() else {
+				continue;
+			};
+
+			let addr = addr.parse().map_err(|_| {
+				fmt_error!("Invalid address in hosts file" @ ErrorKind::InvalidData)
+			})?;
+
+			for host in fields {
+				self.insert(host, addr);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// The overrides for `host`
+	#[must_use]
+	pub fn get(&self, host: &str) -> Option<&[IpAddr]> {
+		self.hosts
+			.get(&host.to_ascii_lowercase())
+			.map(Vec::as_slice)
+	}
+
+	pub const fn fallback(&self) -> &R {
+		&self.fallback
+	}
+}
+
+#[asynchronous]
+impl<R> HostsResolver<R> {
+	/// Add the entries of the hosts file at `path`. See
+	/// [`HostsResolver::parse_hosts`]
+	///
+	/// # Errors
+	/// If reading the file fails, or if an address is invalid
+	#[allow(clippy::impl_trait_in_params)]
+	pub async fn load(&mut self, path: impl AsRef<Path>) -> Result<()> {
+		let hosts = fs::read_to_string(path).await?;
+
+		self.parse_hosts(&hosts)
+	}
+}
+
+#[asynchronous]
+impl<R: Resolve> Resolve for HostsResolver<R> {
+	async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+		match self.get(host) {
+			Some(addrs) => Ok(addrs
+				.iter()
+				.map(|addr| SocketAddr::new(*addr, port))
+				.collect()),
+			None => self.fallback.resolve(host, port).await
+		}
+	}
+}
+
+/// Split `addr` in the form `host:port` or `[ipv6]:port`
+fn split_host_port(addr: &str) -> Result<(&str, u16)> {
+	let invalid = || fmt_error!("Invalid address, expected host:port" @ ErrorKind::InvalidInput);
+	let (host, port) = addr.rsplit_once(':').ok_or_else(invalid)?;
+	let port = port.parse().map_err(|_| invalid())?;
+
+	let host = match host.strip_prefix('[') {
+		Some(host) => host.strip_suffix(']').ok_or_else(invalid)?,
+		None => host
+	};
+
+	Ok((host, port))
+}
+
+/// Resolve `addr` in the form `host:port` with `resolver`. IP addresses are
+/// returned without consulting the resolver
+#[asynchronous]
+pub async fn resolve_with<R>(resolver: &R, addr: &str) -> Result<Vec<SocketAddr>>
+where
+	R: Resolve
+{
+	let (host, port) = split_host_port(addr)?;
+
+	if let Ok(ip) = host.parse::<IpAddr>() {
+		return Ok(vec![SocketAddr::new(ip, port)]);
+	}
+
+	resolver.resolve(host, port).await
+}
@@ -1,8 +1,16 @@
 //! Common sockets and streams
 
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::mem::MaybeUninit;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::os::fd::AsRawFd;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
 
+use xx_core::async_std::AsyncIterator;
 use xx_core::coroutines::ops::{AsyncFn, AsyncFnExt, AsyncFnOnce};
 use xx_core::macros::*;
 use xx_core::os::epoll::PollFlag;
@@ -13,6 +21,9 @@ use xx_core::pointer::*;
 use xx_core::trace;
 
 use super::*;
+use crate::impls::TaskExt;
+use crate::ops::fd_budget::{open_reserve, take_reserve, try_acquire_permit, FdRelease};
+use crate::ops::io::{FdDisplay, ReadUninit};
 
 #[asynchronous]
 async fn foreach_addr<A, F, Output>(addrs: A, f: F) -> Result<Output>
@@ -32,15 +43,34 @@ where
 	Err(error.unwrap_or_else(|| common::NO_ADDRESSES.into()))
 }
 
+mod opt {
+	pub const SOL_IP: i32 = 0;
+	pub const SOL_SOCKET: i32 = 1;
+	pub const SOL_IPV6: i32 = 41;
+
+	pub const SO_REUSEPORT: i32 = 15;
+	pub const SO_BINDTODEVICE: i32 = 25;
+	pub const SO_MARK: i32 = 36;
+
+	pub const IP_RECVERR: i32 = 11;
+	pub const IPV6_RECVERR: i32 = 25;
+	pub const IPV6_V6ONLY: i32 = 26;
+
+	pub const IFNAMSIZ: usize = 16;
+}
+
 #[asynchronous]
-async fn bind_addr<A>(addr: A, socket_type: u32, protocol: IpProtocol) -> Result<Socket>
+async fn bind_addr_with<A, F>(
+	addr: A, socket_type: u32, protocol: IpProtocol, configure: F
+) -> Result<Socket>
 where
-	A: ToSocketAddrs
+	A: ToSocketAddrs,
+	F: Fn(&Socket, &Address) -> Result<()>
 {
 	foreach_addr(addr, |addr| async move {
 		let sock = Socket::new_for_addr(&addr, socket_type, protocol).await?;
 
-		set_reuse_addr(sock.fd(), true)?;
+		configure(&sock, &addr)?;
 		io::bind_addr(sock.fd(), &addr).await?;
 
 		Ok(sock)
@@ -49,13 +79,30 @@ where
 }
 
 #[asynchronous]
-async fn connect_addrs<A>(addr: A, socket_type: u32, protocol: IpProtocol) -> Result<Socket>
+async fn bind_addr<A>(addr: A, socket_type: u32, protocol: IpProtocol) -> Result<Socket>
 where
 	A: ToSocketAddrs
+{
+	bind_addr_with(addr, socket_type, protocol, |sock, _| {
+		set_reuse_addr(sock.fd(), true)?;
+
+		Ok(())
+	})
+	.await
+}
+
+#[asynchronous]
+async fn connect_addrs_with<A, F>(
+	addr: A, socket_type: u32, protocol: IpProtocol, configure: F
+) -> Result<Socket>
+where
+	A: ToSocketAddrs,
+	F: Fn(&Socket, &Address) -> Result<()>
 {
 	foreach_addr(addr, |addr| async move {
 		let sock = Socket::new_for_addr(&addr, socket_type, protocol).await?;
 
+		configure(&sock, &addr)?;
 		sock.connect(&addr).await?;
 
 		Ok(sock)
@@ -63,10 +110,18 @@ where
 	.await
 }
 
-#[allow(clippy::unwrap_used, clippy::missing_panics_doc)]
-fn convert_addr(storage: AddressStorage) -> SocketAddr {
-	/* into should be ok here unless OS is broken */
-	storage.try_into().unwrap()
+#[asynchronous]
+async fn connect_addrs<A>(addr: A, socket_type: u32, protocol: IpProtocol) -> Result<Socket>
+where
+	A: ToSocketAddrs
+{
+	connect_addrs_with(addr, socket_type, protocol, |_, _| Ok(())).await
+}
+
+fn convert_addr(storage: AddressStorage) -> Result<SocketAddr> {
+	storage
+		.try_into()
+		.map_err(|_| fmt_error!("Unsupported address family"))
 }
 
 #[asynchronous]
@@ -102,7 +157,7 @@ macro_rules! sync_io {
 		let $flags = $flags | MessageFlag::DontWait | MessageFlag::NoSignal;
 		let result = $func($fd, $buf, $flags);
 
-		if result != Err(OsError::WouldBlock) {
+		if result != Err(OsError::WouldBlock) && trace_enabled(TraceSubsystem::Net) {
 			trace!(target: $this, $($trace)*, result);
 		}
 
@@ -120,7 +175,7 @@ macro_rules! sync_buf_io {
 			$flags,
 			"## sync_{}(fd = {}, buf = &{}[u8; {}], flags = {}) = {:?}",
 			stringify!($func),
-			$fd.as_raw_fd(),
+			FdDisplay($fd.as_raw_fd()),
 			if stringify!($func) == "send" {
 				""
 			} else {
@@ -142,7 +197,7 @@ macro_rules! sync_hdr_io {
 			$flags,
 			"## sync_{}(fd = {}, header = {:?}, flags = {}) = {:?}",
 			stringify!($func),
-			$fd.as_raw_fd(),
+			FdDisplay($fd.as_raw_fd()),
 			ptr!((&*$hdr)),
 			$flags
 		)
@@ -158,6 +213,8 @@ macro_rules! impl_common {
 			) -> Result<usize> {
 				read_into!(buf);
 
+				self.check_read()?;
+
 				let this = ptr!(&*self);
 
 				with_budget(
@@ -176,6 +233,28 @@ macro_rules! impl_common {
 				.await
 			}
 
+			/// Receive into the possibly uninitialized buffer `buf`, avoiding the
+			/// need to zero it beforehand
+			///
+			/// Returns the part of the buffer that was received into.
+			pub async fn recv_uninit<'buf>(
+				&mut self, buf: &'buf mut [MaybeUninit<u8>], flags: BitFlags<MessageFlag>
+			) -> Result<&'buf mut [u8]> {
+				if buf.is_empty() {
+					return Ok(&mut []);
+				}
+
+				self.check_read()?;
+
+				let recvd = io::recv_uninit(self.fd.as_fd(), buf, flags).await?;
+				let recvd = check_interrupt_if_zero(recvd).await?;
+
+				self.ready.insert(PollFlag::In);
+
+				/* Safety: the kernel initialized `recvd` bytes */
+				Ok(unsafe { io::assume_init_prefix(buf, recvd) })
+			}
+
 			pub async fn recv_vectored(
 				&mut self, bufs: &mut [IoSliceMut<'_>], flags: BitFlags<MessageFlag>
 			) -> Result<usize> {
@@ -189,6 +268,8 @@ macro_rules! impl_common {
 			pub async fn recvmsg(
 				&mut self, header: &mut MsgHdrMut<'_>, flags: BitFlags<MessageFlag>
 			) -> Result<usize> {
+				self.check_read()?;
+
 				let this = ptr!(&*self);
 
 				with_budget(
@@ -211,6 +292,8 @@ macro_rules! impl_common {
 			) -> Result<usize> {
 				write_from!(buf);
 
+				self.check_write()?;
+
 				let this = ptr!(&*self);
 
 				with_budget(
@@ -232,6 +315,8 @@ macro_rules! impl_common {
 			pub async fn sendmsg(
 				&mut self, header: &MsgHdr<'_>, flags: BitFlags<MessageFlag>
 			) -> Result<usize> {
+				self.check_write()?;
+
 				let this = ptr!(&*self);
 
 				with_budget(
@@ -271,7 +356,7 @@ macro_rules! impl_common {
 
 				let recvd = self.recvmsg(&mut header, flags).await?;
 
-				Ok((recvd, convert_addr(addr)))
+				Ok((recvd, convert_addr(addr)?))
 			}
 
 			pub async fn sendto(
@@ -296,23 +381,66 @@ macro_rules! impl_common {
 			pub async fn poll(&mut self, flags: BitFlags<PollFlag>) -> Result<BitFlags<PollFlag>> {
 				self.ready.remove(flags);
 
-				let result = io::poll(self.fd.as_fd(), flags).await?;
+				/*
+				 * the peer closing its side makes the socket readable, so also
+				 * asking for the hang up does not change when the poll completes
+				 */
+				let mut result = if flags.contains(PollFlag::In) {
+					io::poll(self.fd.as_fd(), flags | PollFlag::RdHangUp).await?
+				} else {
+					io::poll(self.fd.as_fd(), flags).await?
+				};
 
 				self.ready.insert(result);
 
+				if !flags.contains(PollFlag::RdHangUp) {
+					result.remove(PollFlag::RdHangUp);
+				}
+
 				Ok(result)
 			}
 
+			/// Shut down part of the connection. The shutdown state is shared with
+			/// the socket's halves and clones
 			pub async fn shutdown(&mut self, how: Shutdown) -> Result<()> {
 				io::shutdown(self.fd(), how).await?;
 
-				let flags = match how {
-					Shutdown::Read => PollFlag::In.into(),
-					Shutdown::Write => PollFlag::Out.into(),
-					Shutdown::Both => PollFlag::In | PollFlag::Out
-				};
+				self.record_shutdown(shutdown_directions(how));
+
+				Ok(())
+			}
+
+			/// Which halves of the connection were shut down with
+			/// [`shutdown`](Self::shutdown), if any. Receiving fails with
+			/// [`OsError::NotConn`] after the read half is shut down, and
+			/// sending fails with [`OsError::Pipe`] after the write half is
+			/// shut down
+			#[must_use]
+			pub fn shutdown_state(&self) -> Option<Shutdown> {
+				let shut = self.shut_directions();
+				let read = shut.contains(Direction::Read);
+				let write = shut.contains(Direction::Write);
+
+				match (read, write) {
+					(false, false) => None,
+					(true, false) => Some(Shutdown::Read),
+					(false, true) => Some(Shutdown::Write),
+					(true, true) => Some(Shutdown::Both)
+				}
+			}
+
+			fn check_read(&self) -> Result<()> {
+				if self.shut_directions().contains(Direction::Read) {
+					return Err(OsError::NotConn.into());
+				}
+
+				Ok(())
+			}
 
-				self.ready.insert(flags);
+			fn check_write(&self) -> Result<()> {
+				if self.shut_directions().contains(Direction::Write) {
+					return Err(OsError::Pipe.into());
+				}
 
 				Ok(())
 			}
@@ -334,6 +462,16 @@ macro_rules! impl_common {
 			}
 		}
 
+		#[asynchronous]
+		#[allow(single_use_lifetimes)]
+		impl $($generics)* ReadUninit for $type $($generics)* {
+			async fn read_uninit<'buf>(
+				&mut self, buf: &'buf mut [MaybeUninit<u8>]
+			) -> Result<&'buf mut [u8]> {
+				self.recv_uninit(buf, BitFlags::default()).await
+			}
+		}
+
 		#[asynchronous]
 		#[allow(single_use_lifetimes)]
 		impl $($generics)* Write for $type $($generics)* {
@@ -365,12 +503,17 @@ macro_rules! socket_common {
 			#[must_use]
 			pub fn fd(&self) -> BorrowedFd<'_>;
 
+			pub fn set_trace_name(&self, name: &str);
+
 			#[asynchronous]
 			pub async fn close(self) -> Result<()>;
 
 			#[asynchronous]
 			pub async fn recv(&mut self, buf: &mut [u8], flags: BitFlags<MessageFlag>) -> Result<usize>;
 
+			#[asynchronous]
+			pub async fn recv_uninit<'buf>(&mut self, buf: &'buf mut [MaybeUninit<u8>], flags: BitFlags<MessageFlag>) -> Result<&'buf mut [u8]>;
+
 			#[asynchronous]
 			pub async fn recv_vectored(&mut self, bufs: &mut [IoSliceMut<'_>], flags: BitFlags<MessageFlag>) -> Result<usize>;
 
@@ -398,6 +541,9 @@ macro_rules! socket_common {
 			#[asynchronous]
 			pub async fn shutdown(&mut self, how: Shutdown) -> Result<()>;
 
+			#[must_use]
+			pub fn shutdown_state(&self) -> Option<Shutdown>;
+
 			#[asynchronous]
 			pub async fn set_recvbuf_size(&self, size: i32) -> Result<()>;
 
@@ -409,6 +555,11 @@ macro_rules! socket_common {
 
 			#[asynchronous]
 			pub async fn peer_addr(&self) -> Result<SocketAddr>;
+
+			pub fn bytes_available(&self) -> Result<usize>;
+
+			#[asynchronous]
+			pub async fn recv_available(&mut self, max: usize, flags: BitFlags<MessageFlag>) -> Result<Vec<u8>>;
 		}
 
 		pub fn try_clone(&self) -> Result<Self> {
@@ -428,6 +579,15 @@ macro_rules! socket_impl {
 			}
 		}
 
+		#[asynchronous]
+		impl ReadUninit for $type {
+			async fn read_uninit<'buf>(
+				&mut self, buf: &'buf mut [MaybeUninit<u8>]
+			) -> Result<&'buf mut [u8]> {
+				self.socket.read_uninit(buf).await
+			}
+		}
+
 		impl Write for $type {
 			write_wrapper! {
 				inner = socket;
@@ -440,7 +600,7 @@ macro_rules! socket_impl {
 			type Writer<'a> = SocketHalf<'a>;
 
 			fn try_split(&mut self) -> Result<(Self::Reader<'_>, Self::Writer<'_>)> {
-				let half = SocketHalf::new(self.fd(), self.socket.ready);
+				let half = self.socket.half();
 
 				Ok((half, half))
 			}
@@ -448,9 +608,32 @@ macro_rules! socket_impl {
 	};
 }
 
+/// The halves of a connection that were shut down
+#[bitflags]
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Direction {
+	Read  = 1 << 0,
+	Write = 1 << 1
+}
+
+fn shutdown_directions(how: Shutdown) -> BitFlags<Direction> {
+	match how {
+		Shutdown::Read => Direction::Read.into(),
+		Shutdown::Write => Direction::Write.into(),
+		Shutdown::Both => Direction::Read | Direction::Write
+	}
+}
+
+fn load_directions(shut: &AtomicU8) -> BitFlags<Direction> {
+	BitFlags::from_bits_truncate(shut.load(Ordering::Relaxed))
+}
+
 pub struct Socket {
 	fd: OwnedFd,
-	ready: BitFlags<PollFlag>
+	ready: BitFlags<PollFlag>,
+	shut: Arc<AtomicU8>,
+	release: FdRelease
 }
 
 impl_common!(Socket);
@@ -460,9 +643,7 @@ impl Socket {
 	pub async fn new(
 		domain: AddressFamily, socket_type: u32, protocol: IpProtocol
 	) -> Result<Self> {
-		let fd = io::socket(domain, socket_type, protocol).await?;
-
-		Ok(Self { fd, ready: BitFlags::default() })
+		Ok(io::socket(domain, socket_type, protocol).await?.into())
 	}
 
 	pub async fn new_for_addr(
@@ -480,7 +661,14 @@ impl Socket {
 		self.fd.as_fd()
 	}
 
+	/// Label this socket with `name` in trace output. See
+	/// [`io::set_trace_name`]
+	pub fn set_trace_name(&self, name: &str) {
+		io::set_trace_name(self.fd(), Some(name));
+	}
+
 	pub async fn close(self) -> Result<()> {
+		drop(self.release);
 		io::close(self.fd).await
 	}
 
@@ -508,6 +696,58 @@ impl Socket {
 		set_tcp_keepalive(self.fd(), enable, idle).map_err(Into::into)
 	}
 
+	fn set_option<T>(&self, level: i32, option: i32, value: &T) -> Result<()> {
+		/* Safety: callers pass the type the option expects */
+		unsafe { set_sock_opt(self.fd(), level, option, value) }.map_err(Into::into)
+	}
+
+	fn set_reuse_port(&self, enable: bool) -> Result<()> {
+		self.set_option(opt::SOL_SOCKET, opt::SO_REUSEPORT, &i32::from(enable))
+	}
+
+	fn set_v6_only(&self, enable: bool) -> Result<()> {
+		self.set_option(opt::SOL_IPV6, opt::IPV6_V6ONLY, &i32::from(enable))
+	}
+
+	fn bind_to_device(&self, device: &str) -> Result<()> {
+		let mut name = [0u8; opt::IFNAMSIZ];
+
+		/* leave room for the null terminator */
+		if device.len() >= name.len() || device.contains('\0') {
+			return Err(fmt_error!("Invalid device name"));
+		}
+
+		name[0..device.len()].copy_from_slice(device.as_bytes());
+
+		self.set_option(opt::SOL_SOCKET, opt::SO_BINDTODEVICE, &name)
+	}
+
+	fn set_mark(&self, mark: u32) -> Result<()> {
+		self.set_option(opt::SOL_SOCKET, opt::SO_MARK, &mark)
+	}
+
+	/// Apply the options that choose how the socket's traffic is routed
+	fn set_routing(&self, device: Option<&str>, mark: Option<u32>) -> Result<()> {
+		if let Some(device) = device {
+			self.bind_to_device(device)?;
+		}
+
+		if let Some(mark) = mark {
+			self.set_mark(mark)?;
+		}
+
+		Ok(())
+	}
+
+	fn set_recv_errors(&self, addr: &Address, enable: bool) -> Result<()> {
+		let enable = i32::from(enable);
+
+		match addr {
+			Address::V4(_) => self.set_option(opt::SOL_IP, opt::IP_RECVERR, &enable),
+			Address::V6(_) => self.set_option(opt::SOL_IPV6, opt::IPV6_RECVERR, &enable)
+		}
+	}
+
 	#[allow(clippy::unused_async)]
 	pub async fn local_addr(&self) -> Result<SocketAddr> {
 		let mut addr = AddressStorage::default();
@@ -515,7 +755,7 @@ impl Socket {
 		/* Safety: addr is able to store addresses */
 		unsafe { get_sock_name(self.fd(), &mut addr)? };
 
-		Ok(convert_addr(addr))
+		convert_addr(addr)
 	}
 
 	#[allow(clippy::unused_async)]
@@ -525,31 +765,195 @@ impl Socket {
 		/* Safety: addr is able to store addresses */
 		unsafe { get_peer_name(self.fd(), &mut addr)? };
 
-		Ok(convert_addr(addr))
+		convert_addr(addr)
+	}
+
+	/// Returns the number of bytes that can be received without blocking. For
+	/// a datagram socket, this is the size of the next datagram
+	pub fn bytes_available(&self) -> Result<usize> {
+		io::bytes_available(self.fd())
+	}
+
+	/// Receive into a new buffer sized to the data that is available, up to
+	/// `max` bytes, waiting for data to arrive if there is none
+	///
+	/// This avoids allocating large buffers for small messages, and receives
+	/// a whole datagram without truncation if `max` permits. An empty buffer
+	/// means the peer shut down the connection, or an empty datagram was
+	/// received. If `max` is zero, nothing is received.
+	pub async fn recv_available(
+		&mut self, max: usize, flags: BitFlags<MessageFlag>
+	) -> Result<Vec<u8>> {
+		self.check_read()?;
+
+		/* receiving zero bytes would discard a waiting datagram */
+		if max == 0 {
+			return Ok(Vec::new());
+		}
+
+		let mut available = self.bytes_available()?;
+
+		if available == 0 {
+			self.poll(PollFlag::In.into()).await?;
+
+			available = self.bytes_available()?;
+		}
+
+		let len = available.min(max);
+
+		if len == 0 {
+			/* consume the empty datagram, if any */
+			io::recv(self.fd(), &mut [], flags).await?;
+
+			return Ok(Vec::new());
+		}
+
+		let mut buf = Vec::with_capacity(len);
+		let recvd = self
+			.recv_uninit(&mut buf.spare_capacity_mut()[0..len], flags)
+			.await?
+			.len();
+
+		/* Safety: `recvd` bytes were initialized by the kernel */
+		unsafe { buf.set_len(recvd) };
+
+		Ok(buf)
+	}
+
+	/// Receive exactly `buf.len()` bytes, such as a fixed size protocol
+	/// header
+	///
+	/// Sets `MSG_WAITALL`, so the kernel waits for the whole buffer to be
+	/// filled before completing, instead of waking the task for every partial
+	/// receive. Only meaningful for stream sockets. If the kernel still
+	/// returns early, for example because of a signal, the rest is received
+	/// in a loop
+	///
+	/// # Errors
+	/// With [`ErrorKind::UnexpectedEof`] if the peer closed the connection
+	/// before the buffer was filled
+	///
+	/// # Cancel safety
+	///
+	/// This function is not cancel safe. Data received before the
+	/// cancellation is lost.
+	pub async fn recv_all(&mut self, buf: &mut [u8], flags: BitFlags<MessageFlag>) -> Result<()> {
+		let mut buf = buf;
+
+		self.check_read()?;
+
+		while !buf.is_empty() {
+			let recvd = io::recv(self.fd(), buf, flags | MessageFlag::WaitAll).await?;
+
+			if recvd == 0 {
+				check_interrupt().await?;
+
+				return Err(fmt_error!(
+					"Connection closed before the buffer was filled" @ ErrorKind::UnexpectedEof
+				));
+			}
+
+			buf = &mut buf[recvd..];
+		}
+
+		self.ready.insert(PollFlag::In);
+
+		Ok(())
+	}
+
+	/// Check whether the peer closed its side of the connection, without
+	/// consuming any data and without waiting
+	///
+	/// Returns `false` if there is data to receive, even if the peer closed
+	/// the connection after sending it. A reset connection is considered
+	/// closed.
+	///
+	/// # Errors
+	/// With [`OsError::NotConn`] if the read half was shut down locally, as a
+	/// peek would report the end of the stream either way
+	pub fn is_peer_closed(&self) -> Result<bool> {
+		self.check_read()?;
+
+		if self.ready.contains(PollFlag::RdHangUp) && self.bytes_available()? == 0 {
+			return Ok(true);
+		}
+
+		let mut byte = [0u8; 1];
+		let flags = MessageFlag::Peek | MessageFlag::DontWait;
+
+		/* Safety: byte is valid */
+		match unsafe { recv(self.fd(), (&mut byte[..]).into(), flags) } {
+			Ok(peeked) => Ok(peeked == 0),
+			Err(OsError::WouldBlock) => Ok(false),
+			Err(OsError::ConnReset) => Ok(true),
+			Err(err) => Err(err.into())
+		}
+	}
+
+	/// Wait until there is data to receive or the peer closed its side of the
+	/// connection, then report whether the peer closed it. No data is
+	/// consumed. See [`Socket::is_peer_closed`]
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	pub async fn probe_eof(&mut self) -> Result<bool> {
+		let ready = self.poll(PollFlag::In | PollFlag::RdHangUp).await?;
+
+		if ready.intersects(PollFlag::HangUp | PollFlag::Error) {
+			return Ok(true);
+		}
+
+		self.is_peer_closed()
 	}
 
 	#[must_use]
 	pub fn half(&self) -> SocketHalf<'_> {
-		SocketHalf::new(self.fd(), self.ready)
+		SocketHalf {
+			fd: self.fd(),
+			ready: self.ready,
+			shut: BitFlags::EMPTY,
+			shared: Some(&self.shut)
+		}
+	}
+
+	fn shut_directions(&self) -> BitFlags<Direction> {
+		load_directions(&self.shut)
+	}
+
+	fn record_shutdown(&mut self, directions: BitFlags<Direction>) {
+		self.shut.fetch_or(directions.bits(), Ordering::Relaxed);
 	}
 
 	pub fn try_clone(&self) -> Result<Self> {
 		let fd = self.fd.try_clone()?;
+		let release = FdRelease::new(fd.as_fd());
 
-		Ok(Self { fd, ready: self.ready })
+		Ok(Self { fd, ready: self.ready, shut: self.shut.clone(), release })
 	}
 }
 
 impl From<OwnedFd> for Socket {
 	fn from(fd: OwnedFd) -> Self {
-		Self { fd, ready: BitFlags::default() }
+		let release = FdRelease::new(fd.as_fd());
+
+		Self {
+			fd,
+			ready: BitFlags::default(),
+			shut: Arc::default(),
+			release
+		}
 	}
 }
 
+/// A borrowed half of a [`Socket`]. Halves share the shutdown state of the
+/// socket they were created from
 #[derive(Clone, Copy)]
 pub struct SocketHalf<'a> {
 	fd: BorrowedFd<'a>,
-	ready: BitFlags<PollFlag>
+	ready: BitFlags<PollFlag>,
+	shut: BitFlags<Direction>,
+	shared: Option<&'a AtomicU8>
 }
 
 impl_common!(SocketHalf<'a>);
@@ -558,18 +962,33 @@ impl_common!(SocketHalf<'a>);
 impl<'a> SocketHalf<'a> {
 	#[must_use]
 	pub const fn new(fd: BorrowedFd<'a>, ready: BitFlags<PollFlag>) -> Self {
-		Self { fd, ready }
+		Self { fd, ready, shut: BitFlags::EMPTY, shared: None }
 	}
 
 	#[must_use]
 	pub const fn fd(&self) -> BorrowedFd<'_> {
 		self.fd
 	}
+
+	fn shut_directions(&self) -> BitFlags<Direction> {
+		match self.shared {
+			Some(shared) => self.shut | load_directions(shared),
+			None => self.shut
+		}
+	}
+
+	fn record_shutdown(&mut self, directions: BitFlags<Direction>) {
+		self.shut |= directions;
+
+		if let Some(shared) = self.shared {
+			shared.fetch_or(directions.bits(), Ordering::Relaxed);
+		}
+	}
 }
 
 impl<'a> From<BorrowedFd<'a>> for SocketHalf<'a> {
 	fn from(fd: BorrowedFd<'a>) -> Self {
-		Self { fd, ready: BitFlags::default() }
+		Self::new(fd, BitFlags::default())
 	}
 }
 
@@ -592,12 +1011,31 @@ impl StreamSocket {
 	wrapper_functions! {
 		inner = self.socket;
 
+		pub fn is_peer_closed(&self) -> Result<bool>;
+
+		#[asynchronous]
+		pub async fn recv_all(&mut self, buf: &mut [u8], flags: BitFlags<MessageFlag>) -> Result<()>;
+
+		#[asynchronous]
+		pub async fn probe_eof(&mut self) -> Result<bool>;
+
 		#[asynchronous]
 		pub async fn set_tcp_nodelay(&self, enable: bool) -> Result<()>;
 
 		#[asynchronous]
 		pub async fn set_tcp_keepalive(&self, enable: bool, idle: i32) -> Result<()>;
 	}
+
+	pub(crate) fn half(&self) -> SocketHalf<'_> {
+		self.socket.half()
+	}
+
+	/// Convert this socket into a [`WriteQueue`], which coalesces small
+	/// writes in the background
+	#[must_use]
+	pub fn into_write_queue(self) -> WriteQueue {
+		WriteQueue::new(self)
+	}
 }
 
 socket_impl!(StreamSocket);
@@ -646,8 +1084,79 @@ impl DatagramSocket {
 
 socket_impl!(DatagramSocket);
 
+/// How a [`TcpListener`] backs off when accepting a connection fails because
+/// the process or the system ran out of file descriptors. Without a backoff,
+/// the pending connection stays in the backlog and every accept fails
+/// immediately, spinning the accept loop
+#[derive(Clone, Copy, Debug)]
+pub struct AcceptBackoff {
+	/// The delay after the first failure, doubled after each consecutive
+	/// failure
+	pub initial: Duration,
+
+	/// The longest delay
+	pub max: Duration,
+
+	/// Use the spare file descriptor of the runtime, which is released to
+	/// accept and immediately close a pending connection when out of file
+	/// descriptors. The peer then sees the connection closed, instead of
+	/// waiting in the backlog. One spare is shared by all listeners on a
+	/// runtime, and opened again by the next accept after it is used
+	pub reserve_fd: bool
+}
+
+impl AcceptBackoff {
+	/// The delay after `failures` consecutive failures, with up to half of it
+	/// randomized so that many listeners don't retry in lockstep
+	fn delay(&self, failures: u32) -> Duration {
+		let delay = self
+			.initial
+			.saturating_mul(2u32.saturating_pow(failures))
+			.min(self.max);
+
+		let mut hasher = RandomState::new().build_hasher();
+
+		hasher.write_u64(nanotime());
+
+		#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+		let jitter = (delay / 2).saturating_mul((hasher.finish() % 0x400) as u32) / 0x400;
+
+		delay.saturating_sub(jitter)
+	}
+}
+
+impl Default for AcceptBackoff {
+	fn default() -> Self {
+		Self {
+			initial: Duration::from_millis(1),
+			max: Duration::from_secs(1),
+			reserve_fd: true
+		}
+	}
+}
+
+/// An event raised by [`TcpListener::accept`] while the process or the system
+/// is out of file descriptors. See [`TcpListener::on_accept_event`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AcceptEvent {
+	/// Accepting failed with `error`. The listener waits for `delay` before
+	/// trying again
+	Exhausted { error: OsError, delay: Duration },
+
+	/// A pending connection was accepted with the reserve file descriptor and
+	/// closed
+	Shed,
+
+	/// A connection was accepted after one or more failures
+	Recovered
+}
+
 pub struct TcpListener {
-	socket: Socket
+	socket: Socket,
+	backoff: AcceptBackoff,
+	on_event: Option<Box<dyn Fn(AcceptEvent) + Send>>,
+	failures: Cell<u32>,
+	batch_error: Cell<Option<Error>>
 }
 
 impl TcpListener {
@@ -657,6 +1166,8 @@ impl TcpListener {
 		#[asynchronous]
 		async fn close(self) -> Result<()>;
 
+		pub fn set_trace_name(&self, name: &str);
+
 		#[asynchronous]
 		pub async fn local_addr(&self) -> Result<SocketAddr>;
 
@@ -664,14 +1175,328 @@ impl TcpListener {
 		pub async fn peer_addr(&self) -> Result<SocketAddr>;
 	}
 
+	#[asynchronous]
+	async fn new(socket: Socket) -> Self {
+		open_reserve().await;
+
+		Self {
+			socket,
+			backoff: AcceptBackoff::default(),
+			on_event: None,
+			failures: Cell::new(0),
+			batch_error: Cell::new(None)
+		}
+	}
+
+	fn emit(&self, event: AcceptEvent) {
+		if let Some(callback) = &self.on_event {
+			callback(event);
+		}
+	}
+
+	/// Set how accepting backs off when out of file descriptors. See
+	/// [`AcceptBackoff`]
+	pub fn set_accept_backoff(&mut self, backoff: AcceptBackoff) {
+		self.backoff = backoff;
+	}
+
+	/// Call `callback` whenever accepting runs out of file descriptors, sheds
+	/// a connection, or recovers. See [`AcceptEvent`]
+	pub fn on_accept_event<F>(&mut self, callback: F)
+	where
+		F: Fn(AcceptEvent) + Send + 'static
+	{
+		self.on_event = Some(Box::new(callback));
+	}
+
+	fn accepted(&self) {
+		if self.failures.replace(0) != 0 {
+			self.emit(AcceptEvent::Recovered);
+		}
+	}
+
+	#[asynchronous]
+	async fn exhausted(&self, error: OsError) -> Result<()> {
+		let failures = self.failures.get();
+		let delay = self.backoff.delay(failures);
+
+		self.failures.set(failures.saturating_add(1));
+		self.emit(AcceptEvent::Exhausted { error, delay });
+
+		let reserve = if self.backoff.reserve_fd {
+			take_reserve().await
+		} else {
+			None
+		};
+
+		if let Some(reserve) = reserve {
+			/* free up a descriptor, and use it to drain a pending connection. the
+			 * accept doubles as the backoff if no connection arrives in time.
+			 * the next accept opens the reserve again, even if this one is
+			 * cancelled
+			 */
+			drop(reserve);
+
+			let mut storage = AddressStorage::default();
+
+			/* Safety: storage is able to store addresses. the connection is
+			 * closed right away, so it does not wait for the budget
+			 */
+			let shed = unsafe { io::accept_unbudgeted(self.socket.fd(), &mut storage) }
+				.timeout(delay)
+				.await;
+
+			if let Some(Ok((fd, _))) = shed {
+				let _ = io::close(fd).await;
+
+				self.emit(AcceptEvent::Shed);
+			}
+		} else {
+			sleep(delay).await?;
+		}
+
+		check_interrupt().await
+	}
+
+	/// Accept a connection
+	///
+	/// If the process or the system is out of file descriptors, this function
+	/// backs off and tries again, instead of failing. See [`AcceptBackoff`]
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
 	#[asynchronous]
 	pub async fn accept(&self) -> Result<(StreamSocket, SocketAddr)> {
+		loop {
+			if self.backoff.reserve_fd {
+				open_reserve().await;
+			}
+
+			let mut storage = AddressStorage::default();
+
+			/* Safety: storage is able to store addresses */
+			match unsafe { io::accept(self.socket.fd(), &mut storage).await } {
+				Ok((fd, _)) => {
+					self.accepted();
+
+					return Ok((StreamSocket { socket: fd.into() }, convert_addr(storage)?));
+				}
+
+				Err(err) => match err.os_error() {
+					Some(error @ (OsError::MFile | OsError::NFile)) => self.exhausted(error).await?,
+					_ => return Err(err)
+				}
+			}
+		}
+	}
+
+	/// Accept a connection that is already pending with a synchronous
+	/// syscall. Returns `None` if there is none, or the file descriptor budget
+	/// is exhausted
+	#[asynchronous]
+	async fn accept_pending(&self) -> Result<Option<(StreamSocket, SocketAddr)>> {
+		let Some(permit) = try_acquire_permit().await else {
+			return Ok(None);
+		};
+
 		let mut storage = AddressStorage::default();
 
 		/* Safety: storage is able to store addresses */
-		let (fd, _) = unsafe { io::accept(self.socket.fd(), &mut storage).await? };
+		let fd = match unsafe { io::accept_pending(self.socket.fd(), &mut storage) } {
+			Ok((fd, _)) => fd,
+			Err(err) if err.os_error() == Some(OsError::WouldBlock) => return Ok(None),
+			Err(err) => return Err(err)
+		};
+
+		permit.bind(fd.as_fd());
+
+		Ok(Some((StreamSocket { socket: fd.into() }, convert_addr(storage)?)))
+	}
+
+	/// Accept up to `max` connections at once. Waits for the first connection,
+	/// then accepts the connections that are already pending with synchronous
+	/// syscalls, without waiting for more
+	///
+	/// Accepting a burst of connections in one call saves a completion and a
+	/// wake up per connection. Running out of file descriptors after the first
+	/// connection ends the batch early. If accepting fails for another reason
+	/// after the first connection, the connections accepted so far are
+	/// returned, and the error is returned by the next call to this function
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	#[asynchronous]
+	pub async fn accept_batch(&self, max: usize) -> Result<Vec<(StreamSocket, SocketAddr)>> {
+		if let Some(err) = self.batch_error.take() {
+			return Err(err);
+		}
+
+		let mut accepted = Vec::new();
+
+		if max == 0 {
+			return Ok(accepted);
+		}
+
+		accepted.push(self.accept().await?);
+
+		while accepted.len() < max {
+			match self.accept_pending().await {
+				Ok(Some(connection)) => accepted.push(connection),
+				Ok(None) => break,
+				Err(err) => {
+					if !matches!(err.os_error(), Some(OsError::MFile | OsError::NFile)) {
+						self.batch_error.set(Some(err));
+					}
+
+					break;
+				}
+			}
+		}
+
+		Ok(accepted)
+	}
+
+	/// Returns an async iterator over the incoming connections of this
+	/// listener. The iterator never ends.
+	#[must_use]
+	pub const fn incoming(&self) -> Incoming<'_> {
+		Incoming { listener: self }
+	}
+}
+
+/// An async iterator over the connections of a [`TcpListener`]. See
+/// [`TcpListener::incoming`] for more information.
+pub struct Incoming<'a> {
+	listener: &'a TcpListener
+}
+
+#[asynchronous]
+impl AsyncIterator for Incoming<'_> {
+	type Item = Result<(StreamSocket, SocketAddr)>;
+
+	/// Accept the next connection
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	async fn next(&mut self) -> Option<Self::Item> {
+		Some(self.listener.accept().await)
+	}
+}
+
+type Accepted = Result<(StreamSocket, SocketAddr)>;
+
+#[asynchronous]
+async fn race_accept(listeners: &[&TcpListener], extra: &RefCell<VecDeque<Accepted>>) -> Accepted {
+	let (listener, rest) = match listeners {
+		[] => return Err(common::NO_ADDRESSES.into()),
+		[listener] => return listener.accept().await,
+		[listener, rest @ ..] => (listener, rest)
+	};
+
+	/* if both branches accepted a connection, keep the other one for later */
+	let (accepted, other) = match select(listener.accept(), race_accept(rest, extra)).await {
+		Select::First(accepted, other) | Select::Second(accepted, other) => (accepted, other)
+	};
+
+	if let Some(other) = other {
+		extra.borrow_mut().push_back(other);
+	}
+
+	accepted
+}
+
+/// A group of listeners bound to every address of a name, such as the IPv4
+/// and IPv6 addresses of a dual stack server. See [`Tcp::bind_all`]
+pub struct MultiListener {
+	listeners: Vec<TcpListener>,
+	extra: RefCell<VecDeque<Accepted>>,
+	next: Cell<usize>
+}
+
+#[asynchronous]
+impl MultiListener {
+	/// The listeners, in the order their addresses were resolved
+	#[must_use]
+	pub fn listeners(&self) -> &[TcpListener] {
+		&self.listeners
+	}
+
+	pub fn into_listeners(self) -> Vec<TcpListener> {
+		self.listeners
+	}
+
+	/// The local addresses of the listeners
+	pub async fn local_addrs(&self) -> Result<Vec<SocketAddr>> {
+		let mut addrs = Vec::with_capacity(self.listeners.len());
+
+		for listener in &self.listeners {
+			addrs.push(listener.local_addr().await?);
+		}
+
+		Ok(addrs)
+	}
+
+	/// Set the accept backoff of every listener. See
+	/// [`TcpListener::set_accept_backoff`]
+	pub fn set_accept_backoff(&mut self, backoff: AcceptBackoff) {
+		for listener in &mut self.listeners {
+			listener.set_accept_backoff(backoff);
+		}
+	}
+
+	/// Accept a connection from whichever listener receives one first. The
+	/// listener polled first rotates between calls, so that a busy listener
+	/// does not starve the others
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe. Connections accepted by more than one
+	/// listener at once are returned by later calls.
+	pub async fn accept(&self) -> Result<(StreamSocket, SocketAddr)> {
+		if let Some(accepted) = self.extra.borrow_mut().pop_front() {
+			return accepted;
+		}
 
-		Ok((StreamSocket { socket: fd.into() }, convert_addr(storage)))
+		let start = self.next.get() % self.listeners.len().max(1);
+		let listeners: Vec<_> = self.listeners[start..]
+			.iter()
+			.chain(&self.listeners[..start])
+			.collect();
+
+		#[allow(clippy::arithmetic_side_effects)]
+		self.next.set(start + 1);
+
+		race_accept(&listeners, &self.extra).await
+	}
+
+	/// Returns an async iterator over the incoming connections of all the
+	/// listeners. The iterator never ends.
+	#[must_use]
+	pub const fn incoming(&self) -> MultiIncoming<'_> {
+		MultiIncoming { listener: self }
+	}
+}
+
+/// An async iterator over the connections of a [`MultiListener`]. See
+/// [`MultiListener::incoming`] for more information.
+pub struct MultiIncoming<'a> {
+	listener: &'a MultiListener
+}
+
+#[asynchronous]
+impl AsyncIterator for MultiIncoming<'_> {
+	type Item = Result<(StreamSocket, SocketAddr)>;
+
+	/// Accept the next connection
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	async fn next(&mut self) -> Option<Self::Item> {
+		Some(self.listener.accept().await)
 	}
 }
 
@@ -689,6 +1514,18 @@ impl Tcp {
 		Ok(StreamSocket { socket: sock })
 	}
 
+	/// Connect to `addr` in the form `host:port`, resolving the host with
+	/// `resolver` instead of the system resolver. See [`Resolve`]
+	pub async fn connect_with<R>(resolver: &R, addr: &str) -> Result<StreamSocket>
+	where
+		R: Resolve
+	{
+		let addrs = resolve_with(resolver, addr).await?;
+		let sock = connect_addrs(&addrs[..], SocketType::Stream as u32, IpProtocol::Tcp).await?;
+
+		Ok(StreamSocket { socket: sock })
+	}
+
 	pub async fn bind<A>(addr: A) -> Result<TcpListener>
 	where
 		A: ToSocketAddrs
@@ -697,7 +1534,186 @@ impl Tcp {
 
 		io::listen(sock.fd(), MAX_BACKLOG).await?;
 
-		Ok(TcpListener { socket: sock })
+		Ok(TcpListener::new(sock).await)
+	}
+
+	/// Bind a listener to every address that `addrs` resolves to, instead of
+	/// only the first one that succeeds. IPv6 listeners only accept IPv6
+	/// connections, so that wildcard IPv4 and IPv6 addresses can share a port
+	///
+	/// # Errors
+	/// If no addresses were resolved, or if binding any of the addresses
+	/// failed. Listeners bound before the failure are closed
+	pub async fn bind_all<A>(addrs: A) -> Result<MultiListener>
+	where
+		A: ToSocketAddrs
+	{
+		let mut listeners = Vec::new();
+
+		for addr in addrs.to_socket_addrs()? {
+			let addr = Address::from(addr);
+			let sock =
+				Socket::new_for_addr(&addr, SocketType::Stream as u32, IpProtocol::Tcp).await?;
+
+			set_reuse_addr(sock.fd(), true)?;
+
+			if matches!(addr, Address::V6(_)) {
+				sock.set_v6_only(true)?;
+			}
+
+			io::bind_addr(sock.fd(), &addr).await?;
+			io::listen(sock.fd(), MAX_BACKLOG).await?;
+
+			listeners.push(TcpListener::new(sock).await);
+		}
+
+		if listeners.is_empty() {
+			return Err(common::NO_ADDRESSES.into());
+		}
+
+		Ok(MultiListener { listeners, extra: RefCell::new(VecDeque::new()), next: Cell::new(0) })
+	}
+
+	/// Create a builder for a [`StreamSocket`] or [`TcpListener`], for options
+	/// that must be set before the socket is connected or bound
+	#[must_use]
+	pub fn builder() -> TcpBuilder {
+		TcpBuilder::new()
+	}
+}
+
+/// A builder for connecting a [`StreamSocket`] or binding a [`TcpListener`].
+/// See [`Tcp::builder`]
+///
+/// # Examples
+///
+/// ```
+/// let stream = Tcp::builder()
+/// 	.device("wg0")
+/// 	.mark(0x100)
+/// 	.connect("10.0.0.1:443")
+/// 	.await?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct TcpBuilder {
+	device: Option<String>,
+	mark: Option<u32>,
+	reuse_port: bool,
+	recvbuf_size: Option<i32>,
+	sendbuf_size: Option<i32>
+}
+
+#[asynchronous]
+impl TcpBuilder {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			device: None,
+			mark: None,
+			reuse_port: false,
+			recvbuf_size: None,
+			sendbuf_size: None
+		}
+	}
+
+	/// Only send and receive packets through the network interface `device`.
+	/// Requires `CAP_NET_RAW` on older kernels
+	#[must_use]
+	pub fn device(mut self, device: impl Into<String>) -> Self {
+		self.device = Some(device.into());
+		self
+	}
+
+	/// Tag outgoing packets with the firewall mark `mark`, which routing
+	/// rules can use to select a routing table. Requires `CAP_NET_ADMIN`
+	#[must_use]
+	pub const fn mark(mut self, mark: u32) -> Self {
+		self.mark = Some(mark);
+		self
+	}
+
+	/// Allow multiple listeners to bind to the same address. The kernel
+	/// distributes incoming connections between them
+	#[must_use]
+	pub const fn reuse_port(mut self, enable: bool) -> Self {
+		self.reuse_port = enable;
+		self
+	}
+
+	#[must_use]
+	pub const fn recvbuf_size(mut self, size: i32) -> Self {
+		self.recvbuf_size = Some(size);
+		self
+	}
+
+	#[must_use]
+	pub const fn sendbuf_size(mut self, size: i32) -> Self {
+		self.sendbuf_size = Some(size);
+		self
+	}
+
+	fn configure(&self, sock: &Socket) -> Result<()> {
+		sock.set_routing(self.device.as_deref(), self.mark)?;
+
+		if let Some(size) = self.recvbuf_size {
+			set_recvbuf_size(sock.fd(), size)?;
+		}
+
+		if let Some(size) = self.sendbuf_size {
+			set_sendbuf_size(sock.fd(), size)?;
+		}
+
+		Ok(())
+	}
+
+	/// Create the socket and connect it to the first address in `addrs` that
+	/// succeeds
+	pub async fn connect<A>(self, addrs: A) -> Result<StreamSocket>
+	where
+		A: ToSocketAddrs
+	{
+		let sock = connect_addrs_with(
+			addrs,
+			SocketType::Stream as u32,
+			IpProtocol::Tcp,
+			|sock, _| self.configure(sock)
+		)
+		.await?;
+
+		Ok(StreamSocket { socket: sock })
+	}
+
+	/// Create the listener and bind it to the first address in `addrs` that
+	/// succeeds
+	pub async fn bind<A>(self, addrs: A) -> Result<TcpListener>
+	where
+		A: ToSocketAddrs
+	{
+		let sock = bind_addr_with(
+			addrs,
+			SocketType::Stream as u32,
+			IpProtocol::Tcp,
+			|sock, _| {
+				set_reuse_addr(sock.fd(), true)?;
+
+				if self.reuse_port {
+					sock.set_reuse_port(true)?;
+				}
+
+				self.configure(sock)
+			}
+		)
+		.await?;
+
+		io::listen(sock.fd(), MAX_BACKLOG).await?;
+
+		Ok(TcpListener::new(sock).await)
+	}
+}
+
+impl Default for TcpBuilder {
+	fn default() -> Self {
+		Self::new()
 	}
 }
 
@@ -723,4 +1739,158 @@ impl Udp {
 
 		Ok(DatagramSocket { socket: sock })
 	}
+
+	/// Create a builder for a [`DatagramSocket`], for options that must be set
+	/// before the socket is bound or connected
+	#[must_use]
+	pub fn builder() -> UdpBuilder {
+		UdpBuilder::new()
+	}
+}
+
+/// A builder for binding or connecting a [`DatagramSocket`]. See
+/// [`Udp::builder`]
+///
+/// # Examples
+///
+/// ```
+/// let socket = Udp::builder()
+/// 	.device("eth0")
+/// 	.reuse_port(true)
+/// 	.recvbuf_size(0x400000)
+/// 	.bind("0.0.0.0:5000")
+/// 	.await?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct UdpBuilder {
+	device: Option<String>,
+	mark: Option<u32>,
+	reuse_port: bool,
+	recvbuf_size: Option<i32>,
+	sendbuf_size: Option<i32>,
+	recv_errors: bool
+}
+
+#[asynchronous]
+impl UdpBuilder {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			device: None,
+			mark: None,
+			reuse_port: false,
+			recvbuf_size: None,
+			sendbuf_size: None,
+			recv_errors: false
+		}
+	}
+
+	/// Only send and receive packets through the network interface `device`.
+	/// Requires `CAP_NET_RAW` on older kernels
+	#[must_use]
+	pub fn device(mut self, device: impl Into<String>) -> Self {
+		self.device = Some(device.into());
+		self
+	}
+
+	/// Tag outgoing packets with the firewall mark `mark`, which routing
+	/// rules can use to select a routing table. Requires `CAP_NET_ADMIN`
+	#[must_use]
+	pub const fn mark(mut self, mark: u32) -> Self {
+		self.mark = Some(mark);
+		self
+	}
+
+	/// Allow multiple sockets to bind to the same address. The kernel
+	/// distributes incoming packets between them by hashing the source
+	/// address, so each socket can be served by a different thread
+	#[must_use]
+	pub const fn reuse_port(mut self, enable: bool) -> Self {
+		self.reuse_port = enable;
+		self
+	}
+
+	#[must_use]
+	pub const fn recvbuf_size(mut self, size: i32) -> Self {
+		self.recvbuf_size = Some(size);
+		self
+	}
+
+	#[must_use]
+	pub const fn sendbuf_size(mut self, size: i32) -> Self {
+		self.sendbuf_size = Some(size);
+		self
+	}
+
+	/// Queue extended errors such as ICMP unreachable messages on the socket's
+	/// error queue, to be read with `MSG_ERRQUEUE`
+	#[must_use]
+	pub const fn recv_errors(mut self, enable: bool) -> Self {
+		self.recv_errors = enable;
+		self
+	}
+
+	fn configure(&self, sock: &Socket, addr: &Address) -> Result<()> {
+		set_reuse_addr(sock.fd(), true)?;
+
+		if self.reuse_port {
+			sock.set_reuse_port(true)?;
+		}
+
+		sock.set_routing(self.device.as_deref(), self.mark)?;
+
+		if let Some(size) = self.recvbuf_size {
+			set_recvbuf_size(sock.fd(), size)?;
+		}
+
+		if let Some(size) = self.sendbuf_size {
+			set_sendbuf_size(sock.fd(), size)?;
+		}
+
+		if self.recv_errors {
+			sock.set_recv_errors(addr, true)?;
+		}
+
+		Ok(())
+	}
+
+	/// Create the socket and bind it to the first address in `addrs` that
+	/// succeeds
+	pub async fn bind<A>(self, addrs: A) -> Result<DatagramSocket>
+	where
+		A: ToSocketAddrs
+	{
+		let sock = bind_addr_with(
+			addrs,
+			SocketType::Datagram as u32,
+			IpProtocol::Udp,
+			|sock, addr| self.configure(sock, addr)
+		)
+		.await?;
+
+		Ok(DatagramSocket { socket: sock })
+	}
+
+	/// Create the socket and connect it to the first address in `addrs` that
+	/// succeeds. The socket is bound to an ephemeral port
+	pub async fn connect<A>(self, addrs: A) -> Result<DatagramSocket>
+	where
+		A: ToSocketAddrs
+	{
+		let sock = connect_addrs_with(
+			addrs,
+			SocketType::Datagram as u32,
+			IpProtocol::Udp,
+			|sock, addr| self.configure(sock, addr)
+		)
+		.await?;
+
+		Ok(DatagramSocket { socket: sock })
+	}
+}
+
+impl Default for UdpBuilder {
+	fn default() -> Self {
+		Self::new()
+	}
 }
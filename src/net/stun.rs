@@ -0,0 +1,266 @@
+//! A minimal STUN client for discovering the external address of a
+//! [`DatagramSocket`], as described in RFC 5389
+//!
+//! # Examples
+//!
+//! ```
+//! let mut socket = Udp::bind("0.0.0.0:0").await?;
+//! let server = "stun.l.google.com:19302".to_socket_addrs()?.next().unwrap();
+//! let external = stun::binding(&mut socket, server).await?;
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use super::*;
+use crate::impls::TaskExt;
+
+const MAGIC_COOKIE: u32 = 0x2112_a442;
+const HEADER_LEN: usize = 20;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const BINDING_ERROR: u16 = 0x0111;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+const FAMILY_V4: u8 = 0x01;
+const FAMILY_V6: u8 = 0x02;
+
+/// The largest response accepted. Binding responses are far smaller
+const MAX_RESPONSE_LEN: usize = 0x200;
+
+/// A STUN transaction ID
+pub type TransactionId = [u8; 12];
+
+/// Retransmission parameters for a STUN request. The defaults are the ones
+/// recommended by RFC 5389
+#[derive(Clone, Copy, Debug)]
+pub struct Retransmit {
+	/// The initial retransmission timeout, doubled after each transmission
+	pub rto: Duration,
+
+	/// The total number of times the request is sent
+	pub count: u32,
+
+	/// After the last transmission, wait this many multiples of the initial
+	/// timeout for a response
+	pub last_wait: u32
+}
+
+impl Default for Retransmit {
+	fn default() -> Self {
+		Self { rto: Duration::from_millis(500), count: 7, last_wait: 16 }
+	}
+}
+
+fn new_transaction_id() -> TransactionId {
+	let state = RandomState::new();
+	let mut id = [0u8; 12];
+
+	for (index, chunk) in id.chunks_mut(8).enumerate() {
+		let mut hasher = state.build_hasher();
+
+		hasher.write_u64(nanotime());
+		hasher.write_usize(index);
+
+		let bytes = hasher.finish().to_ne_bytes();
+
+		chunk.copy_from_slice(&bytes[0..chunk.len()]);
+	}
+
+	id
+}
+
+/// Encode a binding request with no attributes
+#[must_use]
+pub fn binding_request(id: &TransactionId) -> [u8; HEADER_LEN] {
+	let mut request = [0u8; HEADER_LEN];
+
+	request[0..2].copy_from_slice(&BINDING_REQUEST.to_be_bytes());
+	request[2..4].copy_from_slice(&0u16.to_be_bytes());
+	request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+	request[8..20].copy_from_slice(id);
+
+	request
+}
+
+fn malformed() -> Error {
+	fmt_error!("Malformed STUN message")
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Result<u16> {
+	#[allow(clippy::arithmetic_side_effects)]
+	let bytes = buf.get(offset..offset + 2).ok_or_else(malformed)?;
+
+	Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn parse_address(value: &[u8], xor: Option<&TransactionId>) -> Result<SocketAddr> {
+	if value.len() < 4 {
+		return Err(malformed());
+	}
+
+	let family = value[1];
+	let mut port = read_u16(value, 2)?;
+
+	#[allow(clippy::cast_possible_truncation)]
+	if xor.is_some() {
+		port ^= (MAGIC_COOKIE >> 16) as u16;
+	}
+
+	let mut mask = [0u8; 16];
+
+	if let Some(id) = xor {
+		mask[0..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+		mask[4..16].copy_from_slice(id);
+	}
+
+	let ip = match (family, &value[4..]) {
+		(FAMILY_V4, addr) if addr.len() == 4 => {
+			let mut octets = [0u8; 4];
+
+			for (index, octet) in octets.iter_mut().enumerate() {
+				*octet = addr[index] ^ mask[index];
+			}
+
+			IpAddr::V4(Ipv4Addr::from(octets))
+		}
+
+		(FAMILY_V6, addr) if addr.len() == 16 => {
+			let mut octets = [0u8; 16];
+
+			for (index, octet) in octets.iter_mut().enumerate() {
+				*octet = addr[index] ^ mask[index];
+			}
+
+			IpAddr::V6(Ipv6Addr::from(octets))
+		}
+
+		_ => return Err(malformed())
+	};
+
+	Ok(SocketAddr::new(ip, port))
+}
+
+/// Parse a binding response for the transaction `id`, returning the mapped
+/// address. Returns `Ok(None)` if the message is not a response to this
+/// transaction.
+///
+/// # Errors
+/// If the response is malformed, is an error response, or does not contain a
+/// mapped address
+pub fn parse_binding_response(
+	message: &[u8], id: &TransactionId
+) -> Result<Option<SocketAddr>> {
+	if message.len() < HEADER_LEN ||
+		message[4..8] != MAGIC_COOKIE.to_be_bytes() ||
+		&message[8..20] != id
+	{
+		return Ok(None);
+	}
+
+	let kind = read_u16(message, 0)?;
+	let len = usize::from(read_u16(message, 2)?);
+
+	#[allow(clippy::arithmetic_side_effects)]
+	let attrs = message
+		.get(HEADER_LEN..HEADER_LEN + len)
+		.ok_or_else(malformed)?;
+
+	match kind {
+		BINDING_SUCCESS => (),
+		BINDING_ERROR => return Err(fmt_error!("STUN server returned an error")),
+		_ => return Ok(None)
+	}
+
+	let mut offset = 0;
+	let mut mapped = None;
+
+	#[allow(clippy::arithmetic_side_effects)]
+	while offset + 4 <= attrs.len() {
+		let attr = read_u16(attrs, offset)?;
+		let attr_len = usize::from(read_u16(attrs, offset + 2)?);
+		let value = attrs
+			.get(offset + 4..offset + 4 + attr_len)
+			.ok_or_else(malformed)?;
+
+		match attr {
+			ATTR_XOR_MAPPED_ADDRESS => return parse_address(value, Some(id)).map(Some),
+			ATTR_MAPPED_ADDRESS => mapped = Some(parse_address(value, None)?),
+			_ => ()
+		}
+
+		/* attributes are padded to a multiple of 4 bytes */
+		offset += 4 + attr_len.next_multiple_of(4);
+	}
+
+	match mapped {
+		Some(addr) => Ok(Some(addr)),
+		None => Err(fmt_error!("STUN response has no mapped address"))
+	}
+}
+
+#[asynchronous]
+async fn recv_response(
+	socket: &mut DatagramSocket, server: &SocketAddr, id: &TransactionId
+) -> Result<SocketAddr> {
+	let mut buf = [0u8; MAX_RESPONSE_LEN];
+
+	loop {
+		let (len, from) = socket.recvfrom(&mut buf, BitFlags::default()).await?;
+
+		if &from != server {
+			continue;
+		}
+
+		if let Some(addr) = parse_binding_response(&buf[0..len], id)? {
+			break Ok(addr);
+		}
+	}
+}
+
+/// Send a binding request to the STUN `server` using the default
+/// [`Retransmit`] parameters, returning the address of `socket` as seen by
+/// the server
+#[asynchronous]
+pub async fn binding(socket: &mut DatagramSocket, server: SocketAddr) -> Result<SocketAddr> {
+	binding_with(socket, server, Retransmit::default()).await
+}
+
+/// Send a binding request to the STUN `server`, retransmitting it according
+/// to `retransmit` until a response arrives
+///
+/// # Errors
+/// If the request or response failed, or with [`ErrorKind::TimedOut`] if the
+/// server never responded
+#[asynchronous]
+pub async fn binding_with(
+	socket: &mut DatagramSocket, server: SocketAddr, retransmit: Retransmit
+) -> Result<SocketAddr> {
+	let id = new_transaction_id();
+	let request = binding_request(&id);
+	let mut rto = retransmit.rto;
+
+	for attempt in 1..=retransmit.count {
+		socket
+			.sendto(&request, BitFlags::default(), &server)
+			.await?;
+
+		let wait = if attempt == retransmit.count {
+			retransmit.rto.saturating_mul(retransmit.last_wait)
+		} else {
+			rto
+		};
+
+		if let Some(result) = recv_response(socket, &server, &id).timeout(wait).await {
+			return result;
+		}
+
+		rto = rto.saturating_mul(2);
+	}
+
+	Err(fmt_error!("STUN request timed out" @ ErrorKind::TimedOut))
+}
@@ -0,0 +1,293 @@
+//! The implementation for [`WriteQueue`]
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::iter::once;
+use std::rc::Rc;
+
+use super::*;
+use crate::ops::sync::Mutex;
+
+/// The maximum number of buffers sent in a single `sendmsg`
+const MAX_IOVECS: usize = 0x400;
+
+struct Shared {
+	socket: StreamSocket,
+	queue: RefCell<VecDeque<Vec<u8>>>,
+	queued: Cell<usize>,
+
+	/// Held while sending, so that queued data is never sent by two tasks at
+	/// once
+	sending: Mutex<()>,
+	flushing: Cell<bool>,
+	task: RefCell<Option<JoinHandle<()>>>,
+	error: RefCell<Option<Error>>,
+
+	/// The runtime running the background task, set on the first use
+	tag: Cell<Option<RuntimeTag>>
+}
+
+impl Shared {
+	fn take_batch(&self) -> Vec<Vec<u8>> {
+		let mut queue = self.queue.borrow_mut();
+		let len = queue.len().min(MAX_IOVECS);
+
+		queue.drain(0..len).collect()
+	}
+
+	/// Return the unsent part of `batch`, starting at `offset` into the buffer
+	/// at `index`, to the front of the queue
+	fn requeue(&self, mut batch: Vec<Vec<u8>>, index: usize, offset: usize) {
+		let mut queue = self.queue.borrow_mut();
+
+		batch[index].drain(..offset);
+
+		for buf in batch.drain(index..).rev() {
+			queue.push_front(buf);
+		}
+	}
+
+	fn take_error(&self) -> Result<()> {
+		match self.error.borrow_mut().take() {
+			Some(err) => Err(err),
+			None => Ok(())
+		}
+	}
+
+	/// Check that the queue is used on the runtime of its background task
+	#[asynchronous]
+	async fn check_runtime(&self) -> Result<()> {
+		match self.tag.get() {
+			Some(tag) => tag.check().await?,
+			None => self.tag.set(Some(RuntimeTag::current().await))
+		}
+
+		Ok(())
+	}
+
+	/// Send `batch`, returning the unsent part to the queue if sending fails
+	#[asynchronous]
+	async fn send_batch(&self, half: &mut SocketHalf<'_>, batch: Vec<Vec<u8>>) -> Result<()> {
+		let (mut index, mut offset) = (0, 0);
+
+		while index < batch.len() {
+			let first = &batch[index][offset..];
+
+			#[allow(clippy::arithmetic_side_effects)]
+			let slices: Vec<_> = once(IoSlice::new(first))
+				.chain(batch[index + 1..].iter().map(|buf| IoSlice::new(buf)))
+				.collect();
+
+			let result = match half.send_vectored(&slices, BitFlags::default()).await {
+				Ok(0) => Err(fmt_error!("Failed to send queued data")),
+				result => result
+			};
+
+			let mut sent = match result {
+				Ok(sent) => sent,
+				Err(err) => {
+					self.requeue(batch, index, offset);
+
+					return Err(err);
+				}
+			};
+
+			#[allow(clippy::arithmetic_side_effects)]
+			self.queued.set(self.queued.get() - sent);
+
+			#[allow(clippy::arithmetic_side_effects)]
+			while sent != 0 {
+				let remaining = batch[index].len() - offset;
+
+				if sent < remaining {
+					offset += sent;
+
+					break;
+				}
+
+				sent -= remaining;
+				index += 1;
+				offset = 0;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Send everything in the queue, coalescing up to [`MAX_IOVECS`] buffers
+	/// into each `sendmsg`. Fails with the error of an earlier failed send, if
+	/// it was not reported yet
+	#[asynchronous]
+	async fn drain(&self) -> Result<()> {
+		let _sending = self.sending.lock().await?;
+		let mut half = self.socket.half();
+
+		self.take_error()?;
+
+		loop {
+			let batch = self.take_batch();
+
+			if batch.is_empty() {
+				break Ok(());
+			}
+
+			self.send_batch(&mut half, batch).await?;
+		}
+	}
+
+	/// Drain the queue in the background, until it stays empty or sending
+	/// fails
+	#[asynchronous]
+	async fn flush_background(&self) {
+		/* let other tasks queue more data before sending */
+		yield_now().await;
+
+		loop {
+			let result = self.drain().await;
+
+			self.flushing.set(false);
+
+			if let Err(err) = result {
+				self.error.replace(Some(err));
+
+				break;
+			}
+
+			/* data may have been queued after the last batch was taken */
+			if self.queue.borrow().is_empty() || self.flushing.replace(true) {
+				break;
+			}
+		}
+	}
+}
+
+/// A queue of owned buffers for a [`StreamSocket`]
+///
+/// Buffers pushed to the queue are sent by a background task, which coalesces
+/// every buffer queued during the same turn of the runtime into as few
+/// `sendmsg` calls as possible. This reduces the number of syscalls for
+/// protocols that write many small messages.
+///
+/// Only one task sends at a time, so data is sent in the order it was pushed,
+/// even when [`flush`](Self::flush) is called while the background task is
+/// sending. If sending fails, the unsent data stays queued, and the error is
+/// returned from the next call to [`push`](Self::push), [`flush`](Self::flush)
+/// or [`flushed`](Self::flushed).
+///
+/// The queue belongs to the runtime it is first used on, and fails with a
+/// [`WrongRuntime`] error on any other. See [`RuntimeTag`]. Use
+/// [`WriteQueue::rebind`] to move it to another runtime
+pub struct WriteQueue {
+	shared: Rc<Shared>
+}
+
+#[asynchronous]
+impl WriteQueue {
+	#[must_use]
+	pub fn new(socket: StreamSocket) -> Self {
+		let shared = Shared {
+			socket,
+			queue: RefCell::new(VecDeque::new()),
+			queued: Cell::new(0),
+			sending: Mutex::new(()),
+			flushing: Cell::new(false),
+			task: RefCell::new(None),
+			error: RefCell::new(None),
+			tag: Cell::new(None)
+		};
+
+		Self { shared: Rc::new(shared) }
+	}
+
+	/// Queue `buf` to be sent. A flush is started in the background if one
+	/// isn't already running
+	pub async fn push(&self, buf: Vec<u8>) -> Result<()> {
+		self.shared.check_runtime().await?;
+		self.shared.take_error()?;
+
+		if buf.is_empty() {
+			return Ok(());
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		self.shared.queued.set(self.shared.queued.get() + buf.len());
+		self.shared.queue.borrow_mut().push_back(buf);
+
+		if self.shared.flushing.replace(true) {
+			return Ok(());
+		}
+
+		let shared = self.shared.clone();
+		let task = spawn(async move { shared.flush_background().await }).await;
+
+		self.shared.task.replace(Some(task));
+
+		Ok(())
+	}
+
+	/// Wait for the background flush, if any, to finish
+	pub async fn flushed(&self) -> Result<()> {
+		self.shared.check_runtime().await?;
+
+		let task = self.shared.task.borrow_mut().take();
+
+		if let Some(task) = task {
+			/* any error is stored in `shared.error` */
+			task.await;
+		}
+
+		self.shared.take_error()
+	}
+
+	/// Send all queued data, without waiting for the background task's
+	/// coalescing delay. Waits for the background task if it is sending
+	pub async fn flush(&self) -> Result<()> {
+		self.shared.check_runtime().await?;
+		self.shared.take_error()?;
+		self.shared.drain().await
+	}
+
+	/// Move the queue to the current runtime. Queued data is sent by the
+	/// next background flush, on the new runtime
+	///
+	/// # Errors
+	/// With [`WrongRuntime`] if the background task is still running on the
+	/// previous runtime. Wait for it with [`flushed`](Self::flushed) there
+	/// first
+	pub async fn rebind(&mut self) -> Result<()> {
+		let current = RuntimeTag::current().await;
+
+		let previous = self.shared.tag.get();
+
+		if let Some(tag) = previous.filter(|tag| *tag != current && self.shared.flushing.get()) {
+			let err = WrongRuntime { expected: Some(tag.id()), current: Some(current.id()) };
+
+			return Err(err.into());
+		}
+
+		/* the task has finished, and its error is stored in `shared.error` */
+		self.shared.task.replace(None);
+		self.shared.tag.set(Some(current));
+
+		Ok(())
+	}
+
+	/// The number of bytes waiting to be sent
+	#[must_use]
+	pub fn queued(&self) -> usize {
+		self.shared.queued.get()
+	}
+
+	/// Get the underlying socket. This can be used to receive data while
+	/// writes are queued
+	#[must_use]
+	pub fn socket(&self) -> &StreamSocket {
+		&self.shared.socket
+	}
+
+	/// Get a reading half of the socket
+	#[must_use]
+	pub fn reader(&self) -> SocketHalf<'_> {
+		self.shared.socket.half()
+	}
+}
@@ -0,0 +1,123 @@
+//! Tagging resources with the runtime that created them
+//!
+//! File descriptors are shared by the whole process, so a [`File`] or
+//! [`Socket`] works on any runtime. Other resources hold state that belongs
+//! to the driver of one runtime, such as the timers registered through a
+//! [`TimerOwner`], a [`StdWaker`] or a [`WriteQueue`]. Using those from
+//! another runtime fails with a [`WrongRuntime`] error from
+//! [`RuntimeTag::check`], instead of touching the state of a driver that may
+//! be running on another thread. Those resources have a `rebind` method to
+//! move them to the current runtime.
+//!
+//! [`File`]: crate::fs::File
+//! [`Socket`]: crate::net::Socket
+//! [`WriteQueue`]: crate::net::WriteQueue
+
+use std::fmt;
+
+use super::*;
+
+/// The error for using a resource on a runtime other than the one it belongs
+/// to, or for calling xx-pulse functions outside of an xx-pulse runtime
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WrongRuntime {
+	/// The id of the runtime the resource belongs to, if any
+	pub expected: Option<u64>,
+
+	/// The id of the current runtime, or `None` if the current task is not
+	/// running on an xx-pulse runtime
+	pub current: Option<u64>
+}
+
+impl WrongRuntime {
+	pub(crate) const fn not_pulse() -> Self {
+		Self { expected: None, current: None }
+	}
+}
+
+impl fmt::Display for WrongRuntime {
+	fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (self.expected, self.current) {
+			(_, None) => write!(fmt, "Not running on an xx-pulse runtime"),
+			(Some(expected), Some(current)) => write!(
+				fmt,
+				"Resource belongs to runtime {}, but was used on runtime {}",
+				expected, current
+			),
+			(None, Some(current)) => {
+				write!(fmt, "Resource does not belong to runtime {}", current)
+			}
+		}
+	}
+}
+
+impl std::error::Error for WrongRuntime {}
+
+impl From<WrongRuntime> for Error {
+	/// The [`WrongRuntime`] is kept in the error, and can be retrieved with
+	/// `downcast_ref`
+	fn from(value: WrongRuntime) -> Self {
+		let kind = if value.current.is_none() {
+			std::io::ErrorKind::Unsupported
+		} else {
+			std::io::ErrorKind::InvalidInput
+		};
+
+		std::io::Error::new(kind, value).into()
+	}
+}
+
+/// Get the unique id of the current runtime. See [`Runtime::id`]
+#[asynchronous]
+pub async fn runtime_id() -> u64 {
+	internal_get_driver().await.id()
+}
+
+/// Records the runtime a resource belongs to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RuntimeTag {
+	id: u64
+}
+
+#[asynchronous]
+impl RuntimeTag {
+	/// A tag for the current runtime
+	pub async fn current() -> Self {
+		Self { id: runtime_id().await }
+	}
+
+	/// The id of the runtime this tag is for
+	#[must_use]
+	pub const fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// Returns `true` if the tag is for the current runtime
+	pub async fn is_current(&self) -> bool {
+		self.id == runtime_id().await
+	}
+
+	/// Check that the tag is for the current runtime
+	///
+	/// # Errors
+	/// With [`WrongRuntime`] if the tag is for a different runtime. Converted
+	/// into an [`Error`], it has the kind [`ErrorKind::InvalidInput`]
+	pub async fn check(&self) -> std::result::Result<(), WrongRuntime> {
+		let current = runtime_id().await;
+
+		if self.id != current {
+			return Err(WrongRuntime { expected: Some(self.id), current: Some(current) });
+		}
+
+		Ok(())
+	}
+
+	/// Move the tag to the current runtime
+	///
+	/// This only changes the tag itself. To move a resource that holds state
+	/// in the driver of its runtime, use its own `rebind` method, such as
+	/// [`TimerOwner::rebind`].
+	pub async fn rebind(&mut self) {
+		self.id = runtime_id().await;
+	}
+}
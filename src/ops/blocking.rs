@@ -23,7 +23,7 @@ where
 	F: FnOnce(&TaskContext) -> Output + Send,
 	Output: Send
 {
-	let driver = internal_get_driver().await;
+	let driver = internal_try_get_driver().await?;
 
 	check_interrupt().await?;
 
@@ -1,17 +1,21 @@
 //! Utilities for branching and spawning async tasks.
 
+use std::panic::Location;
+
 use super::*;
 
 #[asynchronous]
 #[allow(clippy::multiple_unsafe_ops_per_block)]
-pub(crate) async fn spawn_entry<T, Output>(task: T) -> Output
+pub(crate) async fn spawn_entry<T, Output>(
+	task: T, location: Option<&'static Location<'static>>
+) -> Output
 where
 	T: for<'ctx> Task<Output<'ctx> = Output>
 {
 	let workers = internal_get_pulse_env().await.workers;
 
 	/* Safety: the worker is appended to the list */
-	let worker = unsafe { PulseWorker::new().await };
+	let worker = unsafe { PulseWorker::new(location).await };
 
 	/* Safety: worker is pinned */
 	unsafe { ptr!(workers=>append(ptr!(&worker.node))) };
@@ -77,13 +81,45 @@ where
 /// ```
 #[asynchronous]
 pub async fn spawn<T, Output>(task: T) -> JoinHandle<Output>
+where
+	T: for<'ctx> Task<Output<'ctx> = Output> + 'static
+{
+	spawn_at(task, None).await
+}
+
+/// Like [`spawn`], but records where the task was spawned. The location is
+/// reported if the task is still running when the runtime is dropped. See
+/// [`RuntimeBuilder::drop_iterations`]
+///
+/// # Examples
+///
+/// ```
+/// let handle = spawn_tracked(serve(listener)).await;
+/// ```
+#[track_caller]
+pub fn spawn_tracked<T, Output>(
+	task: T
+) -> impl for<'ctx> Task<Output<'ctx> = JoinHandle<Output>>
+where
+	T: for<'ctx> Task<Output<'ctx> = Output> + 'static
+{
+	/* the body of an async function does not know its caller, so the location
+	 * is taken here
+	 */
+	spawn_at(task, Some(Location::caller()))
+}
+
+#[asynchronous]
+async fn spawn_at<T, Output>(
+	task: T, location: Option<&'static Location<'static>>
+) -> JoinHandle<Output>
 where
 	T: for<'ctx> Task<Output<'ctx> = Output> + 'static
 {
 	let runtime = internal_get_pulse_env().await;
 
 	/* Safety: task is static */
-	unsafe { coroutines::spawn(runtime, spawn_entry(task)) }
+	unsafe { coroutines::spawn(runtime, spawn_entry(task, location)) }
 }
 
 #[doc(hidden)]
@@ -116,18 +152,112 @@ pub mod internal {
 /// }
 /// .await;
 /// ```
+///
+/// Async iterators can be selected from with `pattern in iterator`, which
+/// runs the handler with the next item. When any of the iterators ends, the
+/// `on_closed` handler runs instead. The `on_closed` handler is required if
+/// there are any iterator branches
+///
+/// ```
+/// loop {
+/// 	let done = select_many! {
+/// 		connection in incoming => {
+/// 			handle(connection?).await;
+///
+/// 			false
+/// 		}
+///
+/// 		message in messages => {
+/// 			process(message).await;
+///
+/// 			false
+/// 		}
+///
+/// 		on_closed => true
+/// 	}
+/// 	.await;
+///
+/// 	if done {
+/// 		break;
+/// 	}
+/// }
+/// ```
 #[macro_export]
 macro_rules! select_many {
-	{$($tokens:tt)*} => {
+	(@collect [$($arms:tt)*] [$($closed:tt)*]) => {
+		$crate::select_many!(@emit [$($closed)*] [] $($arms)*)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] on_closed => $body:block, $($rest:tt)*) => {
+		$crate::select_many!(@collect [$($arms)*] [$body] $($rest)*)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] on_closed => $body:block $($rest:tt)*) => {
+		$crate::select_many!(@collect [$($arms)*] [$body] $($rest)*)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] on_closed => $body:expr $(, $($rest:tt)*)?) => {
+		$crate::select_many!(@collect [$($arms)*] [{ $body }] $($($rest)*)?)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] $pat:pat in $iter:expr => $body:block, $($rest:tt)*) => {
+		$crate::select_many!(@collect [$($arms)* [iter ($pat) ($iter) $body]] [$($closed)*] $($rest)*)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] $pat:pat in $iter:expr => $body:block $($rest:tt)*) => {
+		$crate::select_many!(@collect [$($arms)* [iter ($pat) ($iter) $body]] [$($closed)*] $($rest)*)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] $pat:pat in $iter:expr => $body:expr $(, $($rest:tt)*)?) => {
+		$crate::select_many!(@collect [$($arms)* [iter ($pat) ($iter) { $body }]] [$($closed)*] $($($rest)*)?)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] $pat:pat = $task:expr => $body:block, $($rest:tt)*) => {
+		$crate::select_many!(@collect [$($arms)* [task ($pat) ($task) $body]] [$($closed)*] $($rest)*)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] $pat:pat = $task:expr => $body:block $($rest:tt)*) => {
+		$crate::select_many!(@collect [$($arms)* [task ($pat) ($task) $body]] [$($closed)*] $($rest)*)
+	};
+
+	(@collect [$($arms:tt)*] [$($closed:tt)*] $pat:pat = $task:expr => $body:expr $(, $($rest:tt)*)?) => {
+		$crate::select_many!(@collect [$($arms)* [task ($pat) ($task) { $body }]] [$($closed)*] $($($rest)*)?)
+	};
+
+	(@emit [$($closed:tt)*] [$($out:tt)*]) => {
 		#[allow(clippy::multiple_unsafe_ops_per_block)]
 		/* Safety: runtimes and executor live until there are no more workers */
 		unsafe {
 			::xx_core::coroutines::select! {
 				$crate::ops::branch::internal::runtime().await;
-				$($tokens)*
+				$($out)*
 			}
 		}
-	}
+	};
+
+	(@emit [$($closed:tt)*] [$($out:tt)*] [task ($pat:pat) ($task:expr) $body:block] $($arms:tt)*) => {
+		$crate::select_many!(@emit [$($closed)*] [$($out)* $pat = $task => $body] $($arms)*)
+	};
+
+	(@emit [] [$($out:tt)*] [iter ($pat:pat) ($iter:expr) $body:block] $($arms:tt)*) => {
+		::std::compile_error!("`select_many!` requires an `on_closed` branch when selecting from async iterators")
+	};
+
+	(@emit [$closed:block] [$($out:tt)*] [iter ($pat:pat) ($iter:expr) $body:block] $($arms:tt)*) => {
+		$crate::select_many!(@emit [$closed] [
+			$($out)*
+			next = $crate::ops::iter::AsyncIterator::next(&mut $iter) => {
+				match next {
+					::std::option::Option::Some($pat) => $body,
+					::std::option::Option::None => $closed
+				}
+			}
+		] $($arms)*)
+	};
+
+	{$($tokens:tt)*} => {
+		$crate::select_many!(@collect [] [] $($tokens)*)
+	};
 }
 
 pub use select_many;
@@ -0,0 +1,285 @@
+//! A runtime-wide budget of file descriptors
+//!
+//! With a budget set by [`RuntimeBuilder::fd_budget`], [`io::open`],
+//! [`io::socket`] and [`io::accept`] take a permit from the budget before
+//! creating a descriptor, and wait while all `limit` permits are in use
+//! instead of failing with `EMFILE`. Running out of file descriptors then
+//! slows down the tasks opening new ones, rather than failing every request at
+//! once.
+//!
+//! A permit is returned to the budget when its descriptor is closed with
+//! [`io::close`], or when the [`File`] or socket owning it is dropped or
+//! closed. Descriptors that the runtime opens for itself, such as the ring,
+//! the wake up eventfd and the accept reserve, do not take a permit.
+//!
+//! A budgeted descriptor dropped as a bare [`OwnedFd`] cannot be observed, so
+//! its permit is only recovered once the descriptor is found to be closed, or
+//! its number is handed out again. While tasks are waiting for a permit, they
+//! look for such descriptors every 10 milliseconds.
+//!
+//! [`File`]: crate::fs::File
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::ptr;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+use xx_core::coroutines::ops::AsyncFn;
+use xx_core::os::syscall::*;
+
+use super::sync::WaitQueue;
+use super::*;
+
+const F_GETFD: i32 = 1;
+
+/// How often waiting tasks look for budgeted descriptors that were closed by
+/// dropping them, which does not wake waiting tasks
+const RECHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+thread_local! {
+	/// The budget each budgeted descriptor on this thread took its permit from
+	static PERMITS: RefCell<HashMap<RawFd, Weak<FdBudget>>> = RefCell::new(HashMap::new());
+}
+
+/// The state of the file descriptor budget of a runtime. See
+/// [`RuntimeBuilder::fd_budget`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FdBudgetStats {
+	/// The budget, or `None` if disabled
+	pub limit: Option<usize>,
+
+	/// The number of permits held by open descriptors and pending operations
+	pub in_use: usize,
+
+	/// The number of tasks waiting for a file descriptor
+	pub waiting: usize,
+
+	/// The total number of times a task had to wait for a file descriptor
+	pub waits: u64
+}
+
+pub(crate) struct FdBudget {
+	limit: Cell<Option<usize>>,
+	in_use: Cell<usize>,
+	waiting: Cell<usize>,
+	waits: Cell<u64>,
+	waiters: WaitQueue,
+	reserve: RefCell<Option<OwnedFd>>
+}
+
+impl FdBudget {
+	pub(crate) const fn new() -> Self {
+		Self {
+			limit: Cell::new(None),
+			in_use: Cell::new(0),
+			waiting: Cell::new(0),
+			waits: Cell::new(0),
+			waiters: WaitQueue::new(),
+			reserve: RefCell::new(None)
+		}
+	}
+
+	pub(crate) fn set_limit(&self, limit: Option<usize>) {
+		self.limit.set(limit);
+		self.waiters.wake_all();
+	}
+
+	pub(crate) fn stats(&self) -> FdBudgetStats {
+		FdBudgetStats {
+			limit: self.limit.get(),
+			in_use: self.in_use.get(),
+			waiting: self.waiting.get(),
+			waits: self.waits.get()
+		}
+	}
+
+	/// Take a permit if one is available. Returns an untracked permit if the
+	/// budget is disabled
+	pub(crate) fn try_acquire(self: &Rc<Self>) -> Option<Permit> {
+		let Some(limit) = self.limit.get() else {
+			return Some(Permit(None));
+		};
+
+		if self.in_use.get() >= limit {
+			return None;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		self.in_use.set(self.in_use.get() + 1);
+
+		Some(Permit(Some(self.clone())))
+	}
+
+	fn release(&self) {
+		self.in_use.set(self.in_use.get().saturating_sub(1));
+		self.waiters.wake_one();
+	}
+
+	/// Return the permits of budgeted descriptors that were closed without
+	/// the budget observing it. Returns `true` if any were returned
+	fn reclaim(&self) -> bool {
+		let stale: Vec<RawFd> = PERMITS.with_borrow_mut(|permits| {
+			/* the budgets of runtimes that have exited cannot take permits back */
+			permits.retain(|_, budget| budget.strong_count() != 0);
+
+			permits
+				.iter()
+				.filter(|(fd, budget)| ptr::eq(budget.as_ptr(), self) && !is_open(**fd))
+				.map(|(fd, _)| *fd)
+				.collect()
+		});
+
+		for fd in &stale {
+			release(*fd);
+		}
+
+		!stale.is_empty()
+	}
+}
+
+fn is_open(fd: RawFd) -> bool {
+	/* Safety: F_GETFD does not access memory */
+	unsafe { syscall_int!(Fcntl, fd, F_GETFD) }.is_ok()
+}
+
+/// A permit taken from a budget, returned if dropped before being bound to a
+/// descriptor
+pub(crate) struct Permit(Option<Rc<FdBudget>>);
+
+impl Permit {
+	/// Hand the permit to `fd`. It is returned when the descriptor is released
+	pub(crate) fn bind(mut self, fd: BorrowedFd<'_>) {
+		let Some(budget) = self.0.take() else {
+			return;
+		};
+
+		let stale = PERMITS.with_borrow_mut(|permits| {
+			permits.insert(fd.as_raw_fd(), Rc::downgrade(&budget))
+		});
+
+		/* the number was reused, so the old descriptor was closed by dropping it */
+		if let Some(budget) = stale.and_then(|budget| budget.upgrade()) {
+			budget.release();
+		}
+	}
+}
+
+impl Drop for Permit {
+	fn drop(&mut self) {
+		if let Some(budget) = self.0.take() {
+			budget.release();
+		}
+	}
+}
+
+/// Return the permit held by `fd`, if any. Must be called before `fd` is
+/// closed, so that its number cannot have been reused
+pub(crate) fn release(fd: RawFd) {
+	let budget = PERMITS.with_borrow_mut(|permits| permits.remove(&fd));
+
+	if let Some(budget) = budget.and_then(|budget| budget.upgrade()) {
+		budget.release();
+	}
+}
+
+/// Returns the permit of the descriptor owned by a [`File`] or socket, and
+/// removes its trace label, when it is dropped
+///
+/// [`File`]: crate::fs::File
+pub(crate) struct FdRelease(RawFd);
+
+impl FdRelease {
+	pub(crate) fn new(fd: BorrowedFd<'_>) -> Self {
+		Self(fd.as_raw_fd())
+	}
+}
+
+impl Drop for FdRelease {
+	fn drop(&mut self) {
+		release(self.0);
+		io::clear_trace_name(self.0);
+	}
+}
+
+/// Get the state of the file descriptor budget of the current runtime. See
+/// [`FdBudgetStats`]
+#[asynchronous]
+pub async fn fd_budget_stats() -> FdBudgetStats {
+	internal_get_driver().await.fd_budget().stats()
+}
+
+/// Open the spare descriptor of the current runtime if it is missing. See
+/// [`AcceptBackoff::reserve_fd`]
+///
+/// [`AcceptBackoff::reserve_fd`]: crate::net::AcceptBackoff::reserve_fd
+#[asynchronous]
+pub(crate) async fn open_reserve() {
+	let budget = internal_get_driver().await.fd_budget();
+
+	if budget.reserve.borrow().is_some() {
+		return;
+	}
+
+	/* the reserve must not wait for the budget it helps recover from */
+	if let Ok(fd) = io::open_unbudgeted("/dev/null".as_ref(), BitFlags::default(), 0).await {
+		budget.reserve.replace(Some(fd));
+	}
+}
+
+/// Take the spare descriptor of the current runtime, to free up a descriptor
+#[asynchronous]
+pub(crate) async fn take_reserve() -> Option<OwnedFd> {
+	internal_get_driver().await.fd_budget().reserve.take()
+}
+
+/// Take a permit, then create a descriptor with `create`. The permit is
+/// returned if `create` fails or is cancelled
+#[asynchronous]
+pub(crate) async fn budgeted<F>(create: F) -> Result<OwnedFd>
+where
+	F: AsyncFn() -> Result<OwnedFd>
+{
+	let permit = acquire_permit().await?;
+	let fd = create.call(()).await?;
+
+	permit.bind(fd.as_fd());
+
+	Ok(fd)
+}
+
+/// Take a permit for a descriptor that will be created by an operation that
+/// cannot be retried, such as accepting a connection
+#[asynchronous]
+pub(crate) async fn acquire_permit() -> Result<Permit> {
+	let budget = internal_try_get_driver().await?.fd_budget();
+
+	loop {
+		if let Some(permit) = budget.try_acquire() {
+			return Ok(permit);
+		}
+
+		if budget.reclaim() {
+			continue;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		budget.waiting.set(budget.waiting.get() + 1);
+		budget.waits.set(budget.waits.get().saturating_add(1));
+
+		/* permits of dropped descriptors do not wake us, so look again periodically */
+		let _ = select(block_on(budget.waiters.wait()), sleep(RECHECK_INTERVAL)).await;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		budget.waiting.set(budget.waiting.get() - 1);
+
+		check_interrupt().await?;
+	}
+}
+
+/// Take a permit without waiting. See [`FdBudget::try_acquire`]
+#[asynchronous]
+pub(crate) async fn try_acquire_permit() -> Option<Permit> {
+	internal_get_driver().await.fd_budget().try_acquire()
+}
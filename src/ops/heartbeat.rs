@@ -0,0 +1,233 @@
+//! Heartbeat scheduling for many connections with a single timer
+//!
+//! Protocols such as MQTT and AMQP require pinging idle peers and
+//! disconnecting peers that stay silent for too long. Instead of one timer per
+//! connection, a [`Heartbeat`] tracks the last activity of every registered
+//! connection and checks them all on a single coalesced tick.
+//!
+//! # Examples
+//!
+//! ```
+//! let heartbeat = Heartbeat::new(duration!(30 s), duration!(90 s))?;
+//! let conn = heartbeat.register();
+//!
+//! /* on every recv or send */
+//! conn.touch();
+//!
+//! /* in a separate task */
+//! loop {
+//! 	for due in heartbeat.tick().await? {
+//! 		match due.action {
+//! 			Action::Ping => send_ping(due.id).await?,
+//! 			Action::Expire => disconnect(due.id).await?
+//! 		}
+//! 	}
+//! }
+//! ```
+
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use super::*;
+
+/// What to do with a connection. See [`Heartbeat::tick`]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Action {
+	/// The connection has been idle for the idle duration and should be pinged
+	Ping,
+
+	/// The connection has been idle for the timeout duration and should be
+	/// disconnected. The connection will not be reported again
+	Expire
+}
+
+/// A connection that needs attention
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Due {
+	/// The id of the connection. See [`Activity::id`]
+	pub id: u64,
+	pub action: Action
+}
+
+struct Entry {
+	last: Cell<u64>,
+	pinged: Cell<bool>,
+	expired: Cell<bool>
+}
+
+struct Shared {
+	now: Cell<u64>,
+	next_id: Cell<u64>,
+	entries: RefCell<BTreeMap<u64, Rc<Entry>>>
+}
+
+/// The activity tracker for a single connection. The connection is
+/// unregistered when this is dropped
+pub struct Activity {
+	id: u64,
+	entry: Rc<Entry>,
+	shared: Rc<Shared>
+}
+
+impl Activity {
+	#[must_use]
+	pub const fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// Record activity on the connection. This is cheap enough to call on
+	/// every send and receive, as it only stores the time of the last tick
+	pub fn touch(&self) {
+		self.entry.last.set(self.shared.now.get());
+		self.entry.pinged.set(false);
+	}
+}
+
+impl Drop for Activity {
+	fn drop(&mut self) {
+		self.shared.entries.borrow_mut().remove(&self.id);
+	}
+}
+
+fn zero_resolution() -> Error {
+	fmt_error!("Heartbeat resolution must be non-zero" @ ErrorKind::InvalidInput)
+}
+
+fn saturating_nanos(duration: Duration) -> u64 {
+	duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+/// A heartbeat scheduler. See the [module level documentation](self) for more
+/// information
+pub struct Heartbeat {
+	shared: Rc<Shared>,
+	idle: u64,
+	timeout: u64,
+	interval: RefCell<Interval>,
+	owner: OnceCell<TimerOwner>
+}
+
+#[asynchronous]
+impl Heartbeat {
+	/// Create a new heartbeat scheduler, which pings connections idle for
+	/// `idle` and expires connections idle for `timeout`. Connections are
+	/// checked every half of `idle`
+	///
+	/// Durations longer than `u64::MAX` nanoseconds are treated as infinite
+	///
+	/// # Errors
+	/// If half of `idle` is zero. See [`Heartbeat::with_resolution`]
+	pub fn new(idle: Duration, timeout: Duration) -> Result<Self> {
+		Self::with_resolution(idle, timeout, idle / 2)
+	}
+
+	/// Same as [`Heartbeat::new`], but checks connections every `resolution`
+	///
+	/// # Errors
+	/// With [`ErrorKind::InvalidInput`] if `resolution` is zero, which would
+	/// tick without ever waiting
+	pub fn with_resolution(idle: Duration, timeout: Duration, resolution: Duration) -> Result<Self> {
+		if resolution.is_zero() {
+			return Err(zero_resolution());
+		}
+
+		let shared = Shared {
+			now: Cell::new(nanotime()),
+			next_id: Cell::new(0),
+			entries: RefCell::new(BTreeMap::new())
+		};
+
+		let mut interval = Interval::from_nanos(saturating_nanos(resolution));
+
+		interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+		Ok(Self {
+			shared: Rc::new(shared),
+			idle: saturating_nanos(idle),
+			timeout: saturating_nanos(timeout),
+			interval: RefCell::new(interval),
+			owner: OnceCell::new()
+		})
+	}
+
+	/// Register a new connection. The connection starts out active
+	#[must_use]
+	pub fn register(&self) -> Activity {
+		let id = self.shared.next_id.get();
+		let entry = Rc::new(Entry {
+			last: Cell::new(self.shared.now.get()),
+			pinged: Cell::new(false),
+			expired: Cell::new(false)
+		});
+
+		#[allow(clippy::arithmetic_side_effects)]
+		self.shared.next_id.set(id + 1);
+		self.shared.entries.borrow_mut().insert(id, entry.clone());
+
+		Activity { id, entry, shared: self.shared.clone() }
+	}
+
+	/// The number of registered connections
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.shared.entries.borrow().len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Interrupt a pending [`Heartbeat::tick`], which fails with
+	/// [`ErrorKind::Interrupted`]. Returns `false` if no tick was pending
+	pub fn cancel_tick(&self) -> bool {
+		self.owner.get().is_some_and(|owner| owner.cancel_all() > 0)
+	}
+
+	/// Wait for the next tick, then return the connections that need to be
+	/// pinged or disconnected. Each idle period results in at most one ping
+	///
+	/// # Panics
+	/// If called from more than one task at the same time
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	pub async fn tick(&self) -> Result<Vec<Due>> {
+		let owner = match self.owner.get() {
+			Some(owner) => owner,
+			None => {
+				let owner = TimerOwner::new().await;
+
+				self.owner.get_or_init(|| owner)
+			}
+		};
+
+		#[allow(clippy::await_holding_refcell_ref)]
+		self.interval.borrow_mut().next_owned(owner).await?;
+
+		let now = nanotime();
+		let mut due = Vec::new();
+
+		self.shared.now.set(now);
+
+		for (&id, entry) in self.shared.entries.borrow().iter() {
+			if entry.expired.get() {
+				continue;
+			}
+
+			let idle = now.saturating_sub(entry.last.get());
+
+			if idle >= self.timeout {
+				entry.expired.set(true);
+				due.push(Due { id, action: Action::Expire });
+			} else if idle >= self.idle && !entry.pinged.get() {
+				entry.pinged.set(true);
+				due.push(Due { id, action: Action::Ping });
+			}
+		}
+
+		Ok(due)
+	}
+}
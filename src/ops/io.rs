@@ -1,8 +1,13 @@
 //! Direct I/O operations and syscalls.
 
+#[cfg(feature = "tracing")]
+use std::cell::RefCell;
+#[cfg(feature = "tracing")]
+use std::collections::HashMap;
 use std::ffi::CStr;
-use std::mem::size_of;
-use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::fmt;
+use std::mem::{size_of, MaybeUninit};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
 use std::path::Path;
 
 use xx_core::coroutines::ops::AsyncFnOnce;
@@ -15,9 +20,19 @@ use xx_core::os::inet::*;
 use xx_core::os::openat::*;
 use xx_core::os::socket::*;
 use xx_core::os::stat::*;
+use xx_core::os::syscall::*;
 use xx_core::pointer::*;
 
 use super::*;
+use crate::impls::TaskExt;
+use crate::ops::fd_budget::{self, acquire_permit, budgeted};
+
+#[cfg(any(feature = "compress-gzip", feature = "compress-zstd"))]
+pub mod compress;
+pub mod idle;
+pub mod owned;
+
+pub use xx_core::async_std::io::{Read, ReadExt, Seek, SeekExt, Write, WriteExt};
 
 pub mod raw {
 	//! Raw async I/O functions. Use with care. See [the documentation for the
@@ -112,7 +127,7 @@ pub mod raw {
 			#[asynchronous]
 			#[inline]
 			pub async unsafe fn $func($($arg: $type),*) -> $return_type {
-				let driver = internal_get_driver().await;
+				let driver = internal_try_get_driver().await?;
 
 				if !$force {
 					check_interrupt().await?;
@@ -125,7 +140,9 @@ pub mod raw {
 				let $result = paste! { Engine::[<result_for_ $func>](result) };
 
 				#[cfg(feature = "tracing")]
-				xx_core::trace!(target: driver, $($trace)*, $result $($map)*);
+				if trace_enabled(TraceSubsystem::Io) {
+					xx_core::trace!(target: driver, $($trace)*, $result $($map)*);
+				}
 
 				$result.map_err(|err| err.into())
 			}
@@ -143,15 +160,15 @@ pub mod raw {
 	});
 
 	async_engine_task!(true, close(fd: RawFd) -> Result<()> {
-		trace("## close(fd = {}) = {:?}", fd) = result
+		trace("## close(fd = {}) = {:?}", FdDisplay(fd)) = result
 	});
 
 	async_engine_task!(false, read(fd: RawFd, buf: MutPtr<()>, len: usize, offset: i64) -> Result<usize> {
-		trace("## read(fd = {}, buf = &mut [u8; {}], offset = {}) = {:?}", fd, len, offset) = result
+		trace("## read(fd = {}, buf = &mut [u8; {}], offset = {}) = {:?}", FdDisplay(fd), len, offset) = result
 	});
 
 	async_engine_task!(false, write(fd: RawFd, buf: Ptr<()>, len: usize, offset: i64) -> Result<usize> {
-		trace("## write(fd = {}, buf = &[u8; {}], offset = {}) = {:?}", fd, len, offset) = result
+		trace("## write(fd = {}, buf = &[u8; {}], offset = {}) = {:?}", FdDisplay(fd), len, offset) = result
 	});
 
 	async_engine_task!(false, socket(domain: u32, socket_type: u32, protocol: u32) -> Result<OwnedFd> {
@@ -164,17 +181,17 @@ pub mod raw {
 	});
 
 	async_engine_task!(false, accept(socket: RawFd, addr: MutPtr<()>, addrlen: MutPtr<i32>) -> Result<OwnedFd> {
-		trace("## accept(fd = {}, addr = {:?}, addrlen = {:?}) = {:?}", socket, addr, addrlen) = result
+		trace("## accept(fd = {}, addr = {:?}, addrlen = {:?}) = {:?}", FdDisplay(socket), addr, addrlen) = result
 	});
 
 	async_engine_task!(false, connect(socket: RawFd, addr: Ptr<()>, addrlen: i32) -> Result<()> {
-		trace("## connect(fd = {}, addr = {:?}, addrlen = {}) = {:?}", socket, addr, addrlen) = result
+		trace("## connect(fd = {}, addr = {:?}, addrlen = {}) = {:?}", FdDisplay(socket), addr, addrlen) = result
 	});
 
 	async_engine_task!(false, recv(socket: RawFd, buf: MutPtr<()>, len: usize, flags: u32) -> Result<usize> {
 		trace(
 			"## recv(fd = {}, buf = &mut [u8; {}], flags = {}) = {:?}",
-			socket,
+			FdDisplay(socket),
 			len,
 			FlagsDisplay::<MessageFlag>::new(flags)
 		) = result
@@ -183,7 +200,7 @@ pub mod raw {
 	async_engine_task!(false, recvmsg(socket: RawFd, header: MutPtr<MsgHdr>, flags: u32) -> Result<usize> {
 		trace(
 			"## recvmsg(fd = {}, header = {:?}, flags = {}) = {:?}",
-			socket,
+			FdDisplay(socket),
 			header,
 			FlagsDisplay::<MessageFlag>::new(flags)
 		) = result
@@ -192,7 +209,7 @@ pub mod raw {
 	async_engine_task!(false, send(socket: RawFd, buf: Ptr<()>, len: usize, flags: u32) -> Result<usize> {
 		trace(
 			"## send(fd = {}, buf = &[u8; {}], flags = {}) = {:?}",
-			socket,
+			FdDisplay(socket),
 			len,
 			FlagsDisplay::<MessageFlag>::new(flags)
 		) = result
@@ -201,32 +218,32 @@ pub mod raw {
 	async_engine_task!(false, sendmsg(socket: RawFd, header: Ptr<MsgHdr>, flags: u32) -> Result<usize> {
 		trace(
 			"## sendmsg(fd = {}, header = {:?}, flags = {}) = {:?}",
-			socket,
+			FdDisplay(socket),
 			header,
 			FlagsDisplay::<MessageFlag>::new(flags)
 		) = result
 	});
 
 	async_engine_task!(false, shutdown(socket: RawFd, how: u32) -> Result<()> {
-		trace("## shutdown(fd = {}, how = {}) = {:?}", socket, EnumDisplay::<Shutdown>::new(how)) = result
+		trace("## shutdown(fd = {}, how = {}) = {:?}", FdDisplay(socket), EnumDisplay::<Shutdown>::new(how)) = result
 	});
 
 	async_engine_task!(false, bind(socket: RawFd, addr: Ptr<()>, addrlen: i32) -> Result<()> {
-		trace("## bind(fd = {}, addr = {:?}, addrlen = {}) = {:?}", socket, addr, addrlen) = result
+		trace("## bind(fd = {}, addr = {:?}, addrlen = {}) = {:?}", FdDisplay(socket), addr, addrlen) = result
 	});
 
 	async_engine_task!(false, listen(socket: RawFd, backlog: i32) -> Result<()> {
-		trace("## listen(fd = {}, backlog = {}) = {:?}", socket, backlog) = result
+		trace("## listen(fd = {}, backlog = {}) = {:?}", FdDisplay(socket), backlog) = result
 	});
 
 	async_engine_task!(false, fsync(file: RawFd) -> Result<()> {
-		trace("## fsync(fd = {}) = {:?}", file) = result
+		trace("## fsync(fd = {}) = {:?}", FdDisplay(file)) = result
 	});
 
 	async_engine_task!(false, statx(dirfd: RawFd, path: Ptr<()>, flags: u32, mask: u32, statx: MutPtr<Statx>) -> Result<()> {
 		trace(
 			"## statx(dirfd = {}, path = {}, flags = {}, mask = {}, statx = {:?}) = {:?}",
-			dirfd,
+			FdDisplay(dirfd),
 			/* Safety: guaranteed by caller */
 			unsafe { get_cstr_as_str(path) },
 			FlagsDisplay::<AtFlag>::new(flags),
@@ -235,11 +252,31 @@ pub mod raw {
 		) = result
 	});
 
+	async_engine_task!(false, fadvise(fd: RawFd, offset: u64, len: u32, advice: u32) -> Result<()> {
+		trace(
+			"## fadvise(fd = {}, offset = {}, len = {}, advice = {}) = {:?}",
+			FdDisplay(fd),
+			offset,
+			len,
+			advice
+		) = result
+	});
+
 	async_engine_task!(false, poll(fd: RawFd, mask: u32) -> Result<u32> {
-		trace("## poll(fd = {}, mask = {}) = {:?}", fd, FlagsDisplay::<PollFlag>::new(mask)) = result
+		trace("## poll(fd = {}, mask = {}) = {:?}", FdDisplay(fd), FlagsDisplay::<PollFlag>::new(mask)) = result
 			.as_ref()
 			.map(|mask| FlagsDisplay::<PollFlag>::new(*mask))
 	});
+
+	#[cfg(feature = "uring-cmd")]
+	async_engine_task!(false, uring_cmd(fd: RawFd, cmd_op: u32, cmd: Ptr<()>, len: usize, extra: MutPtr<[u64; 2]>) -> Result<u32> {
+		trace(
+			"## uring_cmd(fd = {}, cmd_op = {:#x}, cmd = &[u8; {}]) = {:?}",
+			FdDisplay(fd),
+			cmd_op,
+			len
+		) = result
+	});
 }
 
 #[asynchronous]
@@ -262,21 +299,123 @@ where
 ///
 /// The argument `mode` is only used when creating a file, and specifies the
 /// permissions.
+///
+/// Waits for a permit while the file descriptor budget is exhausted. See
+/// [`fd_budget`]
 #[asynchronous]
 #[allow(clippy::impl_trait_in_params)]
 pub async fn open(path: impl AsRef<Path>, flags: BitFlags<OpenFlag>, mode: u32) -> Result<OwnedFd> {
-	with_path_as_cstr(path, |path: &CStr| async move {
+	let path = path.as_ref();
+
+	budgeted(|| async move { open_unbudgeted(path, flags, mode).await }).await
+}
+
+/// Like [`open`], but ignores the file descriptor budget, for descriptors
+/// that the runtime reserves for itself
+#[asynchronous]
+pub(crate) async fn open_unbudgeted(
+	path: &Path, flags: BitFlags<OpenFlag>, mode: u32
+) -> Result<OwnedFd> {
+	let fd = with_path_as_cstr(path, |path: &CStr| async move {
 		/* Safety: all references must be valid for this function call */
 		unsafe { raw::open(ptr!(path.as_ptr()).cast(), flags.bits(), mode).await }
 	})
-	.await
+	.await?;
+
+	/* the number was reused, so a budgeted descriptor holding it was dropped */
+	fd_budget::release(fd.as_raw_fd());
+	clear_trace_name(fd.as_raw_fd());
+
+	Ok(fd)
+}
+
+#[cfg(feature = "tracing")]
+thread_local! {
+	static TRACE_NAMES: RefCell<HashMap<RawFd, Box<str>>> = RefCell::new(HashMap::new());
+}
+
+/// Displays an fd along with its name, if one was set with
+/// [`set_trace_name`]
+pub(crate) struct FdDisplay(pub(crate) RawFd);
+
+impl fmt::Display for FdDisplay {
+	fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+		#[cfg(feature = "tracing")]
+		if let Some(result) = TRACE_NAMES.with_borrow(|names| {
+			names
+				.get(&self.0)
+				.map(|name| write!(fmt, "{} ({})", self.0, name))
+		}) {
+			return result;
+		}
+
+		fmt::Display::fmt(&self.0, fmt)
+	}
+}
+
+/// Label `fd` with a human readable `name`, which is shown next to the fd
+/// number in trace output. Passing `None` removes the label. The label is
+/// also removed when the fd is closed with [`close`], or when the [`File`] or
+/// socket owning it is dropped.
+///
+/// [`File`]: crate::fs::File
+///
+/// Does nothing unless the `tracing` feature is enabled.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub fn set_trace_name(fd: BorrowedFd<'_>, name: Option<&str>) {
+	#[cfg(feature = "tracing")]
+	TRACE_NAMES.with_borrow_mut(|names| match name {
+		Some(name) => {
+			names.insert(fd.as_raw_fd(), name.into());
+		}
+
+		None => {
+			names.remove(&fd.as_raw_fd());
+		}
+	});
+}
+
+/// Get the label of `fd` set with [`set_trace_name`]. Always `None` unless the
+/// `tracing` feature is enabled
+#[cfg_attr(
+	not(feature = "tracing"),
+	allow(unused_variables, clippy::missing_const_for_fn)
+)]
+#[must_use]
+pub fn trace_name(fd: BorrowedFd<'_>) -> Option<String> {
+	#[cfg(feature = "tracing")]
+	if let Some(name) = TRACE_NAMES.with_borrow(|names| names.get(&fd.as_raw_fd()).cloned()) {
+		return Some(name.into());
+	}
+
+	None
+}
+
+/// Remove the label of `fd`. Called when `fd` is released, and when a new fd
+/// is created, so that it cannot show the label of a closed fd with the same
+/// number
+#[cfg_attr(
+	not(feature = "tracing"),
+	allow(unused_variables, clippy::missing_const_for_fn)
+)]
+pub(crate) fn clear_trace_name(fd: RawFd) {
+	#[cfg(feature = "tracing")]
+	TRACE_NAMES.with_borrow_mut(|names| names.remove(&fd));
 }
 
 /// The equivalent of a `close(2)` syscall. Closes the file descriptor `fd`.
+///
+/// Returns the file descriptor budget permit held by `fd`, if any. See
+/// [`fd_budget`]
 #[asynchronous]
 pub async fn close(fd: OwnedFd) -> Result<()> {
+	let raw = fd.as_raw_fd();
+
+	fd_budget::release(raw);
+	clear_trace_name(raw);
+
 	/* Safety: all references must be valid for this function call */
-	unsafe { raw::close(fd.as_raw_fd()).await }
+	unsafe { raw::close(raw).await }
 }
 
 /// The equivalent of a read(2) syscall. Reads from the file descriptor into the
@@ -300,6 +439,56 @@ pub async fn read(fd: BorrowedFd<'_>, buf: &mut [u8], offset: i64) -> Result<usi
 	}
 }
 
+/// The same as [`read`], but reads into a possibly uninitialized buffer,
+/// avoiding the need to zero it beforehand. See [`assume_init_prefix`] to
+/// obtain the initialized part of the buffer.
+///
+/// Returns the number of bytes read.
+#[asynchronous]
+pub async fn read_uninit(
+	fd: BorrowedFd<'_>, buf: &mut [MaybeUninit<u8>], offset: i64
+) -> Result<usize> {
+	/* Safety: all references must be valid for this function call */
+	unsafe {
+		raw::read(
+			fd.as_raw_fd(),
+			ptr!(buf.as_mut_ptr()).cast(),
+			buf.len(),
+			offset
+		)
+		.await
+	}
+}
+
+/// Get the first `len` bytes of `buf` as an initialized slice
+///
+/// # Safety
+/// The first `len` bytes of `buf` must be initialized, such as by a
+/// successful call to [`read_uninit`] or [`recv_uninit`]
+///
+/// # Panics
+/// If `len` is greater than the length of the buffer
+#[must_use]
+pub unsafe fn assume_init_prefix(buf: &mut [MaybeUninit<u8>], len: usize) -> &mut [u8] {
+	let buf = &mut buf[0..len];
+
+	/* Safety: guaranteed by caller */
+	unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), len) }
+}
+
+/// A [`Read`] stream that can read into a possibly uninitialized buffer,
+/// avoiding the need to zero it beforehand. See [`ReadUninitExt`] for the
+/// operations built on it
+///
+/// [`ReadUninitExt`]: crate::impls::ReadUninitExt
+#[asynchronous]
+pub trait ReadUninit: Read {
+	/// Read into `buf`, returning the part of the buffer that was read into
+	async fn read_uninit<'buf>(
+		&mut self, buf: &'buf mut [MaybeUninit<u8>]
+	) -> Result<&'buf mut [u8]>;
+}
+
 /// The equivalent of a `write(2)` syscall. Write to the file descriptor from
 /// the buffer, with an optional offset. On files that support seeking, if the
 /// offset is set to `-1`, the write operation commences at the file offset, and
@@ -315,12 +504,28 @@ pub async fn write(fd: BorrowedFd<'_>, buf: &[u8], offset: i64) -> Result<usize>
 
 /// The equivalent of a `socket(2)` syscall. A socket is created matching the
 /// `domain`, `socket_type` and `protocol` arguments
+///
+/// Waits for a permit while the file descriptor budget is exhausted. See
+/// [`fd_budget`]
 #[asynchronous]
 pub async fn socket(
 	domain: AddressFamily, socket_type: u32, protocol: IpProtocol
 ) -> Result<OwnedFd> {
-	/* Safety: all references must be valid for this function call */
-	unsafe { raw::socket(domain as u32, socket_type, protocol as u32).await }
+	let fd = budgeted(|| async move {
+		/* Safety: all references must be valid for this function call */
+		unsafe { raw::socket(domain as u32, socket_type, protocol as u32).await }
+	})
+	.await?;
+
+	clear_trace_name(fd.as_raw_fd());
+
+	Ok(fd)
+}
+
+fn addr_len<A>() -> Result<i32> {
+	size_of::<A>()
+		.try_into()
+		.map_err(|_| fmt_error!("Address type too large"))
 }
 
 /// The equivalent of an `accept(2)` syscall. Accepts an incoming connection on
@@ -328,17 +533,76 @@ pub async fn socket(
 /// connection. Returns a tuple of the socket file descriptor and the length in
 /// bytes of the address
 ///
+/// Waits for a permit while the file descriptor budget is exhausted, leaving
+/// the connection pending. See [`fd_budget`]
+///
 /// # Safety
 /// `addr` must be valid for stores of socket addresses
 #[asynchronous]
 pub async unsafe fn accept<A>(socket: BorrowedFd<'_>, addr: &mut A) -> Result<(OwnedFd, i32)> {
-	#[allow(clippy::unwrap_used)]
-	let mut addrlen = size_of::<A>().try_into().unwrap();
+	let permit = acquire_permit().await?;
+
+	/* Safety: guaranteed by caller */
+	let (fd, addrlen) = unsafe { accept_unbudgeted(socket, addr).await? };
+
+	permit.bind(fd.as_fd());
+
+	Ok((fd, addrlen))
+}
+
+/// Like [`accept`], but ignores the file descriptor budget, for connections
+/// that are closed right away
+///
+/// # Safety
+/// `addr` must be valid for stores of socket addresses
+#[asynchronous]
+pub(crate) async unsafe fn accept_unbudgeted<A>(
+	socket: BorrowedFd<'_>, addr: &mut A
+) -> Result<(OwnedFd, i32)> {
+	let mut addrlen = addr_len::<A>()?;
 
 	/* Safety: all references must be valid for this function call */
 	let fd =
 		unsafe { raw::accept(socket.as_raw_fd(), ptr!(addr).cast(), ptr!(&mut addrlen)).await? };
 
+	clear_trace_name(fd.as_raw_fd());
+
+	Ok((fd, addrlen))
+}
+
+/// Accept a connection that is already pending on `socket` with a blocking
+/// `accept4(2)` syscall, failing with [`OsError::WouldBlock`] instead of
+/// waiting if there is none. Does not take a file descriptor budget permit
+///
+/// # Safety
+/// `addr` must be valid for stores of socket addresses
+pub(crate) unsafe fn accept_pending<A>(
+	socket: BorrowedFd<'_>, addr: &mut A
+) -> Result<(OwnedFd, i32)> {
+	/* the listener is blocking, so only accept if a connection is pending */
+	if !poll_ready(socket, PollFlag::In.into())?.intersects(PollFlag::In) {
+		return Err(os::error::OsError::WouldBlock.into());
+	}
+
+	let mut addrlen = addr_len::<A>()?;
+
+	/* Safety: guaranteed by caller */
+	let fd = unsafe {
+		syscall_int!(
+			Accept4,
+			socket.as_raw_fd(),
+			ptr!(addr).as_ptr(),
+			ptr!(&mut addrlen).as_ptr(),
+			0
+		)?
+	};
+
+	#[allow(clippy::cast_possible_truncation)]
+	/* Safety: the kernel gave us a new fd */
+	let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+	clear_trace_name(fd.as_raw_fd());
+
 	Ok((fd, addrlen))
 }
 
@@ -346,16 +610,10 @@ pub async unsafe fn accept<A>(socket: BorrowedFd<'_>, addr: &mut A) -> Result<(O
 /// specified by `addr`
 #[asynchronous]
 pub async fn connect<A>(socket: BorrowedFd<'_>, addr: &A) -> Result<()> {
+	let addrlen = addr_len::<A>()?;
+
 	/* Safety: all references must be valid for this function call */
-	#[allow(clippy::unwrap_used)]
-	unsafe {
-		raw::connect(
-			socket.as_raw_fd(),
-			ptr!(addr).cast(),
-			size_of::<A>().try_into().unwrap()
-		)
-		.await
-	}
+	unsafe { raw::connect(socket.as_raw_fd(), ptr!(addr).cast(), addrlen).await }
 }
 
 /// The same as [`connect`]
@@ -389,6 +647,27 @@ pub async fn recv(
 	}
 }
 
+/// The same as [`recv`], but receives into a possibly uninitialized buffer,
+/// avoiding the need to zero it beforehand. See [`assume_init_prefix`] to
+/// obtain the initialized part of the buffer.
+///
+/// Returns the number of bytes read.
+#[asynchronous]
+pub async fn recv_uninit(
+	socket: BorrowedFd<'_>, buf: &mut [MaybeUninit<u8>], flags: BitFlags<MessageFlag>
+) -> Result<usize> {
+	/* Safety: all references must be valid for this function call */
+	unsafe {
+		raw::recv(
+			socket.as_raw_fd(),
+			ptr!(buf.as_mut_ptr()).cast(),
+			buf.len(),
+			flags.bits()
+		)
+		.await
+	}
+}
+
 /// The equivalent of a `recvmsg(2)` syscall. Receives data from the socket into
 /// a buffer specified by the [`MsgHdrMut`]
 ///
@@ -452,16 +731,10 @@ pub async fn shutdown(socket: BorrowedFd<'_>, how: Shutdown) -> Result<()> {
 /// The equivalent of a `bind(2)` syscall. Assign an address to the socket.
 #[asynchronous]
 pub async fn bind<A>(socket: BorrowedFd<'_>, addr: &A) -> Result<()> {
+	let addrlen = addr_len::<A>()?;
+
 	/* Safety: all references must be valid for this function call */
-	#[allow(clippy::unwrap_used)]
-	unsafe {
-		raw::bind(
-			socket.as_raw_fd(),
-			ptr!(addr).cast(),
-			size_of::<A>().try_into().unwrap()
-		)
-		.await
-	}
+	unsafe { raw::bind(socket.as_raw_fd(), ptr!(addr).cast(), addrlen).await }
 }
 
 /// The same as [`bind`]
@@ -552,6 +825,162 @@ pub async fn statx_fd(
 	}
 }
 
+/// The permissions to check for with [`access`]
+#[bitflags]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum AccessMode {
+	Execute = 1 << 0,
+	Write   = 1 << 1,
+	Read    = 1 << 2
+}
+
+/// The equivalent of a `faccessat(2)` syscall. Checks whether the calling
+/// process can access the file at `path`, relative to `dirfd` or the current
+/// working directory. An empty `mode` checks only for the existence of the
+/// file.
+///
+/// There is no asynchronous version of this syscall, so it runs on the thread
+/// pool.
+#[asynchronous]
+#[allow(clippy::impl_trait_in_params)]
+pub async fn access(
+	dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>, mode: BitFlags<AccessMode>,
+	flags: BitFlags<AtFlag>
+) -> Result<()> {
+	let dirfd = into_raw_dirfd(dirfd);
+
+	with_path_as_cstr(path, |path: &CStr| async move {
+		run_blocking(|_| os::unistd::faccessat(dirfd, path, mode.bits(), flags.bits())).await??;
+
+		Ok(())
+	})
+	.await
+}
+
+/// Access pattern advice for [`fadvise`]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Advice {
+	/// No special treatment
+	Normal     = 0,
+
+	/// Expect page references in random order
+	Random     = 1,
+
+	/// Expect page references in sequential order
+	Sequential = 2,
+
+	/// Expect access in the near future. The kernel starts reading the data
+	/// into the page cache
+	WillNeed   = 3,
+
+	/// Do not expect access in the near future
+	DontNeed   = 4,
+
+	/// Expect the data to be accessed only once
+	NoReuse    = 5
+}
+
+/// The equivalent of a `posix_fadvise(2)` syscall. Announces an intention to
+/// access the file's data in the range starting at `offset` for `len` bytes.
+/// A `len` of zero extends to the end of the file.
+///
+/// See [`Advice`] for a list of possible values.
+#[asynchronous]
+pub async fn fadvise(fd: BorrowedFd<'_>, offset: u64, len: u32, advice: Advice) -> Result<()> {
+	/* Safety: all references must be valid for this function call */
+	unsafe { raw::fadvise(fd.as_raw_fd(), offset, len, advice as u32).await }
+}
+
+const FIONREAD: u64 = 0x541b;
+const TIOCGWINSZ: u64 = 0x5413;
+
+/// # Safety
+/// `arg` must be the type expected by `request`
+unsafe fn ioctl_sync<T>(fd: RawFd, request: u64, arg: &mut T) -> Result<isize> {
+	/* Safety: guaranteed by caller */
+	let result = unsafe { syscall_int!(Ioctl, fd, request, ptr!(arg).as_ptr()) };
+
+	result.map_err(Into::into)
+}
+
+/// The equivalent of an `ioctl(2)` syscall. Issues the device specific
+/// `request` with `arg` as its argument, returning the result of the syscall.
+///
+/// There is no asynchronous version of this syscall, and some requests may
+/// sleep for a long time, so it runs on the thread pool. If cancelled before
+/// the request starts, the request is not issued.
+///
+/// # Safety
+/// `T` must be the type expected by `request`
+#[asynchronous]
+pub async unsafe fn ioctl<T: Send>(fd: BorrowedFd<'_>, request: u64, arg: &mut T) -> Result<isize> {
+	let fd = fd.as_raw_fd();
+
+	/* Safety: guaranteed by caller */
+	run_blocking(|_| unsafe { ioctl_sync(fd, request, arg) }).await?
+}
+
+/// Returns the number of bytes that can be read from `fd` without blocking,
+/// using `FIONREAD`. For a datagram socket, this is the size of the next
+/// datagram.
+///
+/// This request never blocks, so it is not offloaded to the thread pool.
+pub fn bytes_available(fd: BorrowedFd<'_>) -> Result<usize> {
+	let mut available = 0i32;
+
+	/* Safety: FIONREAD takes an int */
+	unsafe { ioctl_sync(fd.as_raw_fd(), FIONREAD, &mut available)? };
+
+	#[allow(clippy::cast_sign_loss)]
+	Ok(available.max(0) as usize)
+}
+
+/// Returns the events in `mask` that are ready on `fd` right now, without
+/// waiting. The equivalent of `poll(2)` with a timeout of zero
+///
+/// See [`poll`] to wait for the events
+pub fn poll_ready(fd: BorrowedFd<'_>, mask: BitFlags<PollFlag>) -> Result<BitFlags<PollFlag>> {
+	#[repr(C)]
+	struct PollFd {
+		fd: i32,
+		events: i16,
+		revents: i16
+	}
+
+	#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+	let mut poll_fd = PollFd { fd: fd.as_raw_fd(), events: mask.bits() as i16, revents: 0 };
+
+	/* Safety: poll_fd is a valid array of one pollfd */
+	unsafe { syscall_int!(Poll, ptr!(&mut poll_fd).as_ptr(), 1, 0)? };
+
+	#[allow(clippy::cast_sign_loss)]
+	Ok(BitFlags::from_bits_truncate(poll_fd.revents as u16 as u32))
+}
+
+/// The size of a terminal
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct WindowSize {
+	pub rows: u16,
+	pub cols: u16,
+	pub x_pixels: u16,
+	pub y_pixels: u16
+}
+
+/// Returns the size of the terminal `fd`, using `TIOCGWINSZ`
+///
+/// This request never blocks, so it is not offloaded to the thread pool.
+pub fn window_size(fd: BorrowedFd<'_>) -> Result<WindowSize> {
+	let mut size = WindowSize::default();
+
+	/* Safety: TIOCGWINSZ takes a winsize */
+	unsafe { ioctl_sync(fd.as_raw_fd(), TIOCGWINSZ, &mut size)? };
+
+	Ok(size)
+}
+
 /// Wait for an event on a file descriptor.
 ///
 /// See [`PollFlag`] for a list of possible events.
@@ -564,3 +993,105 @@ pub async fn poll(fd: BorrowedFd<'_>, mask: BitFlags<PollFlag>) -> Result<BitFla
 
 	Ok(BitFlags::from_bits_truncate(bits))
 }
+
+/// Submit a passthrough command to the driver behind `fd`, such as an NVMe
+/// command to a `/dev/ng*` character device. `cmd_op` selects the operation,
+/// like `NVME_URING_CMD_IO`, and `cmd` is the driver specific command struct.
+/// The struct is copied when the operation starts.
+///
+/// Requires the `uring-cmd` feature, which sets the ring up with 128 byte
+/// submission entries and 32 byte completion entries. The status is returned
+/// along with the second half of the completion entry. See [`CmdCompletion`]
+///
+/// # Errors
+/// With `ENOSYS` if the kernel does not support passthrough commands, or
+/// `EINVAL` if `cmd` is larger than 80 bytes
+///
+/// # Safety
+/// Any buffers referenced by `cmd` must be valid for the command's use until
+/// this function returns, and `cmd` must be a valid command for the driver
+#[cfg(feature = "uring-cmd")]
+#[asynchronous]
+pub async unsafe fn uring_cmd<C>(
+	fd: BorrowedFd<'_>, cmd_op: u32, cmd: &C
+) -> Result<CmdCompletion> {
+	let mut extra = [0; 2];
+
+	/* Safety: guaranteed by caller. extra outlives the operation */
+	let result = unsafe {
+		raw::uring_cmd(
+			fd.as_raw_fd(),
+			cmd_op,
+			ptr!(cmd).cast(),
+			size_of::<C>(),
+			ptr!(&mut extra)
+		)
+		.await?
+	};
+
+	Ok(CmdCompletion { result, extra })
+}
+
+/// The completion of a [`uring_cmd`]
+#[cfg(feature = "uring-cmd")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CmdCompletion {
+	/// The status returned by the driver
+	pub result: u32,
+
+	/// The driver specific data in the second half of the 32 byte completion
+	/// entry, such as the result of an NVMe command
+	pub extra: [u64; 2]
+}
+
+fn timed_out() -> Error {
+	fmt_error!("Operation timed out" @ ErrorKind::TimedOut)
+}
+
+/// Run `task` with a time limit. If the limit is reached, the task is
+/// cancelled and an error with [`ErrorKind::TimedOut`] is returned
+#[asynchronous]
+async fn with_timeout<T, Output>(task: T, timeout: Duration) -> Result<Output>
+where
+	T: for<'ctx> Task<Output<'ctx> = Result<Output>>
+{
+	task.timeout(timeout).await.unwrap_or_else(|| Err(timed_out()))
+}
+
+/// The same as [`read`], but fails with [`ErrorKind::TimedOut`] if no data is
+/// read within `timeout`
+#[asynchronous]
+pub async fn read_timeout(
+	fd: BorrowedFd<'_>, buf: &mut [u8], offset: i64, timeout: Duration
+) -> Result<usize> {
+	with_timeout(read(fd, buf, offset), timeout).await
+}
+
+/// The same as [`recv`], but fails with [`ErrorKind::TimedOut`] if no data is
+/// received within `timeout`
+#[asynchronous]
+pub async fn recv_timeout(
+	socket: BorrowedFd<'_>, buf: &mut [u8], flags: BitFlags<MessageFlag>, timeout: Duration
+) -> Result<usize> {
+	with_timeout(recv(socket, buf, flags), timeout).await
+}
+
+/// The same as [`accept`], but fails with [`ErrorKind::TimedOut`] if no
+/// connection is accepted within `timeout`
+///
+/// # Safety
+/// See [`accept`]
+#[asynchronous]
+pub async unsafe fn accept_timeout<A>(
+	socket: BorrowedFd<'_>, addr: &mut A, timeout: Duration
+) -> Result<(OwnedFd, i32)> {
+	/* Safety: guaranteed by caller */
+	with_timeout(unsafe { accept(socket, addr) }, timeout).await
+}
+
+/// Get the syscall counters for the I/O engine of the current runtime. See
+/// [`EngineStats`]
+#[asynchronous]
+pub async fn engine_stats() -> EngineStats {
+	internal_get_driver().await.engine_stats()
+}
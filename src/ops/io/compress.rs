@@ -0,0 +1,509 @@
+//! Compression stream adapters
+//!
+//! The adapters in this module wrap any [`Read`] or [`Write`] stream and
+//! perform the actual compression on the thread pool, one chunk at a time.
+//! While a chunk is being processed, I/O for the previous (or next) chunk
+//! continues on the runtime.
+//!
+//! # Examples
+//!
+//! ```
+//! let file = File::create("log.gz").await?;
+//! let mut writer = CompressWriter::new(file, GzipEncoder::default());
+//!
+//! writer.write_all(b"hello world").await?;
+//! writer.finish().await?;
+//! ```
+
+use std::io::Write as _;
+use std::mem::take;
+
+use xx_core::async_std::io::*;
+
+use super::*;
+
+/// The default amount of data processed by the thread pool at once
+pub const DEFAULT_CHUNK_SIZE: usize = 0x10000;
+
+fn check_chunk_size(chunk_size: usize) -> Result<()> {
+	if chunk_size == 0 {
+		return Err(fmt_error!("Chunk size must be non-zero" @ ErrorKind::InvalidInput));
+	}
+
+	Ok(())
+}
+
+/// A streaming compression or decompression algorithm
+///
+/// Codecs are moved to the thread pool while processing a chunk, so they must
+/// be [`Send`].
+pub trait Codec: Send {
+	/// Process `input`, appending any available output to `output`
+	///
+	/// # Errors
+	/// If the input is malformed, or the codec failed
+	fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> std::io::Result<()>;
+
+	/// Finish the stream, appending any remaining output to `output`
+	///
+	/// # Errors
+	/// If the stream is incomplete, or the codec failed
+	fn finish(&mut self, output: &mut Vec<u8>) -> std::io::Result<()>;
+}
+
+macro_rules! impl_write_codec {
+	($type:ident, $finish:ident) => {
+		impl Codec for $type {
+			fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> std::io::Result<()> {
+				self.0.write_all(input)?;
+				output.append(self.0.get_mut());
+
+				Ok(())
+			}
+
+			fn finish(&mut self, output: &mut Vec<u8>) -> std::io::Result<()> {
+				self.0.$finish()?;
+				output.append(self.0.get_mut());
+
+				Ok(())
+			}
+		}
+	};
+}
+
+/// A gzip compressor
+#[cfg(feature = "compress-gzip")]
+pub struct GzipEncoder(flate2::write::GzEncoder<Vec<u8>>);
+
+#[cfg(feature = "compress-gzip")]
+impl GzipEncoder {
+	/// Create a new compressor with the compression `level`, from 0 to 9
+	#[must_use]
+	pub fn new(level: u32) -> Self {
+		Self(flate2::write::GzEncoder::new(
+			Vec::new(),
+			flate2::Compression::new(level)
+		))
+	}
+}
+
+#[cfg(feature = "compress-gzip")]
+impl Default for GzipEncoder {
+	fn default() -> Self {
+		Self(flate2::write::GzEncoder::new(
+			Vec::new(),
+			flate2::Compression::default()
+		))
+	}
+}
+
+#[cfg(feature = "compress-gzip")]
+impl_write_codec!(GzipEncoder, try_finish);
+
+/// A gzip decompressor
+#[cfg(feature = "compress-gzip")]
+pub struct GzipDecoder(flate2::write::GzDecoder<Vec<u8>>);
+
+#[cfg(feature = "compress-gzip")]
+impl GzipDecoder {
+	#[must_use]
+	pub fn new() -> Self {
+		Self(flate2::write::GzDecoder::new(Vec::new()))
+	}
+}
+
+#[cfg(feature = "compress-gzip")]
+impl Default for GzipDecoder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(feature = "compress-gzip")]
+impl_write_codec!(GzipDecoder, try_finish);
+
+/// A zstd compressor
+#[cfg(feature = "compress-zstd")]
+pub struct ZstdEncoder(zstd::stream::write::Encoder<'static, Vec<u8>>);
+
+#[cfg(feature = "compress-zstd")]
+impl ZstdEncoder {
+	/// Create a new compressor with the compression `level`. A level of `0`
+	/// uses zstd's default
+	///
+	/// # Errors
+	/// If the zstd context could not be created
+	pub fn new(level: i32) -> Result<Self> {
+		Ok(Self(zstd::stream::write::Encoder::new(Vec::new(), level)?))
+	}
+}
+
+#[cfg(feature = "compress-zstd")]
+impl_write_codec!(ZstdEncoder, do_finish);
+
+/// How much output to make room for on each call into the zstd decoder
+#[cfg(feature = "compress-zstd")]
+const ZSTD_OUTPUT_CHUNK: usize = 0x20000;
+
+/// A zstd decompressor. Finishing fails with
+/// [`std::io::ErrorKind::UnexpectedEof`] if the input ends in the middle of a
+/// frame
+#[cfg(feature = "compress-zstd")]
+pub struct ZstdDecoder {
+	decoder: zstd::stream::raw::Decoder<'static>,
+	in_frame: bool
+}
+
+#[cfg(feature = "compress-zstd")]
+impl ZstdDecoder {
+	/// # Errors
+	/// If the zstd context could not be created
+	pub fn new() -> Result<Self> {
+		Ok(Self { decoder: zstd::stream::raw::Decoder::new()?, in_frame: false })
+	}
+}
+
+#[cfg(feature = "compress-zstd")]
+impl Codec for ZstdDecoder {
+	#[allow(clippy::arithmetic_side_effects)]
+	fn update(&mut self, mut input: &[u8], output: &mut Vec<u8>) -> std::io::Result<()> {
+		use zstd::stream::raw::Operation;
+
+		loop {
+			let start = output.len();
+
+			output.resize(start + ZSTD_OUTPUT_CHUNK, 0);
+
+			let status = self.decoder.run_on_buffers(input, &mut output[start..]);
+			let written = status.as_ref().map_or(0, |status| status.bytes_written);
+
+			output.truncate(start + written);
+
+			let status = status?;
+
+			input = &input[status.bytes_read..];
+
+			/* zero once a frame is fully decoded and flushed */
+			self.in_frame = status.remaining != 0;
+
+			/* room was left, so everything read so far was flushed */
+			if input.is_empty() && written < ZSTD_OUTPUT_CHUNK {
+				return Ok(());
+			}
+		}
+	}
+
+	fn finish(&mut self, output: &mut Vec<u8>) -> std::io::Result<()> {
+		self.update(&[], output)?;
+
+		if self.in_frame {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::UnexpectedEof,
+				"Truncated zstd stream"
+			));
+		}
+
+		Ok(())
+	}
+}
+
+/// Run the codec on the thread pool
+#[asynchronous]
+async fn process<C: Codec>(
+	codec: &mut C, input: &[u8], output: &mut Vec<u8>, finish: bool
+) -> Result<()> {
+	run_blocking(|_| -> std::io::Result<()> {
+		codec.update(input, output)?;
+
+		if finish {
+			codec.finish(output)?;
+		}
+
+		Ok(())
+	})
+	.await??;
+
+	Ok(())
+}
+
+/// Write `data` from `written` onwards, counting the bytes written in
+/// `written` so that a failed write can be resumed without writing any data
+/// twice
+#[asynchronous]
+async fn write_pending<W: Write>(
+	inner: &mut W, data: &[u8], written: &mut usize
+) -> Result<()> {
+	while *written < data.len() {
+		let wrote = inner.write(&data[*written..]).await?;
+
+		if wrote == 0 {
+			return Err(fmt_error!("Inner stream accepted no data" @ ErrorKind::WriteZero));
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(*written += wrote);
+	}
+
+	Ok(())
+}
+
+/// A writer which runs the data written to it through a [`Codec`] before
+/// writing it to the inner stream
+pub struct CompressWriter<W, C> {
+	inner: W,
+	codec: C,
+	input: Vec<u8>,
+	output: Vec<u8>,
+	written: usize,
+	chunk_size: usize,
+	finished: bool
+}
+
+#[asynchronous]
+impl<W: Write, C: Codec> CompressWriter<W, C> {
+	/// Create a new writer with the [default chunk size](DEFAULT_CHUNK_SIZE)
+	pub fn new(inner: W, codec: C) -> Self {
+		Self::new_unchecked(inner, codec, DEFAULT_CHUNK_SIZE)
+	}
+
+	/// Create a new writer which processes `chunk_size` bytes at a time
+	///
+	/// # Errors
+	/// With [`ErrorKind::InvalidInput`] if `chunk_size` is zero
+	pub fn with_chunk_size(inner: W, codec: C, chunk_size: usize) -> Result<Self> {
+		check_chunk_size(chunk_size)?;
+
+		Ok(Self::new_unchecked(inner, codec, chunk_size))
+	}
+
+	fn new_unchecked(inner: W, codec: C, chunk_size: usize) -> Self {
+		Self {
+			inner,
+			codec,
+			input: Vec::with_capacity(chunk_size),
+			output: Vec::new(),
+			written: 0,
+			chunk_size,
+			finished: false
+		}
+	}
+
+	/// Compress the buffered input while the previously compressed chunk is
+	/// written to the inner stream
+	///
+	/// Once the codec has consumed the input, it is not processed again, and
+	/// output that could not be written is kept for the next call
+	async fn process(&mut self, finish: bool) -> Result<()> {
+		let mut pending = take(&mut self.output);
+		let Join(processed, written) = join(
+			process(&mut self.codec, &self.input, &mut self.output, finish),
+			write_pending(&mut self.inner, &pending, &mut self.written)
+		)
+		.await;
+
+		if processed.is_ok() {
+			self.input.clear();
+		}
+
+		if written.is_ok() {
+			self.written = 0;
+		} else {
+			/* the unwritten output goes before the newly processed output */
+			pending.append(&mut self.output);
+			self.output = pending;
+		}
+
+		written?;
+		processed
+	}
+
+	/// Write all of the processed output to the inner stream
+	async fn write_output(&mut self) -> Result<()> {
+		write_pending(&mut self.inner, &self.output, &mut self.written).await?;
+
+		self.output.clear();
+		self.written = 0;
+
+		Ok(())
+	}
+
+	/// Finish the stream, writing any remaining data to the inner stream.
+	/// Further writes fail after this function is called
+	///
+	/// # Cancel safety
+	///
+	/// This function is not cancel safe. Data may be lost if interrupted.
+	pub async fn finish(&mut self) -> Result<()> {
+		if self.finished {
+			return Ok(());
+		}
+
+		self.process(true).await?;
+		self.write_output().await?;
+		self.finished = true;
+		self.inner.flush().await
+	}
+
+	#[must_use]
+	pub const fn get_ref(&self) -> &W {
+		&self.inner
+	}
+
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.inner
+	}
+
+	/// Unwraps this writer, returning the inner stream. Call
+	/// [`CompressWriter::finish`] before unwrapping, otherwise buffered data
+	/// is lost
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+#[asynchronous]
+impl<W: Write, C: Codec> Write for CompressWriter<W, C> {
+	async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		if self.finished {
+			return Err(fmt_error!("Stream already finished"));
+		}
+
+		write_from!(buf);
+
+		/* process a full chunk first, so that no error is returned after buffering */
+		if self.input.len() >= self.chunk_size {
+			self.process(false).await?;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let available = self.chunk_size - self.input.len();
+		let len = buf.len().min(available);
+
+		self.input.extend_from_slice(&buf[0..len]);
+
+		Ok(len)
+	}
+
+	async fn flush(&mut self) -> Result<()> {
+		if !self.input.is_empty() {
+			self.process(false).await?;
+		}
+
+		self.write_output().await?;
+		self.inner.flush().await
+	}
+}
+
+/// A reader which runs the data read from the inner stream through a
+/// [`Codec`]
+pub struct DecompressReader<R, C> {
+	inner: R,
+	codec: C,
+	input: Vec<u8>,
+	next: Vec<u8>,
+	output: Vec<u8>,
+	pos: usize,
+	eof: bool,
+	finished: bool
+}
+
+#[asynchronous]
+impl<R: Read, C: Codec> DecompressReader<R, C> {
+	/// Create a new reader with the [default chunk size](DEFAULT_CHUNK_SIZE)
+	pub fn new(inner: R, codec: C) -> Self {
+		Self::new_unchecked(inner, codec, DEFAULT_CHUNK_SIZE)
+	}
+
+	/// Create a new reader which processes `chunk_size` bytes at a time
+	///
+	/// # Errors
+	/// With [`ErrorKind::InvalidInput`] if `chunk_size` is zero
+	pub fn with_chunk_size(inner: R, codec: C, chunk_size: usize) -> Result<Self> {
+		check_chunk_size(chunk_size)?;
+
+		Ok(Self::new_unchecked(inner, codec, chunk_size))
+	}
+
+	fn new_unchecked(inner: R, codec: C, chunk_size: usize) -> Self {
+		Self {
+			inner,
+			codec,
+			input: Vec::with_capacity(chunk_size),
+			next: vec![0; chunk_size],
+			output: Vec::new(),
+			pos: 0,
+			eof: false,
+			finished: false
+		}
+	}
+
+	/// Decompress the buffered input while the next chunk is read from the
+	/// inner stream
+	async fn fill(&mut self) -> Result<()> {
+		self.output.clear();
+		self.pos = 0;
+
+		if self.eof {
+			process(&mut self.codec, &self.input, &mut self.output, true).await?;
+
+			self.input.clear();
+			self.finished = true;
+
+			return Ok(());
+		}
+
+		let Join(processed, read) = join(
+			process(&mut self.codec, &self.input, &mut self.output, false),
+			self.inner.read(&mut self.next)
+		)
+		.await;
+
+		processed?;
+
+		let read = read?;
+
+		self.input.clear();
+		self.input.extend_from_slice(&self.next[0..read]);
+		self.eof = read == 0;
+
+		Ok(())
+	}
+
+	#[must_use]
+	pub const fn get_ref(&self) -> &R {
+		&self.inner
+	}
+
+	pub fn get_mut(&mut self) -> &mut R {
+		&mut self.inner
+	}
+
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+}
+
+#[asynchronous]
+impl<R: Read, C: Codec> Read for DecompressReader<R, C> {
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		read_into!(buf);
+
+		while self.pos >= self.output.len() {
+			if self.finished {
+				return Ok(0);
+			}
+
+			self.fill().await?;
+		}
+
+		let output = &self.output[self.pos..];
+		let len = buf.len().min(output.len());
+
+		buf[0..len].copy_from_slice(&output[0..len]);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.pos += len);
+
+		Ok(len)
+	}
+}
@@ -0,0 +1,136 @@
+//! Idle timeouts for streams
+//!
+//! An [`IdleTimeout`] wraps any [`Read`] or [`Write`] stream and fails reads
+//! once the stream has been idle for too long, which protects servers from
+//! peers that open connections and then trickle data or go silent.
+//!
+//! # Examples
+//!
+//! ```
+//! let (client, _) = listener.accept().await?;
+//! let mut client = IdleTimeout::new(client, duration!(30 s));
+//!
+//! /* fails with `ErrorKind::TimedOut` after 30 seconds without reads or writes */
+//! client.read(&mut buf).await?;
+//! ```
+
+use std::cell::Cell;
+
+use super::*;
+
+fn timed_out() -> Error {
+	fmt_error!("Stream was idle for too long" @ ErrorKind::TimedOut)
+}
+
+/// A stream which fails reads with [`ErrorKind::TimedOut`] after no data was
+/// read or written for the idle duration
+///
+/// Reads and writes share the deadline. Each successful read or write pushes
+/// the deadline back, which only stores the current time. The timer for a
+/// pending read is only rearmed when it fires early because of activity
+pub struct IdleTimeout<S> {
+	inner: S,
+	idle: u64,
+	last: Cell<u64>,
+	owner: Option<TimerOwner>
+}
+
+fn remaining(last: u64, idle: u64) -> Duration {
+	let deadline = last.saturating_add(idle);
+
+	Duration::from_nanos(deadline.saturating_sub(nanotime()))
+}
+
+#[asynchronous]
+impl<S> IdleTimeout<S> {
+	/// Wrap `inner`, starting the idle period now
+	pub fn new(inner: S, idle: Duration) -> Self {
+		Self {
+			inner,
+			idle: idle.as_nanos().try_into().unwrap_or(u64::MAX),
+			last: Cell::new(nanotime()),
+			owner: None
+		}
+	}
+
+	/// Record activity on the stream, pushing back the deadline. Reads and
+	/// writes through this wrapper do this automatically
+	pub fn touch(&self) {
+		self.last.set(nanotime());
+	}
+
+	/// The time remaining until the stream is considered idle, or zero if it
+	/// already is
+	#[must_use]
+	pub fn remaining(&self) -> Duration {
+		remaining(self.last.get(), self.idle)
+	}
+
+	#[must_use]
+	pub const fn get_ref(&self) -> &S {
+		&self.inner
+	}
+
+	pub fn get_mut(&mut self) -> &mut S {
+		&mut self.inner
+	}
+
+	pub fn into_inner(self) -> S {
+		self.inner
+	}
+}
+
+#[asynchronous]
+impl<S: Read> Read for IdleTimeout<S> {
+	/// Read from the inner stream, failing with [`ErrorKind::TimedOut`] if the
+	/// stream becomes idle first
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe if the inner stream is cancel safe.
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		read_into!(buf);
+
+		/* register the timers to the stream, so none outlive it */
+		let owner = match self.owner.take() {
+			Some(owner) => owner,
+			None => TimerOwner::new().await
+		};
+
+		let owner = self.owner.insert(owner);
+
+		let read = loop {
+			let remaining = remaining(self.last.get(), self.idle);
+
+			if remaining.is_zero() {
+				return Err(timed_out());
+			}
+
+			match select(self.inner.read(buf), owner.sleep(remaining)).await {
+				Select::First(read, _) | Select::Second(_, Some(read)) => break read?,
+
+				/* the deadline may have been pushed back while waiting */
+				Select::Second(slept, None) => slept?
+			}
+		};
+
+		self.touch();
+
+		Ok(read)
+	}
+}
+
+#[asynchronous]
+impl<S: Write> Write for IdleTimeout<S> {
+	async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		let wrote = self.inner.write(buf).await?;
+
+		self.touch();
+
+		Ok(wrote)
+	}
+
+	async fn flush(&mut self) -> Result<()> {
+		self.inner.flush().await
+	}
+}
@@ -0,0 +1,271 @@
+//! Operations that borrow their buffers for their whole lifetime
+//!
+//! An [`OwnedOp`] describes a single raw engine operation together with the
+//! file descriptor and buffers it uses. The borrows are held by the op until
+//! [`OwnedOp::run`] returns, and `run` only returns once the engine has
+//! completed or cancelled the operation, so the buffers cannot be freed or
+//! reused while the kernel may still access them.
+//!
+//! This allows building operations ahead of time, storing them, or racing
+//! them with [`select`], without writing `unsafe` around [`raw`] calls.
+//!
+//! Timeouts, retries and tracing can be added to an op with [`OpExt`].
+//!
+//! # Examples
+//!
+//! ```
+//! let read = OwnedOp::read(file.fd(), &mut buf, 0);
+//! let write = OwnedOp::write(socket.fd(), &data, -1);
+//!
+//! match select(read.run(), write.run()).await {
+//! 	Select::First(read, _) => println!("read {} bytes", read?),
+//! 	Select::Second(wrote, _) => println!("wrote {} bytes", wrote?)
+//! }
+//!
+//! /* give up after three attempts of one second each */
+//! let policy = RetryPolicy { retries: 2, delay: Duration::ZERO };
+//! let recv = OwnedOp::recv(socket.fd(), &mut buf, BitFlags::default())
+//! 	.with_timeout(Duration::from_secs(1))
+//! 	.with_retry(policy)
+//! 	.traced("heartbeat");
+//!
+//! recv.run().await?;
+//! ```
+
+use std::marker::PhantomData;
+
+use xx_core::os::error::OsError;
+
+use super::*;
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+	Read { buf: MutPtr<()>, len: usize, offset: i64 },
+	Write { buf: Ptr<()>, len: usize, offset: i64 },
+	Recv { buf: MutPtr<()>, len: usize, flags: u32 },
+	Send { buf: Ptr<()>, len: usize, flags: u32 },
+	Poll { mask: u32 },
+	Fsync
+}
+
+/// How [`OpExt::with_retry`] retries an operation
+///
+/// An attempt is retried if it failed with an error that may not occur again,
+/// such as [`ErrorKind::TimedOut`] from [`OpExt::with_timeout`], or an
+/// `EAGAIN` or `EINTR` from the kernel. Operations are never retried after the
+/// task running them is interrupted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RetryPolicy {
+	/// The number of attempts after the first one
+	pub retries: u32,
+
+	/// How long to wait before each retry
+	pub delay: Duration
+}
+
+impl RetryPolicy {
+	fn should_retry(err: &Error) -> bool {
+		err.kind() == ErrorKind::TimedOut ||
+			matches!(err.os_error(), Some(OsError::WouldBlock | OsError::Intr))
+	}
+}
+
+/// Adds timeouts, retries and tracing to an operation
+pub trait OpExt: Sized {
+	/// Fail each attempt with [`ErrorKind::TimedOut`] if it does not complete
+	/// within `timeout`. The attempt is cancelled before the error is returned
+	fn with_timeout(self, timeout: Duration) -> Self;
+
+	/// Retry failed attempts. See [`RetryPolicy`]
+	fn with_retry(self, policy: RetryPolicy) -> Self;
+
+	/// Trace each attempt and its result with `name`, if tracing is enabled
+	/// for [`TraceSubsystem::Io`]
+	fn traced(self, name: &str) -> Self;
+}
+
+/// A raw I/O operation that borrows its file descriptor and buffers for `'buf`
+///
+/// See the [module level documentation](self) for more information
+#[must_use = "operations do nothing unless run"]
+#[derive(Debug)]
+pub struct OwnedOp<'buf> {
+	fd: RawFd,
+	op: Op,
+	timeout: Option<Duration>,
+	retry: RetryPolicy,
+	name: Option<Box<str>>,
+	phantom: PhantomData<&'buf mut [u8]>
+}
+
+impl OpExt for OwnedOp<'_> {
+	fn with_timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	fn with_retry(mut self, policy: RetryPolicy) -> Self {
+		self.retry = policy;
+		self
+	}
+
+	fn traced(mut self, name: &str) -> Self {
+		self.name = Some(name.into());
+		self
+	}
+}
+
+#[asynchronous]
+impl<'buf> OwnedOp<'buf> {
+	fn new(fd: BorrowedFd<'buf>, op: Op) -> Self {
+		Self {
+			fd: fd.as_raw_fd(),
+			op,
+			timeout: None,
+			retry: RetryPolicy::default(),
+			name: None,
+			phantom: PhantomData
+		}
+	}
+
+	/// A read into `buf`. See [`read`]
+	pub fn read(fd: BorrowedFd<'buf>, buf: &'buf mut [u8], offset: i64) -> Self {
+		let len = buf.len();
+
+		Self::new(fd, Op::Read { buf: ptr!(buf.as_mut_ptr()).cast(), len, offset })
+	}
+
+	/// A read into a possibly uninitialized `buf`. See [`read_uninit`]
+	pub fn read_uninit(
+		fd: BorrowedFd<'buf>, buf: &'buf mut [MaybeUninit<u8>], offset: i64
+	) -> Self {
+		let len = buf.len();
+
+		Self::new(fd, Op::Read { buf: ptr!(buf.as_mut_ptr()).cast(), len, offset })
+	}
+
+	/// A write from `buf`. See [`write`]
+	pub fn write(fd: BorrowedFd<'buf>, buf: &'buf [u8], offset: i64) -> Self {
+		let len = buf.len();
+
+		Self::new(fd, Op::Write { buf: ptr!(buf.as_ptr()).cast(), len, offset })
+	}
+
+	/// A receive into `buf`. See [`recv`]
+	pub fn recv(fd: BorrowedFd<'buf>, buf: &'buf mut [u8], flags: BitFlags<MessageFlag>) -> Self {
+		let len = buf.len();
+
+		Self::new(
+			fd,
+			Op::Recv { buf: ptr!(buf.as_mut_ptr()).cast(), len, flags: flags.bits() }
+		)
+	}
+
+	/// A send from `buf`. See [`send`]
+	pub fn send(fd: BorrowedFd<'buf>, buf: &'buf [u8], flags: BitFlags<MessageFlag>) -> Self {
+		let len = buf.len();
+
+		Self::new(fd, Op::Send { buf: ptr!(buf.as_ptr()).cast(), len, flags: flags.bits() })
+	}
+
+	/// A wait for the events in `mask`. See [`poll`]. The result is the bits of
+	/// the events that were notified
+	pub fn poll(fd: BorrowedFd<'buf>, mask: BitFlags<PollFlag>) -> Self {
+		Self::new(fd, Op::Poll { mask: mask.bits() })
+	}
+
+	/// A sync of the file to disk. See [`fsync`]
+	pub fn fsync(fd: BorrowedFd<'buf>) -> Self {
+		Self::new(fd, Op::Fsync)
+	}
+
+	/// Run the operation, returning the number of bytes transferred, the
+	/// notified events for [`OwnedOp::poll`], or zero for operations without a
+	/// result
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe if the operation is. Reads and receives
+	/// may lose data if cancelled after the kernel has consumed it.
+	pub async fn run(self) -> Result<usize> {
+		let mut retries = self.retry.retries;
+
+		loop {
+			let result = match self.timeout {
+				Some(timeout) => with_timeout(self.run_once(), timeout).await,
+				None => self.run_once().await
+			};
+
+			if let Some(name) = &self.name {
+				if trace_enabled(TraceSubsystem::Io) {
+					xx_core::trace!(
+						target: &self,
+						"## {}: {:?}(fd = {}) = {:?}",
+						name,
+						self.op,
+						self.fd,
+						result
+					);
+				}
+			}
+
+			match result {
+				Err(err) if retries > 0 && RetryPolicy::should_retry(&err) => (),
+				result => break result
+			}
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(retries -= 1);
+
+			check_interrupt().await?;
+
+			if !self.retry.delay.is_zero() {
+				sleep(self.retry.delay).await?;
+			}
+		}
+	}
+
+	async fn run_once(&self) -> Result<usize> {
+		let fd = self.fd;
+
+		/* the borrows of the fd and buffers are held by `self` until the
+		 * operation completes or is cancelled, which happens before the raw
+		 * function returns
+		 */
+		match self.op {
+			Op::Read { buf, len, offset } => {
+				/* Safety: `buf` is mutably borrowed for `len` bytes until we return */
+				unsafe { raw::read(fd, buf, len, offset).await }
+			}
+
+			Op::Write { buf, len, offset } => {
+				/* Safety: `buf` is borrowed for `len` bytes until we return */
+				unsafe { raw::write(fd, buf, len, offset).await }
+			}
+
+			Op::Recv { buf, len, flags } => {
+				/* Safety: `buf` is mutably borrowed for `len` bytes until we return */
+				unsafe { raw::recv(fd, buf, len, flags).await }
+			}
+
+			Op::Send { buf, len, flags } => {
+				/* Safety: `buf` is borrowed for `len` bytes until we return */
+				unsafe { raw::send(fd, buf, len, flags).await }
+			}
+
+			Op::Poll { mask } => {
+				/* Safety: no buffers are used, and `fd` is borrowed until we return */
+				let bits = unsafe { raw::poll(fd, mask).await? };
+
+				Ok(bits as usize)
+			}
+
+			Op::Fsync => {
+				/* Safety: no buffers are used, and `fd` is borrowed until we return */
+				unsafe { raw::fsync(fd).await? };
+
+				Ok(0)
+			}
+		}
+	}
+}
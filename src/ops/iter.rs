@@ -0,0 +1,155 @@
+//! Combinators for async iterators
+
+pub use xx_core::async_std::AsyncIterator;
+
+use super::*;
+
+/// An async iterator which yields items from two async iterators as they
+/// become available. See [`merge`] for more information.
+pub struct Merge<A, B: AsyncIterator> {
+	first: Option<A>,
+	second: Option<B>,
+	pending: Option<B::Item>,
+
+	/// Which iterator is polled first on the next call, alternated so that
+	/// neither is favored when both have items ready
+	second_first: bool
+}
+
+impl<A, B: AsyncIterator> Merge<A, B> {
+	fn end(&mut self, first: bool) {
+		if first {
+			self.first = None;
+		} else {
+			self.second = None;
+		}
+	}
+}
+
+#[asynchronous]
+impl<A, B> AsyncIterator for Merge<A, B>
+where
+	A: AsyncIterator,
+	B: AsyncIterator<Item = A::Item>
+{
+	type Item = A::Item;
+
+	/// Get the next item from either iterator. Returns `None` once both
+	/// iterators have ended.
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe if both of the iterators are cancel safe.
+	async fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(item) = self.pending.take() {
+				return Some(item);
+			}
+
+			let (first, second) = match (&mut self.first, &mut self.second) {
+				(Some(first), Some(second)) => (first, second),
+				(Some(first), None) => return first.next().await,
+				(None, Some(second)) => return second.next().await,
+				(None, None) => return None
+			};
+
+			let second_first = self.second_first;
+
+			self.second_first = !second_first;
+
+			let (item, other, is_first) = if second_first {
+				match select(second.next(), first.next()).await {
+					Select::First(item, other) => (item, other, false),
+					Select::Second(item, other) => (item, other, true)
+				}
+			} else {
+				match select(first.next(), second.next()).await {
+					Select::First(item, other) => (item, other, true),
+					Select::Second(item, other) => (item, other, false)
+				}
+			};
+
+			/* the other iterator may have completed before it could be cancelled */
+			match other {
+				Some(Some(other)) => self.pending = Some(other),
+				Some(None) => self.end(!is_first),
+				None => ()
+			}
+
+			match item {
+				Some(item) => return Some(item),
+				None => self.end(is_first)
+			}
+		}
+	}
+}
+
+/// Merge two async iterators with the same item type into one, yielding items
+/// from whichever iterator produces one first. The merged iterator ends once
+/// both iterators have ended.
+///
+/// The iterator polled first alternates between calls, so a busy iterator
+/// cannot starve the other.
+///
+/// Nest calls to merge more than two sources.
+///
+/// # Examples
+///
+/// ```
+/// let v4 = Tcp::bind("0.0.0.0:8080").await?;
+/// let v6 = Tcp::bind("[::]:8080").await?;
+/// let mut incoming = merge(v4.incoming(), v6.incoming());
+///
+/// while let Some(connection) = incoming.next().await {
+/// 	let (client, addr) = connection?;
+/// }
+/// ```
+pub const fn merge<A, B>(first: A, second: B) -> Merge<A, B>
+where
+	A: AsyncIterator,
+	B: AsyncIterator<Item = A::Item>
+{
+	Merge { first: Some(first), second: Some(second), pending: None, second_first: false }
+}
+
+/// The result of [`next_or`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NextOr<T, Output> {
+	/// The iterator produced an item, or `None` if it ended
+	Next(Option<T>),
+
+	/// The other task completed first
+	Other(Output)
+}
+
+/// Get the next item from `iter`, unless `other` completes first. Whichever
+/// finishes second is cancelled.
+///
+/// Useful for consuming a stream while watching for a shutdown signal.
+///
+/// # Examples
+///
+/// ```
+/// loop {
+/// 	match next_or(&mut incoming, shutdown.recv()).await {
+/// 		NextOr::Next(Some(connection)) => handle(connection?),
+/// 		NextOr::Next(None) | NextOr::Other(_) => break
+/// 	}
+/// }
+/// ```
+///
+/// # Cancel safety
+///
+/// This function is cancel safe if both `iter` and `other` are cancel safe.
+#[asynchronous]
+pub async fn next_or<I, T, Output>(iter: &mut I, other: T) -> NextOr<I::Item, Output>
+where
+	I: AsyncIterator + ?Sized,
+	T: for<'ctx> Task<Output<'ctx> = Output>
+{
+	select_many! {
+		item = iter.next() => NextOr::Next(item),
+		output = other => NextOr::Other(output)
+	}
+	.await
+}
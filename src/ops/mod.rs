@@ -1,24 +1,63 @@
 //! Fundamental async operations
 
+#[cfg(feature = "no-panic")]
+use xx_core::impls::ResultExt;
 use xx_core::pointer::*;
 
 use super::*;
 
+pub mod affinity;
 pub mod blocking;
 pub mod branch;
+pub mod fd_budget;
+pub mod heartbeat;
 pub mod io;
+pub mod iter;
+pub mod shutdown;
+pub mod sync;
 pub mod timers;
+pub mod waker;
 
 pub use xx_core::coroutines::{Join, JoinHandle, Select};
 #[doc(inline)]
-pub use {blocking::*, branch::*, timers::*};
+pub use {affinity::*, blocking::*, branch::*, fd_budget::*, iter::*, shutdown::*, timers::*};
+
+#[asynchronous]
+async fn internal_try_get_pulse_env<#[cx] 'current>() -> Result<&'current PulseContext> {
+	get_context()
+		.await
+		.get_environment::<PulseContext>()
+		.ok_or_else(|| WrongRuntime::not_pulse().into())
+}
+
+/// Unwrap an error that the caller cannot return. Panics, or aborts without
+/// unwinding with the `no-panic` feature
+#[cfg(not(feature = "no-panic"))]
+#[allow(clippy::expect_used)]
+#[track_caller]
+fn expect_or_abort<T>(result: Result<T>, msg: &str) -> T {
+	result.expect(msg)
+}
+
+#[cfg(feature = "no-panic")]
+fn expect_or_abort<T>(result: Result<T>, msg: &str) -> T {
+	result.expect_nounwind(msg)
+}
 
 #[asynchronous]
 async fn internal_get_pulse_env<#[cx] 'current>() -> &'current PulseContext {
-	let env = get_context().await.get_environment::<PulseContext>();
+	expect_or_abort(
+		internal_try_get_pulse_env().await,
+		"Cannot use xx-pulse functions with a different runtime"
+	)
+}
 
-	#[allow(clippy::expect_used)]
-	env.expect("Cannot use xx-pulse functions with a different runtime")
+#[asynchronous]
+async fn internal_try_get_driver<#[cx] 'current>() -> Result<&'current Driver> {
+	let env = internal_try_get_pulse_env().await?;
+
+	/* Safety: driver outlives context */
+	Ok(unsafe { env.driver.as_ref() })
 }
 
 #[asynchronous]
@@ -0,0 +1,258 @@
+//! Waiting for a service to be asked to shut down
+//!
+//! [`shutdown_signal`] completes on the first of `SIGINT` or `SIGTERM`
+//! arriving, a [`ShutdownToken`] being triggered, or the runtime exiting,
+//! and reports which one it was. [`shutdown_signals`] yields every signal
+//! as an [`AsyncIterator`], for services that handle more than one.
+//!
+//! # Examples
+//!
+//! ```
+//! let token = ShutdownToken::new();
+//!
+//! match select(serve(&listener), shutdown_signal(&token)).await {
+//! 	Select::First(result, _) => result?,
+//! 	Select::Second(reason, _) => println!("shutting down: {:?}", reason?)
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::mem::size_of;
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+use std::rc::Rc;
+
+use xx_core::os::syscall::*;
+
+use super::sync::WaitQueue;
+use super::*;
+
+const SIG_BLOCK: i32 = 0;
+const SFD_CLOEXEC: i32 = 0o2_000_000;
+
+/// The size of `struct signalfd_siginfo`
+const SIGINFO_LEN: usize = 128;
+
+/// A signal asking the process to shut down
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShutdownSignal {
+	/// `SIGINT`, such as from Ctrl-C
+	Interrupt = 2,
+
+	/// `SIGTERM`, such as from a service manager
+	Terminate = 15
+}
+
+impl ShutdownSignal {
+	const ALL: [Self; 2] = [Self::Interrupt, Self::Terminate];
+
+	fn from_number(number: u32) -> Option<Self> {
+		Self::ALL.into_iter().find(|signal| *signal as u32 == number)
+	}
+}
+
+/// Why [`shutdown_signal`] completed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShutdownReason {
+	/// The process received a signal
+	Signal(ShutdownSignal),
+
+	/// The [`ShutdownToken`] was triggered
+	Token,
+
+	/// The runtime is exiting
+	RuntimeExit
+}
+
+struct TokenState {
+	triggered: Cell<bool>,
+	waiters: WaitQueue
+}
+
+/// A token that asks every task waiting on it to shut down. Clones share the
+/// same state
+#[derive(Clone)]
+pub struct ShutdownToken {
+	state: Rc<TokenState>
+}
+
+#[asynchronous]
+impl ShutdownToken {
+	#[must_use]
+	pub fn new() -> Self {
+		Self {
+			state: Rc::new(TokenState { triggered: Cell::new(false), waiters: WaitQueue::new() })
+		}
+	}
+
+	/// Trigger the token, waking all waiting tasks. Triggering more than once
+	/// has no effect
+	pub fn trigger(&self) {
+		self.state.triggered.set(true);
+		self.state.waiters.wake_all();
+	}
+
+	#[must_use]
+	pub fn is_triggered(&self) -> bool {
+		self.state.triggered.get()
+	}
+
+	/// Wait until the token is triggered
+	///
+	/// # Errors
+	/// If the task was interrupted
+	pub async fn triggered(&self) -> Result<()> {
+		while !self.is_triggered() {
+			block_on(self.state.waiters.wait()).await?;
+		}
+
+		Ok(())
+	}
+}
+
+impl Default for ShutdownToken {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Block the shutdown signals on this thread, and create a signalfd that
+/// receives them instead
+pub(crate) fn signal_fd() -> Result<OwnedFd> {
+	#[allow(clippy::arithmetic_side_effects)]
+	let mask = ShutdownSignal::ALL
+		.iter()
+		.fold(0u64, |mask, signal| mask | (1 << (*signal as u32 - 1)));
+
+	/* Safety: mask is a valid sigset, and the old mask is not requested */
+	unsafe {
+		syscall_int!(RtSigProcMask, SIG_BLOCK, ptr!(&mask).as_ptr(), 0, size_of::<u64>())?
+	};
+
+	/* Safety: mask is a valid sigset */
+	let fd = unsafe {
+		syscall_int!(SignalFd4, -1, ptr!(&mask).as_ptr(), size_of::<u64>(), SFD_CLOEXEC)?
+	};
+
+	#[allow(clippy::cast_possible_truncation)]
+	/* Safety: the kernel gave us a new fd */
+	Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/// The signalfd shared by every caller on this runtime, created on first use
+#[asynchronous]
+async fn runtime_signal_fd() -> Result<Rc<OwnedFd>> {
+	let signals = internal_try_get_driver().await?.shutdown_fd();
+
+	if let Some(fd) = signals.get() {
+		return Ok(fd.clone());
+	}
+
+	let fd = Rc::new(signal_fd()?);
+	let _ = signals.set(fd.clone());
+
+	Ok(fd)
+}
+
+#[asynchronous]
+async fn wait_for_signal(fd: &OwnedFd) -> Result<ShutdownSignal> {
+	let mut info = [0u8; SIGINFO_LEN];
+
+	loop {
+		let read = io::read(fd.as_fd(), &mut info, -1).await?;
+
+		if read < SIGINFO_LEN {
+			return Err(fmt_error!("Short read from signalfd" @ ErrorKind::UnexpectedEof));
+		}
+
+		let number = u32::from_ne_bytes([info[0], info[1], info[2], info[3]]);
+
+		if let Some(signal) = ShutdownSignal::from_number(number) {
+			break Ok(signal);
+		}
+	}
+}
+
+/// Wait until the service should shut down: on `SIGINT` or `SIGTERM`, when
+/// `token` is triggered, or when the runtime exits, whichever comes first
+///
+/// The signals are received through a signalfd shared by every call on the
+/// runtime, and stay blocked after this function returns, so that a signal
+/// arriving between calls is not lost and does not kill the process. Each
+/// signal completes only one of the concurrent calls.
+///
+/// Use [`RuntimeBuilder::shutdown_signals`] to block the signals on every
+/// thread the runtime starts. Otherwise the first call blocks them on the
+/// calling thread only, and threads started before it, such as the runtime's
+/// thread pool, could still receive the signal.
+///
+/// [`RuntimeBuilder::shutdown_signals`]: crate::RuntimeBuilder::shutdown_signals
+///
+/// # Errors
+/// If the signals cannot be received through a signalfd
+#[asynchronous]
+pub async fn shutdown_signal(token: &ShutdownToken) -> Result<ShutdownReason> {
+	if token.is_triggered() {
+		return Ok(ShutdownReason::Token);
+	}
+
+	let fd = runtime_signal_fd().await?;
+
+	let result = select(wait_for_signal(&fd), token.triggered()).await;
+
+	match result {
+		Select::First(Ok(signal), _) => Ok(ShutdownReason::Signal(signal)),
+		Select::Second(Ok(()), _) => Ok(ShutdownReason::Token),
+		Select::First(Err(err), _) | Select::Second(Err(err), _) => {
+			/* the runtime interrupts its tasks when exiting */
+			if check_interrupt().await.is_err() {
+				Ok(ShutdownReason::RuntimeExit)
+			} else {
+				Err(err)
+			}
+		}
+	}
+}
+
+/// An iterator over the shutdown signals received by the process. See
+/// [`shutdown_signals`] for more information
+pub struct ShutdownSignals {
+	fd: Rc<OwnedFd>
+}
+
+#[asynchronous]
+impl AsyncIterator for ShutdownSignals {
+	type Item = Result<ShutdownSignal>;
+
+	/// Wait for the next signal. Never returns `None`
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	async fn next(&mut self) -> Option<Self::Item> {
+		Some(wait_for_signal(&self.fd).await)
+	}
+}
+
+/// Receive `SIGINT` and `SIGTERM` as an async iterator, instead of waiting
+/// for the first one with [`shutdown_signal`]. The signals are blocked in
+/// the same way, and share the same signalfd
+///
+/// # Examples
+///
+/// ```
+/// let mut signals = shutdown_signals().await?;
+///
+/// while let Some(signal) = signals.next().await {
+/// 	match signal? {
+/// 		ShutdownSignal::Interrupt => reload().await?,
+/// 		ShutdownSignal::Terminate => break
+/// 	}
+/// }
+/// ```
+///
+/// # Errors
+/// If the signals cannot be received through a signalfd
+#[asynchronous]
+pub async fn shutdown_signals() -> Result<ShutdownSignals> {
+	Ok(ShutdownSignals { fd: runtime_signal_fd().await? })
+}
@@ -0,0 +1,145 @@
+//! The implementation for [`Condvar`]
+
+use std::cell::Cell;
+
+use super::*;
+
+/// An async condition variable, used with a [`Mutex`] to wait for some state
+/// protected by the mutex to change
+///
+/// # Examples
+///
+/// ```
+/// let mut ready = mutex.lock().await?;
+///
+/// while !*ready {
+/// 	ready = condvar.wait(ready).await?;
+/// }
+/// ```
+pub struct Condvar {
+	waiters: WaitQueue,
+
+	/// Tasks which released their lock, but have not started waiting yet
+	registered: Cell<usize>,
+
+	/// Notifications for registered tasks
+	signals: Cell<usize>
+}
+
+impl Condvar {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			waiters: WaitQueue::new(),
+			registered: Cell::new(0),
+			signals: Cell::new(0)
+		}
+	}
+
+	#[future]
+	fn wait_signal(&self, request: _) -> Result<()> {
+		#[cancel]
+		fn cancel(&self) -> Result<()> {
+			self.waiters.cancel(request)
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		self.registered.set(self.registered.get() - 1);
+
+		if self.signals.get() != 0 {
+			#[allow(clippy::arithmetic_side_effects)]
+			self.signals.set(self.signals.get() - 1);
+
+			return Progress::Done(Ok(()));
+		}
+
+		self.waiters.push(request);
+
+		Progress::Pending(cancel(self))
+	}
+}
+
+#[asynchronous]
+impl Condvar {
+	/// Release the lock held by `guard` and wait for a notification, then
+	/// acquire the lock again
+	///
+	/// Spurious wakeups are possible, so the condition should be checked in a
+	/// loop. See [`Condvar::wait_while`]
+	///
+	/// # Errors
+	/// If the task was interrupted. The lock is not held when an error is
+	/// returned. A notification received just before the interrupt is passed
+	/// on to another waiter
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe, with the same caveat as errors.
+	pub async fn wait<'a, T: ?Sized>(
+		&self, guard: MutexGuard<'a, T>
+	) -> Result<MutexGuard<'a, T>> {
+		let mutex = MutexGuard::mutex(&guard);
+
+		/* register before unlocking, so that a notification sent as soon as the
+		 * lock is released is not lost
+		 */
+		#[allow(clippy::arithmetic_side_effects)]
+		self.registered.set(self.registered.get() + 1);
+
+		drop(guard);
+
+		block_on(self.wait_signal()).await?;
+
+		match mutex.lock().await {
+			Ok(guard) => Ok(guard),
+			Err(err) => {
+				self.notify_one();
+
+				Err(err)
+			}
+		}
+	}
+
+	/// Wait until `condition` returns `false`, checking it each time a
+	/// notification is received
+	///
+	/// # Errors
+	/// See [`Condvar::wait`]
+	pub async fn wait_while<'a, T, F>(
+		&self, mut guard: MutexGuard<'a, T>, mut condition: F
+	) -> Result<MutexGuard<'a, T>>
+	where
+		T: ?Sized,
+		F: FnMut(&mut T) -> bool
+	{
+		while condition(&mut guard) {
+			guard = self.wait(guard).await?;
+		}
+
+		Ok(guard)
+	}
+
+	/// Wake one waiting task, if any
+	pub fn notify_one(&self) {
+		if self.waiters.wake_one() {
+			return;
+		}
+
+		if self.registered.get() > self.signals.get() {
+			#[allow(clippy::arithmetic_side_effects)]
+			self.signals.set(self.signals.get() + 1);
+		}
+	}
+
+	/// Wake every waiting task
+	pub fn notify_all(&self) {
+		self.waiters.wake_all();
+		self.signals.set(self.registered.get());
+	}
+}
+
+impl Default for Condvar {
+	fn default() -> Self {
+		Self::new()
+	}
+}
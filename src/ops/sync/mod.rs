@@ -0,0 +1,13 @@
+//! Synchronization primitives for tasks on the same runtime
+
+use super::*;
+
+pub mod condvar;
+pub mod mutex;
+pub mod once_cell;
+pub mod rwlock;
+mod wait_queue;
+
+pub(crate) use self::wait_queue::*;
+#[doc(inline)]
+pub use {condvar::*, mutex::*, once_cell::*, rwlock::*};
@@ -0,0 +1,127 @@
+//! The implementation for [`Mutex`]
+
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use super::*;
+
+/// An async mutual exclusion lock for tasks on the same runtime
+///
+/// Unlike a blocking mutex, waiting for the lock suspends only the current
+/// task, so the lock may be held across `.await` points.
+pub struct Mutex<T: ?Sized> {
+	locked: Cell<bool>,
+	waiters: WaitQueue,
+	value: UnsafeCell<T>
+}
+
+impl<T> Mutex<T> {
+	#[must_use]
+	pub const fn new(value: T) -> Self {
+		Self {
+			locked: Cell::new(false),
+			waiters: WaitQueue::new(),
+			value: UnsafeCell::new(value)
+		}
+	}
+
+	#[must_use]
+	pub fn into_inner(self) -> T {
+		self.value.into_inner()
+	}
+}
+
+#[asynchronous]
+impl<T: ?Sized> Mutex<T> {
+	/// Acquire the lock, waiting for the current holder to release it
+	///
+	/// # Errors
+	/// If the task was interrupted while waiting
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe. The lock is not acquired if cancelled.
+	pub async fn lock(&self) -> Result<MutexGuard<'_, T>> {
+		loop {
+			if let Some(guard) = self.try_lock() {
+				return Ok(guard);
+			}
+
+			block_on(self.waiters.wait()).await?;
+		}
+	}
+
+	/// Acquire the lock if it is not held
+	#[must_use]
+	pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+		if self.locked.replace(true) {
+			None
+		} else {
+			Some(MutexGuard { mutex: self })
+		}
+	}
+
+	#[must_use]
+	pub fn is_locked(&self) -> bool {
+		self.locked.get()
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		self.value.get_mut()
+	}
+
+	fn unlock(&self) {
+		self.locked.set(false);
+		self.waiters.wake_one();
+	}
+}
+
+impl<T: Default> Default for Mutex<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
+
+impl<T: ?Sized> fmt::Debug for Mutex<T> {
+	fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt.debug_struct("Mutex")
+			.field("locked", &self.locked.get())
+			.finish_non_exhaustive()
+	}
+}
+
+/// Releases the [`Mutex`] when dropped
+pub struct MutexGuard<'a, T: ?Sized> {
+	mutex: &'a Mutex<T>
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+	/// The mutex this guard is for
+	#[must_use]
+	pub const fn mutex(this: &Self) -> &'a Mutex<T> {
+		this.mutex
+	}
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		/* Safety: we hold the lock */
+		unsafe { &*self.mutex.value.get() }
+	}
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		/* Safety: we hold the lock */
+		unsafe { &mut *self.mutex.value.get() }
+	}
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+	fn drop(&mut self) {
+		self.mutex.unlock();
+	}
+}
@@ -0,0 +1,166 @@
+//! The implementation for [`OnceCell`]
+
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::mem::forget;
+
+use xx_core::coroutines::ops::AsyncFnOnce;
+
+use super::*;
+
+/// A cell which is written to at most once, with an async initializer
+///
+/// When several tasks call [`OnceCell::get_or_init`] at the same time, only
+/// one initializer runs, and the other tasks wait for it to finish. If the
+/// initializer fails, the next waiting task runs its own initializer instead.
+///
+/// # Examples
+///
+/// ```
+/// let config = Rc::new(OnceCell::new());
+///
+/// /* in each task */
+/// let config = config.get_or_try_init(|| async move { load_config().await }).await?;
+/// ```
+pub struct OnceCell<T> {
+	value: UnsafeCell<Option<T>>,
+	initializing: Cell<bool>,
+	waiters: WaitQueue
+}
+
+/// Lets the next waiter initialize the cell if the initializer does not
+/// complete
+struct InitGuard<'a, T> {
+	cell: &'a OnceCell<T>
+}
+
+impl<T> Drop for InitGuard<'_, T> {
+	fn drop(&mut self) {
+		self.cell.initializing.set(false);
+		self.cell.waiters.wake_one();
+	}
+}
+
+impl<T> OnceCell<T> {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			value: UnsafeCell::new(None),
+			initializing: Cell::new(false),
+			waiters: WaitQueue::new()
+		}
+	}
+
+	/// Get the value, or `None` if the cell is not initialized
+	#[must_use]
+	pub fn get(&self) -> Option<&T> {
+		/* Safety: the value is never modified once set */
+		unsafe { &*self.value.get() }.as_ref()
+	}
+
+	pub fn get_mut(&mut self) -> Option<&mut T> {
+		self.value.get_mut().as_mut()
+	}
+
+	/// Set the value if the cell is not initialized and no initializer is
+	/// running. Otherwise, `value` is returned
+	///
+	/// # Errors
+	/// If the cell is initialized or being initialized
+	pub fn set(&self, value: T) -> std::result::Result<(), T> {
+		if self.initializing.get() || self.get().is_some() {
+			return Err(value);
+		}
+
+		self.store(value);
+
+		Ok(())
+	}
+
+	fn store(&self, value: T) -> &T {
+		/* Safety: the cell is not initialized, so there are no references to the
+		 * value
+		 */
+		let value = unsafe { &mut *self.value.get() }.insert(value);
+
+		self.waiters.wake_all();
+
+		value
+	}
+
+	pub fn take(&mut self) -> Option<T> {
+		self.value.get_mut().take()
+	}
+
+	pub fn into_inner(self) -> Option<T> {
+		self.value.into_inner()
+	}
+}
+
+#[asynchronous]
+impl<T> OnceCell<T> {
+	/// Get the value, initializing it with `init` if the cell is not
+	/// initialized. If another task is initializing the cell, waits for it to
+	/// finish instead
+	///
+	/// # Errors
+	/// If `init` fails, or if the task was interrupted while waiting. A failed
+	/// initializer lets the next waiting task run its initializer
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe if `init` is cancel safe.
+	pub async fn get_or_try_init<F>(&self, init: F) -> Result<&T>
+	where
+		F: AsyncFnOnce() -> Result<T>
+	{
+		loop {
+			if let Some(value) = self.get() {
+				return Ok(value);
+			}
+
+			if !self.initializing.replace(true) {
+				break;
+			}
+
+			block_on(self.waiters.wait()).await?;
+		}
+
+		let guard = InitGuard { cell: self };
+		let value = init.call_once(()).await?;
+
+		forget(guard);
+
+		self.initializing.set(false);
+
+		Ok(self.store(value))
+	}
+
+	/// Get the value, initializing it with `init` if the cell is not
+	/// initialized. See [`OnceCell::get_or_try_init`]
+	///
+	/// # Errors
+	/// If the task was interrupted while waiting for another initializer
+	pub async fn get_or_init<F>(&self, init: F) -> Result<&T>
+	where
+		F: AsyncFnOnce() -> T
+	{
+		self.get_or_try_init(|| async move { Ok(init.call_once(()).await) })
+			.await
+	}
+}
+
+impl<T> Default for OnceCell<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+	fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt.debug_struct("OnceCell")
+			.field("value", &self.get())
+			.field("initializing", &self.initializing.get())
+			.finish_non_exhaustive()
+	}
+}
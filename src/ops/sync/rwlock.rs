@@ -0,0 +1,291 @@
+//! The implementation for [`RwLock`]
+
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::mem::forget;
+use std::ops::{Deref, DerefMut};
+
+use super::*;
+
+/// An async reader-writer lock for tasks on the same runtime, allowing any
+/// number of readers or a single writer at a time
+///
+/// Acquiring the lock when it is uncontended only updates a counter, without
+/// queueing the task. A write guard can be downgraded to a read guard without
+/// releasing the lock, with [`RwLockWriteGuard::downgrade`].
+///
+/// By default, writers are preferred: once a writer is waiting, new readers
+/// wait behind it, so that a steady stream of readers cannot starve writers.
+/// See [`RwLock::set_prefer_writers`]
+pub struct RwLock<T: ?Sized> {
+	readers: Cell<usize>,
+	writer: Cell<bool>,
+	prefer_writers: Cell<bool>,
+
+	/// Writers waiting for the lock, including ones that were woken but have
+	/// not acquired it yet
+	waiting_writers: Cell<usize>,
+	read_waiters: WaitQueue,
+	write_waiters: WaitQueue,
+	value: UnsafeCell<T>
+}
+
+impl<T> RwLock<T> {
+	#[must_use]
+	pub const fn new(value: T) -> Self {
+		Self {
+			readers: Cell::new(0),
+			writer: Cell::new(false),
+			prefer_writers: Cell::new(true),
+			waiting_writers: Cell::new(0),
+			read_waiters: WaitQueue::new(),
+			write_waiters: WaitQueue::new(),
+			value: UnsafeCell::new(value)
+		}
+	}
+
+	#[must_use]
+	pub fn into_inner(self) -> T {
+		self.value.into_inner()
+	}
+}
+
+#[asynchronous]
+impl<T: ?Sized> RwLock<T> {
+	/// Acquire the lock for reading, waiting for the writer to release it
+	///
+	/// # Errors
+	/// If the task was interrupted while waiting
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe. The lock is not acquired if cancelled.
+	pub async fn read(&self) -> Result<RwLockReadGuard<'_, T>> {
+		loop {
+			if let Some(guard) = self.try_read() {
+				return Ok(guard);
+			}
+
+			block_on(self.read_waiters.wait()).await?;
+		}
+	}
+
+	/// Acquire the lock for writing, waiting for all readers and the writer
+	/// to release it
+	///
+	/// # Errors
+	/// If the task was interrupted while waiting
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe. The lock is not acquired if cancelled.
+	pub async fn write(&self) -> Result<RwLockWriteGuard<'_, T>> {
+		loop {
+			if let Some(guard) = self.try_write() {
+				return Ok(guard);
+			}
+
+			#[allow(clippy::arithmetic_side_effects)]
+			self.waiting_writers.set(self.waiting_writers.get() + 1);
+
+			let result = block_on(self.write_waiters.wait()).await;
+
+			#[allow(clippy::arithmetic_side_effects)]
+			self.waiting_writers.set(self.waiting_writers.get() - 1);
+
+			if result.is_err() {
+				/* readers may have been waiting behind this writer */
+				self.wake_readers();
+			}
+
+			result?;
+		}
+	}
+
+	fn readers_blocked(&self) -> bool {
+		self.writer.get() || (self.prefer_writers.get() && self.waiting_writers.get() != 0)
+	}
+
+	/// Acquire the lock for reading if there is no writer, or a waiting writer
+	/// that takes precedence
+	#[must_use]
+	pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+		if self.readers_blocked() {
+			return None;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		self.readers.set(self.readers.get() + 1);
+
+		Some(RwLockReadGuard { lock: self })
+	}
+
+	/// Acquire the lock for writing if it is not held
+	#[must_use]
+	pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+		if self.writer.get() || self.readers.get() != 0 {
+			return None;
+		}
+
+		self.writer.set(true);
+
+		Some(RwLockWriteGuard { lock: self })
+	}
+
+	/// Set whether waiting writers take precedence over new readers. When
+	/// disabled, readers acquire the lock whenever no writer holds it, which
+	/// can starve writers
+	pub fn set_prefer_writers(&self, prefer: bool) {
+		self.prefer_writers.set(prefer);
+		self.wake_readers();
+	}
+
+	#[must_use]
+	pub fn prefers_writers(&self) -> bool {
+		self.prefer_writers.get()
+	}
+
+	/// The number of read guards currently held
+	#[must_use]
+	pub fn reader_count(&self) -> usize {
+		self.readers.get()
+	}
+
+	#[must_use]
+	pub fn is_write_locked(&self) -> bool {
+		self.writer.get()
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		self.value.get_mut()
+	}
+
+	fn wake_readers(&self) {
+		if !self.readers_blocked() {
+			self.read_waiters.wake_all();
+		}
+	}
+
+	fn unlock_read(&self) {
+		#[allow(clippy::arithmetic_side_effects)]
+		let readers = self.readers.get() - 1;
+
+		self.readers.set(readers);
+
+		if readers == 0 {
+			self.write_waiters.wake_one();
+		}
+	}
+
+	fn unlock_write(&self) {
+		self.writer.set(false);
+
+		if self.prefer_writers.get() && self.write_waiters.wake_one() {
+			return;
+		}
+
+		if self.read_waiters.is_empty() {
+			self.write_waiters.wake_one();
+		} else {
+			self.read_waiters.wake_all();
+		}
+	}
+
+	fn downgrade(&self) {
+		self.writer.set(false);
+		self.readers.set(1);
+		self.wake_readers();
+	}
+}
+
+impl<T: Default> Default for RwLock<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
+
+impl<T: ?Sized> fmt::Debug for RwLock<T> {
+	fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt.debug_struct("RwLock")
+			.field("readers", &self.readers.get())
+			.field("writer", &self.writer.get())
+			.finish_non_exhaustive()
+	}
+}
+
+/// Releases a read lock on the [`RwLock`] when dropped
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+	lock: &'a RwLock<T>
+}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+	/// The lock this guard is for
+	#[must_use]
+	pub const fn lock(this: &Self) -> &'a RwLock<T> {
+		this.lock
+	}
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		/* Safety: we hold a read lock */
+		unsafe { &*self.lock.value.get() }
+	}
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+	fn drop(&mut self) {
+		self.lock.unlock_read();
+	}
+}
+
+/// Releases the write lock on the [`RwLock`] when dropped
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+	lock: &'a RwLock<T>
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+	/// The lock this guard is for
+	#[must_use]
+	pub const fn lock(this: &Self) -> &'a RwLock<T> {
+		this.lock
+	}
+
+	/// Atomically turn the write lock into a read lock, so that no writer can
+	/// acquire the lock in between. Waiting readers are woken
+	#[must_use]
+	pub fn downgrade(this: Self) -> RwLockReadGuard<'a, T> {
+		let lock = this.lock;
+
+		/* the write lock is transferred to the read guard */
+		forget(this);
+
+		lock.downgrade();
+
+		RwLockReadGuard { lock }
+	}
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		/* Safety: we hold the write lock */
+		unsafe { &*self.lock.value.get() }
+	}
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		/* Safety: we hold the write lock */
+		unsafe { &mut *self.lock.value.get() }
+	}
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+	fn drop(&mut self) {
+		self.lock.unlock_write();
+	}
+}
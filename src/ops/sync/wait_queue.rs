@@ -0,0 +1,91 @@
+#![allow(unreachable_pub)]
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::mem::take;
+
+use super::*;
+
+type Waiter = ReqPtr<Result<()>>;
+
+fn cancelled() -> Error {
+	fmt_error!("Wait cancelled" @ ErrorKind::Interrupted)
+}
+
+/// A queue of tasks waiting to be woken, in the order they started waiting
+pub struct WaitQueue {
+	waiters: RefCell<VecDeque<Waiter>>
+}
+
+impl WaitQueue {
+	pub const fn new() -> Self {
+		Self { waiters: RefCell::new(VecDeque::new()) }
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.waiters.borrow().is_empty()
+	}
+
+	pub fn push(&self, request: Waiter) {
+		self.waiters.borrow_mut().push_back(request);
+	}
+
+	/// Remove `request` from the queue, completing it with an error. Fails if
+	/// the request was already woken
+	pub fn cancel(&self, request: Waiter) -> Result<()> {
+		let mut waiters = self.waiters.borrow_mut();
+		let index = match waiters.iter().position(|waiter| *waiter == request) {
+			Some(index) => index,
+			None => return Err(fmt_error!("Waiter not found" @ ErrorKind::NotFound))
+		};
+
+		waiters.remove(index);
+
+		/* the completion may queue more waiters */
+		drop(waiters);
+
+		/* Safety: complete the future */
+		unsafe { Request::complete(request, Err(cancelled())) };
+
+		Ok(())
+	}
+
+	#[future]
+	pub fn wait(&self, request: _) -> Result<()> {
+		#[cancel]
+		fn cancel(&self) -> Result<()> {
+			self.cancel(request)
+		}
+
+		self.push(request);
+
+		Progress::Pending(cancel(self))
+	}
+
+	/// Wake the longest waiting task. Returns `false` if there were no waiters
+	pub fn wake_one(&self) -> bool {
+		let waiter = self.waiters.borrow_mut().pop_front();
+
+		match waiter {
+			Some(waiter) => {
+				/* Safety: complete the future */
+				unsafe { Request::complete(waiter, Ok(())) };
+
+				true
+			}
+
+			None => false
+		}
+	}
+
+	/// Wake every task currently waiting. Tasks which start waiting while
+	/// others are woken are not woken
+	pub fn wake_all(&self) {
+		let waiters = take(&mut *self.waiters.borrow_mut());
+
+		for waiter in waiters {
+			/* Safety: complete the future */
+			unsafe { Request::complete(waiter, Ok(())) };
+		}
+	}
+}
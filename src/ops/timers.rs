@@ -1,5 +1,8 @@
 //! Timers and sleeping
 
+use std::rc::Weak;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use xx_core::os::time::{self, ClockId};
 
 use super::*;
@@ -20,20 +23,126 @@ pub enum TimeoutFlag {
 /// for more information.
 #[asynchronous]
 pub async fn timeout(expire: u64, flags: BitFlags<TimeoutFlag>) -> Result<()> {
-	let driver = internal_get_driver().await;
+	let driver = internal_try_get_driver().await?;
 
 	check_interrupt().await?;
-	block_on(driver.timeout(expire, flags)).await
+	block_on(driver.timeout(expire, flags, 0)).await
 }
 
-/// Suspends the current async task for the specified duration.
-///
-/// # Panics
-/// If the duration in nanoseconds is greater than `u64::MAX` (~585 years).
+/// Suspends the current async task for the specified duration. Durations
+/// longer than `u64::MAX` nanoseconds (~585 years) sleep forever.
 #[asynchronous]
-#[allow(clippy::unwrap_used)]
 pub async fn sleep(duration: Duration) -> Result<()> {
-	timeout(duration.as_nanos().try_into().unwrap(), BitFlags::default()).await
+	timeout(
+		duration.as_nanos().try_into().unwrap_or(u64::MAX),
+		BitFlags::default()
+	)
+	.await
+}
+
+/// A tag for timers registered by a connection or task, so that they can all
+/// be cancelled at once when it dies
+///
+/// Pending timers registered through an owner fail with
+/// [`ErrorKind::Interrupted`] when [`TimerOwner::cancel_all`] is called or the
+/// owner is dropped, instead of staying queued until they expire
+///
+/// An owner belongs to the runtime it was created on, and registering timers
+/// through it from another runtime fails. See [`RuntimeTag`]. Once that
+/// runtime is dropped, the owner has no timers left to cancel. Use
+/// [`TimerOwner::rebind`] to move it to another runtime
+pub struct TimerOwner {
+	id: u64,
+	tag: RuntimeTag,
+	driver: Ptr<Driver>,
+	alive: Weak<()>
+}
+
+#[asynchronous]
+impl TimerOwner {
+	/// Create a new owner with a unique id
+	pub async fn new() -> Self {
+		static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+		let driver = internal_get_driver().await;
+
+		Self {
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+			tag: RuntimeTag::current().await,
+			driver: ptr!(driver),
+			alive: driver.alive()
+		}
+	}
+
+	/// The unique id of this owner
+	#[must_use]
+	pub const fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// The runtime this owner belongs to
+	#[must_use]
+	pub const fn tag(&self) -> RuntimeTag {
+		self.tag
+	}
+
+	/// Move the owner to the current runtime. Timers are only registered
+	/// while borrowing the owner, so none are pending on the previous runtime
+	pub async fn rebind(&mut self) {
+		let driver = internal_get_driver().await;
+
+		self.tag.rebind().await;
+		self.driver = ptr!(driver);
+		self.alive = driver.alive();
+	}
+
+	/// Like [`timeout`], but the timer is registered to this owner
+	///
+	/// # Errors
+	/// If the owner was created on a different runtime. See
+	/// [`RuntimeTag::check`]
+	pub async fn timeout(&self, expire: u64, flags: BitFlags<TimeoutFlag>) -> Result<()> {
+		let driver = internal_try_get_driver().await?;
+
+		self.tag.check().await?;
+		check_interrupt().await?;
+		block_on(driver.timeout(expire, flags, self.id)).await
+	}
+
+	/// Like [`sleep`], but the timer is registered to this owner
+	pub async fn sleep(&self, duration: Duration) -> Result<()> {
+		self.timeout(
+			duration.as_nanos().try_into().unwrap_or(u64::MAX),
+			BitFlags::default()
+		)
+		.await
+	}
+
+	/// Cancel all pending timers registered to this owner, returning the
+	/// number of timers cancelled
+	pub fn cancel_all(&self) -> usize {
+		if self.alive.strong_count() == 0 {
+			return 0;
+		}
+
+		/* Safety: the driver is still alive, and `Weak` keeps the owner on the
+		 * driver's thread
+		 */
+		unsafe { self.driver.as_ref() }.cancel_owned_timers(self.id)
+	}
+}
+
+impl Drop for TimerOwner {
+	fn drop(&mut self) {
+		self.cancel_all();
+	}
+}
+
+/// Get the pending timers and the next timer deadline for the current runtime.
+/// See [`TimerStats`]
+#[asynchronous]
+pub async fn timer_stats() -> TimerStats {
+	internal_get_driver().await.timer_stats()
 }
 
 /// Yield execution of the current async task
@@ -42,10 +151,54 @@ pub async fn yield_now() {
 	let _ = sleep(Duration::ZERO).await;
 }
 
+/// Call `f` on each item of `iter`, consuming task budget for every item and
+/// yielding to other tasks whenever the budget runs out. Use this for CPU bound
+/// loops, such as parsing a large buffer, so that they do not starve other
+/// tasks on the runtime.
+///
+/// Returns the number of times the task yielded.
+///
+/// # Errors
+/// If the task is interrupted while yielding. Items after the interruption are
+/// not processed
+#[asynchronous]
+pub async fn for_each_budgeted<I, F>(iter: I, mut f: F) -> Result<u64>
+where
+	I: IntoIterator,
+	F: FnMut(I::Item)
+{
+	let mut yields = 0u64;
+
+	for item in iter {
+		if !acquire_budget(None).await {
+			yield_now().await;
+			check_interrupt().await?;
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(yields += 1);
+		}
+
+		f(item);
+	}
+
+	Ok(yields)
+}
+
 /// The clock source for the runtime. This is the [`ClockId::Monotonic`] clock.
 /// Returns a time in nanoseconds.
-#[allow(clippy::missing_panics_doc, clippy::expect_used)]
+///
+/// # Panics
+/// If the clock cannot be read. With the `no-panic` feature, the process
+/// aborts instead. See [`try_nanotime`] for a fallible version
 #[must_use]
 pub fn nanotime() -> u64 {
-	time::nanotime(ClockId::Monotonic).expect("Failed to read the clock")
+	expect_or_abort(try_nanotime(), "Failed to read the clock")
+}
+
+/// Like [`nanotime`], but returns an error if the clock cannot be read
+///
+/// # Errors
+/// If reading the clock failed
+pub fn try_nanotime() -> Result<u64> {
+	time::nanotime(ClockId::Monotonic)
 }
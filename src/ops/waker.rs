@@ -0,0 +1,193 @@
+//! Interop with [`std::task::Waker`]
+//!
+//! A [`StdWaker`] creates std wakers that resume the task that created it
+//! when woken from any thread. This allows polling small hand written
+//! [`std::future::Future`] state machines inside a task, without a full
+//! compatibility layer.
+//!
+//! # Examples
+//!
+//! ```
+//! let output = poll_std(receiver).await?;
+//! ```
+
+use std::future::Future as StdFuture;
+use std::mem::take;
+use std::pin::pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context as StdContext, Poll, Wake, Waker as RawStdWaker};
+
+use super::*;
+
+#[derive(Default)]
+struct State {
+	woken: bool,
+	request: Option<ReqPtr<()>>
+}
+
+struct Shared {
+	driver: Ptr<Driver>,
+	state: Mutex<State>
+}
+
+/* Safety: the driver is only used to wake the waiting task, which is thread
+ * safe. the request is only accessed with the lock held
+ */
+unsafe impl Send for Shared {}
+
+/* Safety: see above */
+unsafe impl Sync for Shared {}
+
+impl Shared {
+	fn state(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// # Safety
+	/// `request` must have been prepared with [`Driver::prepare_wake`]
+	unsafe fn complete(&self, request: ReqPtr<()>) {
+		/* Safety: the request is pending, so the task and its driver are alive */
+		let driver = unsafe { self.driver.as_ref() };
+
+		/* Safety: guaranteed by caller */
+		unsafe { driver.wake(request) };
+	}
+
+	#[future]
+	fn wait(&self, request: _) -> Result<()> {
+		#[cancel]
+		fn cancel(&self) -> Result<()> {
+			let waiting = self.state().request.take();
+
+			/* if the request was taken, a wake is already in flight */
+			if let Some(request) = waiting {
+				/* Safety: the request was prepared in `wait` */
+				unsafe { self.complete(request) };
+			}
+
+			Ok(())
+		}
+
+		let mut state = self.state();
+
+		if take(&mut state.woken) {
+			return Progress::Done(Ok(()));
+		}
+
+		/* Safety: the driver is alive while the task runs */
+		unsafe { self.driver.as_ref() }.prepare_wake();
+
+		state.request = Some(request);
+
+		Progress::Pending(cancel(self))
+	}
+}
+
+impl Wake for Shared {
+	fn wake(self: Arc<Self>) {
+		self.wake_by_ref();
+	}
+
+	fn wake_by_ref(self: &Arc<Self>) {
+		let mut state = self.state();
+		let waiting = state.request.take();
+
+		match waiting {
+			/* Safety: the request was prepared in `wait` */
+			Some(request) => unsafe { self.complete(request) },
+			None => state.woken = true
+		}
+	}
+}
+
+/// A source of [`std::task::Waker`]s that resume the task that created it
+///
+/// Wakes that happen while the task is not waiting are remembered, so the
+/// next call to [`StdWaker::wait`] returns immediately
+pub struct StdWaker {
+	shared: Arc<Shared>,
+	tag: RuntimeTag
+}
+
+#[asynchronous]
+impl StdWaker {
+	/// Create a waker for the current task
+	pub async fn new() -> Self {
+		let driver = ptr!(internal_get_driver().await);
+
+		Self {
+			shared: Arc::new(Shared { driver, state: Mutex::new(State::default()) }),
+			tag: RuntimeTag::current().await
+		}
+	}
+
+	/// The runtime this waker belongs to
+	#[must_use]
+	pub const fn tag(&self) -> RuntimeTag {
+		self.tag
+	}
+
+	/// Move the waker to the current task and runtime. A wake that happened
+	/// before the move is kept
+	///
+	/// Std wakers obtained before the move still refer to the previous
+	/// runtime, and no longer resume the task. Get new ones with
+	/// [`StdWaker::waker`]
+	pub async fn rebind(&mut self) {
+		let woken = self.shared.state().woken;
+		let driver = ptr!(internal_get_driver().await);
+		let state = State { woken, request: None };
+
+		self.shared = Arc::new(Shared { driver, state: Mutex::new(state) });
+		self.tag.rebind().await;
+	}
+
+	/// Get a std waker, which may be sent to and woken from any thread
+	#[must_use]
+	pub fn waker(&self) -> RawStdWaker {
+		RawStdWaker::from(self.shared.clone())
+	}
+
+	/// Suspend the current task until one of the wakers is woken. Returns
+	/// immediately if a waker was woken since the last call
+	///
+	/// # Errors
+	/// If the waker was created on a different runtime, whose driver would
+	/// resume the task. See [`RuntimeTag::check`]
+	///
+	/// # Cancel safety
+	///
+	/// This function is cancel safe.
+	pub async fn wait(&self) -> Result<()> {
+		self.tag.check().await?;
+		check_interrupt().await?;
+
+		block_on(self.shared.wait()).await?;
+
+		check_interrupt().await
+	}
+}
+
+/// Run the std `future` to completion in the current task. The task is
+/// suspended while the future is pending, and resumed when the future's waker
+/// is woken, from any thread
+///
+/// # Cancel safety
+///
+/// This function is cancel safe if the future is cancel safe. The future is
+/// dropped if the task is interrupted.
+#[asynchronous]
+pub async fn poll_std<F: StdFuture>(future: F) -> Result<F::Output> {
+	let waker = StdWaker::new().await;
+	let std_waker = waker.waker();
+	let mut context = StdContext::from_waker(&std_waker);
+	let mut future = pin!(future);
+
+	loop {
+		if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+			return Ok(output);
+		}
+
+		waker.wait().await?;
+	}
+}
@@ -0,0 +1,23 @@
+//! The xx-pulse prelude
+//!
+//! Imports the traits and types needed by most programs in a single `use`,
+//! without reaching into `xx_core`. The paths here stay the same when the
+//! internal layout of `xx_core` changes.
+//!
+//! ```
+//! use xx_pulse::prelude::*;
+//! ```
+
+pub use std::time::Duration;
+
+pub use enumflags2::BitFlags;
+pub use xx_core::error::{Error, ErrorKind, Result};
+
+pub use crate::fs::File;
+pub use crate::impls::{AsyncIteratorExt, ReadUninitExt, TaskExt};
+pub use crate::io::{Read, ReadExt, ReadUninit, Seek, SeekExt, Write, WriteExt};
+pub use crate::net::{DatagramSocket, StreamSocket, Tcp, TcpListener, Udp};
+pub use crate::{
+	asynchronous, join, select, sleep, spawn, AsyncIterator, Interval, Join, JoinHandle, Runtime,
+	Select
+};
@@ -1,14 +1,23 @@
 #![allow(unreachable_pub)]
 
 use std::cell::Cell;
+use std::env::{self, VarError};
+use std::fmt::Write;
+use std::mem::ManuallyDrop;
+use std::panic::Location;
+use std::process::abort;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use xx_core::container::intrusive::linked_list::*;
-use xx_core::debug;
 use xx_core::fiber::*;
 use xx_core::pointer::*;
 use xx_core::runtime::join;
+use xx_core::{debug, error};
 
 use super::*;
+use crate::metrics::{Registration, RuntimeMetrics};
 
 pub struct PulseContext {
 	pub(crate) context: Context,
@@ -64,6 +73,7 @@ unsafe impl Environment for PulseContext {
 
 pub struct PulseWorker {
 	pub(crate) context: Ptr<Context>,
+	pub(crate) location: Option<&'static Location<'static>>,
 	pub(crate) node: Node
 }
 
@@ -71,9 +81,10 @@ impl PulseWorker {
 	/// # Safety
 	/// must append to list before dropping
 	#[asynchronous]
-	pub async unsafe fn new() -> Self {
+	pub async unsafe fn new(location: Option<&'static Location<'static>>) -> Self {
 		Self {
 			context: get_context().await.into(),
+			location,
 			node: Node::new()
 		}
 	}
@@ -86,18 +97,329 @@ impl Drop for PulseWorker {
 	}
 }
 
-/// The runtime for xx-pulse
-pub struct Runtime {
-	driver: Driver,
-	executor: Executor,
-	workers: LinkedList,
-	pool: Pool
+/// What to do when dropping a [`Runtime`] takes longer than the configured
+/// budget. See [`RuntimeBuilder::drop_timeout`]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum DropPolicy {
+	/// Keep interrupting the remaining tasks until they exit
+	#[default]
+	Wait,
+
+	/// Abort the process
+	Abort,
+
+	/// Stop waiting and leak the runtime, along with every task still running
+	/// on it. The tasks are never resumed
+	Leak
 }
 
-impl Runtime {
-	pub fn new() -> Result<Pinned<Box<Self>>> {
-		let runtime = Self {
-			driver: Driver::new()?,
+/// Subsystems whose trace output can be turned on or off at runtime. See
+/// [`RuntimeBuilder::trace_subsystems`]
+#[bitflags]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum TraceSubsystem {
+	/// Submissions, completions, and wakes in the I/O engine
+	Engine = 1 << 0,
+
+	/// Timers
+	Driver = 1 << 1,
+
+	/// Asynchronous I/O operations
+	Io     = 1 << 2,
+
+	/// Non blocking socket I/O
+	Net    = 1 << 3
+}
+
+static TRACE_SUBSYSTEMS: AtomicU32 = AtomicU32::new(u32::MAX);
+
+#[inline(always)]
+pub fn trace_enabled(subsystem: TraceSubsystem) -> bool {
+	(TRACE_SUBSYSTEMS.load(Ordering::Relaxed) & subsystem as u32) != 0
+}
+
+fn env_var<T: FromStr>(name: &str) -> Result<Option<T>> {
+	match env::var(name) {
+		Ok(value) => match value.trim().parse() {
+			Ok(value) => Ok(Some(value)),
+			Err(_) => Err(fmt_error!("Invalid value {:?} for {}", value, name))
+		},
+
+		Err(VarError::NotPresent) => Ok(None),
+		Err(VarError::NotUnicode(_)) => Err(fmt_error!("{} is not valid unicode", name))
+	}
+}
+
+fn parse_trace_subsystems(value: &str) -> Result<BitFlags<TraceSubsystem>> {
+	let mut subsystems = BitFlags::default();
+
+	for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+		subsystems |= match name.to_ascii_lowercase().as_str() {
+			"all" => BitFlags::all(),
+			"none" => BitFlags::default(),
+			"engine" => TraceSubsystem::Engine.into(),
+			"driver" => TraceSubsystem::Driver.into(),
+			"io" => TraceSubsystem::Io.into(),
+			"net" => TraceSubsystem::Net.into(),
+			_ => return Err(fmt_error!("Unknown trace subsystem {:?}", name))
+		};
+	}
+
+	Ok(subsystems)
+}
+
+/// A builder for a [`Runtime`]
+#[derive(Clone, Copy, Debug)]
+pub struct RuntimeBuilder {
+	drop_timeout: Option<Duration>,
+	drop_iterations: Option<usize>,
+	drop_policy: DropPolicy,
+	trace: Option<BitFlags<TraceSubsystem>>,
+	engine: EngineOptions,
+	registry_name: Option<&'static str>,
+	fd_budget: Option<usize>,
+	shutdown_signals: bool
+}
+
+impl RuntimeBuilder {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			drop_timeout: None,
+			drop_iterations: None,
+			drop_policy: DropPolicy::Wait,
+			trace: None,
+			engine: EngineOptions::new(),
+			registry_name: None,
+			fd_budget: None,
+			shutdown_signals: false
+		}
+	}
+
+	/// Create a builder configured by the `XX_PULSE_*` environment variables,
+	/// so that deployed binaries can be tuned without recompiling. Unset
+	/// variables keep their defaults
+	///
+	/// | Variable | Value |
+	/// | - | - |
+	/// | `XX_PULSE_ENGINE` | The I/O engine. Only `io_uring` is supported |
+	/// | `XX_PULSE_SQ_ENTRIES` | See [`RuntimeBuilder::sq_entries`] |
+	/// | `XX_PULSE_CQ_ENTRIES` | See [`RuntimeBuilder::cq_entries`] |
+	/// | `XX_PULSE_THREADS` | See [`RuntimeBuilder::thread_pool_size`] |
+	/// | `XX_PULSE_TASK_RUN` | `batched` or `every_enter`. See [`TaskRunPolicy`] |
+	/// | `XX_PULSE_SPIN_US` | See [`RuntimeBuilder::spin_before_park`] |
+	/// | `XX_PULSE_FD_BUDGET` | See [`RuntimeBuilder::fd_budget`] |
+	/// | `XX_PULSE_TRACE` | A comma separated list of [`TraceSubsystem`]s, `all`, or `none` |
+	///
+	/// # Errors
+	/// If a variable has an invalid value
+	pub fn from_env() -> Result<Self> {
+		let mut builder = Self::new();
+
+		if let Some(engine) = env_var::<String>("XX_PULSE_ENGINE")? {
+			match engine.as_str() {
+				"io_uring" | "uring" => (),
+				_ => return Err(fmt_error!("Unsupported engine {:?}", engine))
+			}
+		}
+
+		if let Some(entries) = env_var("XX_PULSE_SQ_ENTRIES")? {
+			builder = builder.sq_entries(entries);
+		}
+
+		if let Some(entries) = env_var("XX_PULSE_CQ_ENTRIES")? {
+			builder = builder.cq_entries(entries);
+		}
+
+		if let Some(threads) = env_var("XX_PULSE_THREADS")? {
+			builder = builder.thread_pool_size(threads);
+		}
+
+		if let Some(policy) = env_var::<String>("XX_PULSE_TASK_RUN")? {
+			builder = builder.task_run_policy(match policy.as_str() {
+				"batched" => TaskRunPolicy::Batched,
+				"every_enter" => TaskRunPolicy::EveryEnter,
+				_ => return Err(fmt_error!("Invalid task run policy {:?}", policy))
+			});
+		}
+
+		if let Some(spin) = env_var("XX_PULSE_SPIN_US")? {
+			builder = builder.spin_before_park(Duration::from_micros(spin));
+		}
+
+		if let Some(limit) = env_var("XX_PULSE_FD_BUDGET")? {
+			builder = builder.fd_budget(limit);
+		}
+
+		if let Some(trace) = env_var::<String>("XX_PULSE_TRACE")? {
+			builder = builder.trace_subsystems(parse_trace_subsystems(&trace)?);
+		}
+
+		Ok(builder)
+	}
+
+	/// The maximum amount of time to spend interrupting tasks when the
+	/// runtime is dropped, before applying the [`DropPolicy`]
+	#[must_use]
+	pub const fn drop_timeout(mut self, timeout: Duration) -> Self {
+		self.drop_timeout = Some(timeout);
+		self
+	}
+
+	/// The maximum number of times to interrupt the remaining tasks when the
+	/// runtime is dropped, before applying the [`DropPolicy`]
+	#[must_use]
+	pub const fn drop_iterations(mut self, iterations: usize) -> Self {
+		self.drop_iterations = Some(iterations);
+		self
+	}
+
+	/// What to do with tasks that do not exit within the drop budget. Has no
+	/// effect unless a timeout or iteration limit is set
+	#[must_use]
+	pub const fn drop_policy(mut self, policy: DropPolicy) -> Self {
+		self.drop_policy = policy;
+		self
+	}
+
+	/// Force disable the engine `features`, even if the kernel supports them.
+	/// Used to exercise the fallback paths for older kernels
+	#[must_use]
+	pub const fn disable_features(mut self, features: BitFlags<EngineFeature>) -> Self {
+		self.engine.disabled = features;
+		self
+	}
+
+	/// The number of entries in the submission queue. The kernel rounds it up
+	/// to a power of two
+	#[must_use]
+	pub const fn sq_entries(mut self, entries: u32) -> Self {
+		self.engine.sq_entries = entries;
+		self
+	}
+
+	/// The number of entries in the completion queue. Ignored on kernels that
+	/// cannot size the completion queue independently
+	#[must_use]
+	pub const fn cq_entries(mut self, entries: u32) -> Self {
+		self.engine.cq_entries = entries;
+		self
+	}
+
+	/// The number of threads used to run blocking operations
+	#[must_use]
+	pub const fn thread_pool_size(mut self, threads: usize) -> Self {
+		self.engine.threads = Some(threads);
+		self
+	}
+
+	/// When the engine runs deferred task work. See [`TaskRunPolicy`]
+	#[must_use]
+	pub const fn task_run_policy(mut self, policy: TaskRunPolicy) -> Self {
+		self.engine.task_run = policy;
+		self
+	}
+
+	/// Do not log warnings about missing kernel support when building the
+	/// runtime. Each warning is only logged once per process regardless. Use
+	/// [`Runtime::supports`] and [`Runtime::is_compatibility_mode`] to query
+	/// the support instead
+	#[must_use]
+	pub const fn quiet(mut self, quiet: bool) -> Self {
+		self.engine.quiet = quiet;
+		self
+	}
+
+	/// Poll for completions for up to `max` before waiting in the kernel,
+	/// which cuts wake up latency under bursty load at the cost of CPU time.
+	/// The time spent spinning adapts to how often spinning finds completions.
+	/// Disabled by default
+	#[must_use]
+	#[allow(clippy::cast_possible_truncation)]
+	pub const fn spin_before_park(mut self, max: Duration) -> Self {
+		self.engine.spin = max.as_nanos() as u64;
+		self
+	}
+
+	/// Make opening files and sockets, and accepting connections, wait once
+	/// `limit` descriptors opened through this runtime are open, instead of
+	/// failing when the process runs out. Set `limit` below the process limit
+	/// to leave a reserve for the runtime's own file descriptors. Disabled by
+	/// default. See [`fd_budget`](crate::fd_budget)
+	#[must_use]
+	pub const fn fd_budget(mut self, limit: usize) -> Self {
+		self.fd_budget = Some(limit);
+		self
+	}
+
+	/// The subsystems to emit trace output for. All subsystems are enabled by
+	/// default. This setting is global, and applies to every runtime once this
+	/// one is built. Building a runtime without setting it leaves the current
+	/// setting unchanged
+	#[must_use]
+	pub const fn trace_subsystems(mut self, subsystems: BitFlags<TraceSubsystem>) -> Self {
+		self.trace = Some(subsystems);
+		self
+	}
+
+	/// Block `SIGINT` and `SIGTERM` on the thread building the runtime before
+	/// any of the runtime's threads start, so that they inherit the mask and
+	/// the signals are only received through [`shutdown_signal`]. Without
+	/// this, the first call to [`shutdown_signal`] blocks them on the calling
+	/// thread only. Disabled by default
+	///
+	/// [`shutdown_signal`]: crate::shutdown_signal
+	#[must_use]
+	pub const fn shutdown_signals(mut self, enable: bool) -> Self {
+		self.shutdown_signals = enable;
+		self
+	}
+
+	/// The order in which tasks are resumed when their operations complete.
+	/// Use [`ScheduleOrder::Seeded`] to make races between tasks reproducible
+	/// in tests, or iterate over seeds to explore different interleavings
+	#[cfg(feature = "test-util")]
+	#[must_use]
+	pub const fn schedule_order(mut self, order: ScheduleOrder) -> Self {
+		self.engine.schedule = order;
+		self
+	}
+
+	/// Add the runtime to the process-wide registry under `name`, so that its
+	/// metrics can be read from other threads. See [`metrics`]
+	#[must_use]
+	pub const fn register(mut self, name: &'static str) -> Self {
+		self.registry_name = Some(name);
+		self
+	}
+
+	/// Create the runtime
+	///
+	/// # Errors
+	/// If the I/O engine failed to initialize
+	pub fn build(self) -> Result<Pinned<Box<Runtime>>> {
+		if let Some(trace) = self.trace {
+			TRACE_SUBSYSTEMS.store(trace.bits(), Ordering::Relaxed);
+		}
+
+		/* before the engine starts its thread pool */
+		let signals = if self.shutdown_signals {
+			Some(Rc::new(ops::shutdown::signal_fd()?))
+		} else {
+			None
+		};
+
+		let driver = Driver::new(&self.engine)?;
+
+		driver.fd_budget().set_limit(self.fd_budget);
+
+		if let Some(fd) = signals {
+			let _ = driver.shutdown_fd().set(fd);
+		}
+
+		let inner = Inner {
+			driver,
 			#[allow(clippy::multiple_unsafe_ops_per_block)]
 			/* Safety: pool is valid */
 			executor: Executor::new(),
@@ -105,8 +427,45 @@ impl Runtime {
 			pool: Pool::new()
 		};
 
+		let runtime = Runtime {
+			inner: ManuallyDrop::new(Box::new(inner)),
+			registration: self.registry_name.map(Registration::new),
+			builder: self
+		};
+
 		Ok(runtime.pin_box())
 	}
+}
+
+impl Default for RuntimeBuilder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+struct Inner {
+	driver: Driver,
+	executor: Executor,
+	workers: LinkedList,
+	pool: Pool
+}
+
+/// The runtime for xx-pulse
+pub struct Runtime {
+	inner: ManuallyDrop<Box<Inner>>,
+	registration: Option<Registration>,
+	builder: RuntimeBuilder
+}
+
+impl Runtime {
+	pub fn new() -> Result<Pinned<Box<Self>>> {
+		RuntimeBuilder::new().build()
+	}
+
+	#[must_use]
+	pub const fn builder() -> RuntimeBuilder {
+		RuntimeBuilder::new()
+	}
 
 	/// Block on a task `T`, running it to completion. This is the entry point
 	/// of any async operation
@@ -114,14 +473,16 @@ impl Runtime {
 	where
 		T: for<'ctx> Task<Output<'ctx> = Output>
 	{
+		let inner = &**self.inner;
+
 		/* Safety: the env lives until the task finishes */
 		#[allow(clippy::multiple_unsafe_ops_per_block)]
 		let task = unsafe {
 			coroutines::spawn_task(
 				PulseContext::new(
-					ptr!(&self.driver),
-					ptr!(&self.executor),
-					ptr!(&self.workers)
+					ptr!(&inner.driver),
+					ptr!(&inner.executor),
+					ptr!(&inner.workers)
 				),
 				task
 			)
@@ -129,11 +490,93 @@ impl Runtime {
 
 		let running = Cell::new(true);
 
-		let block = |_| self.driver.block_while(|| running.get());
+		let block = |_| {
+			inner.driver.block_while(|| {
+				if let Some(registration) = &self.registration {
+					registration.maybe_publish(|| self.metrics());
+				}
+
+				running.get()
+			});
+		};
+
 		let resume = || running.set(false);
 
 		/* Safety: we are blocked until the future completes */
-		join(unsafe { future::block_on(block, resume, task) })
+		let output = join(unsafe { future::block_on(block, resume, task) });
+
+		if let Some(registration) = &self.registration {
+			registration.publish(self.metrics());
+		}
+
+		output
+	}
+
+	/// Get the syscall counters for the I/O engine
+	#[must_use]
+	pub fn engine_stats(&self) -> EngineStats {
+		self.inner.driver.engine_stats()
+	}
+
+	/// Returns `true` if the kernel supports `feature`, and it was not disabled
+	/// with [`RuntimeBuilder::disable_features`]
+	#[must_use]
+	pub fn supports(&self, feature: EngineFeature) -> bool {
+		self.inner.driver.engine_feature_supported(feature)
+	}
+
+	/// A unique id for this runtime. Resources tagged with a [`RuntimeTag`]
+	/// can only be used on the runtime with the same id
+	#[must_use]
+	pub fn id(&self) -> u64 {
+		self.inner.driver.id()
+	}
+
+	/// Returns `true` if the kernel is missing features that the preferred
+	/// configuration of the I/O engine uses, which may degrade performance
+	#[must_use]
+	pub fn is_compatibility_mode(&self) -> bool {
+		self.inner.driver.is_compatibility_mode()
+	}
+
+	/// Get the state of the file descriptor budget of this runtime. See
+	/// [`FdBudgetStats`]
+	#[must_use]
+	pub fn fd_budget_stats(&self) -> FdBudgetStats {
+		self.inner.driver.fd_budget().stats()
+	}
+
+	/// Get the pending timers and the next timer deadline for this runtime.
+	/// See [`TimerStats`]
+	#[must_use]
+	pub fn timer_stats(&self) -> TimerStats {
+		self.inner.driver.timer_stats()
+	}
+
+	/// Get a snapshot of the metrics of this runtime
+	#[must_use]
+	pub fn metrics(&self) -> RuntimeMetrics {
+		RuntimeMetrics {
+			engine: self.engine_stats(),
+			timers: self.timer_stats(),
+			fds: self.fd_budget_stats()
+		}
+	}
+
+	fn drop_budget_exceeded(&self, iterations: usize, start: u64) -> bool {
+		if self
+			.builder
+			.drop_iterations
+			.is_some_and(|max| iterations >= max)
+		{
+			return true;
+		}
+
+		self.builder.drop_timeout.is_some_and(|timeout| {
+			let elapsed = Duration::from_nanos(nanotime().saturating_sub(start));
+
+			elapsed >= timeout
+		})
 	}
 }
 
@@ -144,16 +587,25 @@ impl Drop for Runtime {
 		 * and driver never get deallocated. when the workers try to use the driver,
 		 * it hangs indefinitely
 		 */
+		let start = nanotime();
+		let mut iterations = 0usize;
+		let mut reported = false;
+		let mut stuck = Vec::new();
+
 		loop {
+			let inner = &**self.inner;
+
 			/* to prevent busy looping, move all our nodes to a new list */
 			let list = LinkedList::new();
 
+			stuck.clear();
+
 			pin!(list);
 
 			/* Safety: our new list is pinned, and we clear out all nodes before
 			 * returning
 			 */
-			unsafe { self.workers.move_elements(&list) };
+			unsafe { inner.workers.move_elements(&list) };
 
 			if list.is_empty() {
 				break;
@@ -167,8 +619,11 @@ impl Drop for Runtime {
 				/* Safety: the worker must be valid */
 				let context = unsafe { ptr!(worker=>context) };
 
+				/* Safety: the worker must be valid */
+				let location = unsafe { ptr!(worker=>location) };
+
 				/* Safety: the worker may not exit immediately, but it unlinks itself on drop */
-				unsafe { self.workers.append(node) };
+				unsafe { inner.workers.append(node) };
 
 				/* Safety: signal the task to interrupt */
 				let result = unsafe { Context::interrupt(context) };
@@ -176,23 +631,76 @@ impl Drop for Runtime {
 				if let Err(err) = &result {
 					debug!("Cancel failed: {:?}", err);
 				}
+
+				stuck.push(location);
 			}
 
 			/* complete any pending i/o */
-			self.driver.exit();
+			inner.driver.exit();
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(iterations += 1);
+
+			if !self.drop_budget_exceeded(iterations, start) {
+				continue;
+			}
+
+			/* only report once when waiting, as the budget stays exceeded */
+			if !reported {
+				let mut locations = String::new();
+
+				for location in &stuck {
+					let _ = match location {
+						Some(location) => write!(locations, "\n::   spawned at {}", location),
+						None => write!(locations, "\n::   spawned at an unknown location")
+					};
+				}
+
+				error!(
+					"== Runtime dropped with {} task(s) that did not exit after {} interrupt(s) in {:?}.{}\n\
+					:: Applying drop policy `{:?}`",
+					stuck.len(),
+					iterations,
+					Duration::from_nanos(nanotime().saturating_sub(start)),
+					locations,
+					self.builder.drop_policy
+				);
+
+				reported = true;
+			}
+
+			match self.builder.drop_policy {
+				DropPolicy::Wait => (),
+				DropPolicy::Abort => abort(),
+				DropPolicy::Leak => {
+					/* Safety: the runtime is never used again. the stuck tasks may
+					 * still reference it, so it is never freed
+					 */
+					let inner = unsafe { ManuallyDrop::take(&mut self.inner) };
+
+					Box::leak(inner);
+
+					return;
+				}
+			}
 		}
+
+		/* Safety: all workers have exited */
+		unsafe { ManuallyDrop::drop(&mut self.inner) };
 	}
 }
 
 impl Pin for Runtime {
 	#[allow(clippy::multiple_unsafe_ops_per_block)]
 	unsafe fn pin(&mut self) {
+		let inner = &mut **self.inner;
+
 		/* Safety: we are being pinned */
 		unsafe {
-			self.executor.pin();
-			self.executor.set_pool(ptr!(&self.pool));
-			self.workers.pin();
-			self.driver.pin();
+			inner.executor.pin();
+			inner.executor.set_pool(ptr!(&inner.pool));
+			inner.workers.pin();
+			inner.driver.pin();
 		}
 	}
 }
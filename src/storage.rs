@@ -0,0 +1,148 @@
+//! Raw block device access
+
+use std::ops::Range;
+use std::os::fd::BorrowedFd;
+use std::path::Path;
+
+use xx_core::os::fcntl::OpenFlag;
+
+use super::*;
+use crate::fs::File;
+
+const BLKSSZGET: u64 = 0x1268;
+const BLKDISCARD: u64 = 0x1277;
+const BLKIOMIN: u64 = 0x1278;
+const BLKIOOPT: u64 = 0x1279;
+const BLKPBSZGET: u64 = 0x127b;
+const BLKZEROOUT: u64 = 0x127f;
+const BLKGETSIZE64: u64 = 0x8008_1272;
+
+/// Run the ioctl on the thread pool, as block device ioctls may sleep for a
+/// long time
+///
+/// # Safety
+/// `T` must be the type expected by `request`
+#[asynchronous]
+async unsafe fn offload<T: Send>(fd: BorrowedFd<'_>, request: u64, mut arg: T) -> Result<T> {
+	/* Safety: guaranteed by caller */
+	unsafe { io::ioctl(fd, request, &mut arg).await? };
+
+	Ok(arg)
+}
+
+fn range_arg(range: &Range<u64>) -> Result<[u64; 2]> {
+	let len = range
+		.end
+		.checked_sub(range.start)
+		.ok_or_else(|| fmt_error!("Invalid range"))?;
+
+	Ok([range.start, len])
+}
+
+/// The I/O size and alignment requirements of a [`BlockDevice`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Alignment {
+	/// The smallest unit the device can address. Direct I/O must be aligned
+	/// to this size
+	pub logical_block_size: u32,
+
+	/// The smallest unit the device can write without a read-modify-write
+	pub physical_block_size: u32,
+
+	/// The preferred minimum I/O size
+	pub min_io_size: u32,
+
+	/// The preferred I/O size for sustained throughput, or zero if unknown
+	pub optimal_io_size: u32
+}
+
+/// A block device, such as a disk or partition. The size query, discard and
+/// write zeroes operations are offloaded to the thread pool.
+pub struct BlockDevice {
+	file: File
+}
+
+#[asynchronous]
+impl BlockDevice {
+	/// Open the block device at `path` for reading and writing
+	#[allow(clippy::impl_trait_in_params)]
+	pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let fd = io::open(path.as_ref(), OpenFlag::ReadWrite.into(), 0).await?;
+
+		Ok(Self { file: File::from(fd) })
+	}
+
+	/// Wrap an already opened block device
+	#[must_use]
+	pub const fn from_file(file: File) -> Self {
+		Self { file }
+	}
+
+	#[must_use]
+	pub fn fd(&self) -> BorrowedFd<'_> {
+		self.file.fd()
+	}
+
+	/// Get the size of the device in bytes
+	pub async fn size(&self) -> Result<u64> {
+		/* Safety: BLKGETSIZE64 takes a u64 */
+		unsafe { offload(self.fd(), BLKGETSIZE64, 0u64).await }
+	}
+
+	/// Get the size and alignment requirements for I/O on this device
+	pub async fn alignment(&self) -> Result<Alignment> {
+		/* Safety: these requests take an int */
+		let (logical, physical, min, optimal) = unsafe {
+			(
+				offload(self.fd(), BLKSSZGET, 0u32).await?,
+				offload(self.fd(), BLKPBSZGET, 0u32).await?,
+				offload(self.fd(), BLKIOMIN, 0u32).await?,
+				offload(self.fd(), BLKIOOPT, 0u32).await?
+			)
+		};
+
+		Ok(Alignment {
+			logical_block_size: logical,
+			physical_block_size: physical,
+			min_io_size: min,
+			optimal_io_size: optimal
+		})
+	}
+
+	/// Tell the device the bytes in `range` are no longer in use. Reads from
+	/// the range afterwards may return any data. The range must be aligned to
+	/// the logical block size
+	pub async fn discard(&self, range: Range<u64>) -> Result<()> {
+		/* Safety: BLKDISCARD takes a [u64; 2] */
+		unsafe { offload(self.fd(), BLKDISCARD, range_arg(&range)?).await? };
+
+		Ok(())
+	}
+
+	/// Zero the bytes in `range`, using the device's write zeroes command if
+	/// it has one. The range must be aligned to the logical block size
+	pub async fn write_zeroes(&self, range: Range<u64>) -> Result<()> {
+		/* Safety: BLKZEROOUT takes a [u64; 2] */
+		unsafe { offload(self.fd(), BLKZEROOUT, range_arg(&range)?).await? };
+
+		Ok(())
+	}
+
+	#[must_use]
+	pub const fn file(&self) -> &File {
+		&self.file
+	}
+
+	pub fn file_mut(&mut self) -> &mut File {
+		&mut self.file
+	}
+
+	#[must_use]
+	pub fn into_inner(self) -> File {
+		self.file
+	}
+
+	pub async fn close(self) -> Result<()> {
+		self.file.close().await
+	}
+}
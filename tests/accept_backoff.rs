@@ -0,0 +1,73 @@
+#![allow(warnings)]
+
+use std::cell::RefCell;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use xx_core::error::*;
+use xx_core::os::error::OsError;
+use xx_pulse::net::*;
+use xx_pulse::*;
+
+/* exhausting file descriptors affects every test in the process, so this test
+ * lives in its own binary
+ */
+#[main]
+#[test]
+async fn test_accept_backoff() -> Result<()> {
+	let mut listener = Tcp::bind("127.0.0.1:0").await?;
+
+	/* listeners share the spare descriptor of the runtime */
+	let open_fds = || fs::read_dir("/proc/self/fd").unwrap().count();
+	let before = open_fds();
+	let other = Tcp::bind("127.0.0.1:0").await?;
+
+	assert_eq!(open_fds(), before + 1);
+	let events = Arc::new(Mutex::new(Vec::new()));
+	let recorded = events.clone();
+
+	listener.on_accept_event(move |event| recorded.lock().unwrap().push(event));
+
+	let addr = listener.local_addr().await?;
+	let shed = Tcp::connect(addr).await?;
+	let mut files = Vec::new();
+
+	while files.len() < 0x100000 {
+		match fs::File::open("/dev/null") {
+			Ok(file) => files.push(file),
+			Err(_) => break
+		}
+	}
+
+	if files.len() == 0x100000 {
+		/* no file descriptor limit to exhaust */
+		return Ok(());
+	}
+
+	let files = RefCell::new(files);
+	let Join(accepted, client) = join(listener.accept(), async {
+		sleep(Duration::from_millis(50)).await?;
+
+		files.borrow_mut().clear();
+
+		Tcp::connect(addr).await
+	})
+	.await;
+
+	let (_, from) = accepted?;
+
+	assert_eq!(from, client?.local_addr().await?);
+
+	let events = events.lock().unwrap();
+
+	assert!(matches!(
+		events[0],
+		AcceptEvent::Exhausted { error: OsError::MFile, .. }
+	));
+
+	assert!(events.contains(&AcceptEvent::Shed));
+	assert_eq!(events.last(), Some(&AcceptEvent::Recovered));
+
+	Ok(())
+}
@@ -0,0 +1,244 @@
+#![allow(warnings)]
+
+use std::time::Duration;
+
+use enumflags2::BitFlags;
+use xx_core::async_std::io::*;
+use xx_core::error::*;
+use xx_pulse::fs::File;
+use xx_pulse::impls::TaskExt;
+use xx_pulse::net::*;
+use xx_pulse::*;
+
+#[asynchronous]
+async fn exercise() -> Result<()> {
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), mut client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	let mut buf = [0u8; 1];
+
+	client.send(&[7], Default::default()).await?;
+	server.recv(&mut buf, Default::default()).await?;
+
+	assert_eq!(buf[0], 7);
+
+	sleep(Duration::from_millis(1)).await?;
+
+	let mut file = File::open("Cargo.toml").await?;
+	let mut str = String::new();
+
+	file.read_to_string(&mut str).await?;
+	file.close().await?;
+	client.close().await?;
+	server.close().await?;
+
+	Ok(())
+}
+
+#[test]
+fn test_disabled_features() {
+	let runtime = Runtime::builder()
+		.disable_features(BitFlags::all())
+		.build()
+		.unwrap();
+
+	runtime.block_on(exercise()).unwrap();
+}
+
+#[test]
+fn test_from_env() {
+	std::env::set_var("XX_PULSE_SQ_ENTRIES", "32");
+	std::env::set_var("XX_PULSE_CQ_ENTRIES", "64");
+	std::env::set_var("XX_PULSE_THREADS", "2");
+	std::env::set_var("XX_PULSE_TRACE", "engine, io");
+
+	let runtime = RuntimeBuilder::from_env().unwrap().build().unwrap();
+
+	runtime.block_on(exercise()).unwrap();
+
+	std::env::set_var("XX_PULSE_ENGINE", "epoll");
+
+	assert!(RuntimeBuilder::from_env().is_err());
+
+	std::env::remove_var("XX_PULSE_ENGINE");
+	std::env::set_var("XX_PULSE_THREADS", "many");
+
+	assert!(RuntimeBuilder::from_env().is_err());
+}
+
+#[test]
+fn test_task_run_policy() {
+	for policy in [TaskRunPolicy::Batched, TaskRunPolicy::EveryEnter] {
+		let runtime = Runtime::builder()
+			.sq_entries(2)
+			.task_run_policy(policy)
+			.build()
+			.unwrap();
+
+		runtime.block_on(exercise()).unwrap();
+
+		let stats = runtime.engine_stats();
+
+		assert!(stats.enters > 0);
+		assert!(stats.taskrun_enters <= stats.enters);
+		assert_eq!(runtime.block_on(io::engine_stats()), runtime.engine_stats());
+	}
+}
+
+#[test]
+fn test_spin_before_park() {
+	let runtime = Runtime::builder()
+		.spin_before_park(Duration::from_micros(50))
+		.build()
+		.unwrap();
+
+	runtime.block_on(exercise()).unwrap();
+
+	let stats = runtime.engine_stats();
+
+	assert!(stats.enters > 0);
+	assert_eq!(Runtime::new().unwrap().engine_stats().spin_hits, 0);
+}
+
+#[test]
+fn test_quiet_capabilities() {
+	let runtime = Runtime::builder()
+		.quiet(true)
+		.disable_features(EngineFeature::SocketOp.into())
+		.build()
+		.unwrap();
+
+	assert!(!runtime.supports(EngineFeature::SocketOp));
+
+	let runtime = Runtime::builder()
+		.disable_features(EngineFeature::ExtArg.into())
+		.build()
+		.unwrap();
+
+	assert!(!runtime.supports(EngineFeature::ExtArg));
+	assert!(runtime.is_compatibility_mode());
+}
+
+#[asynchronous]
+async fn sleep_owned(owner: &TimerOwner) -> Result<()> {
+	owner.sleep(Duration::ZERO).await
+}
+
+#[test]
+fn test_wrong_runtime() {
+	let first = Runtime::new().unwrap();
+	let second = Runtime::new().unwrap();
+
+	assert_ne!(first.id(), second.id());
+
+	let mut owner = first.block_on(TimerOwner::new());
+	let mut tag = owner.tag();
+
+	assert_eq!(tag.id(), first.id());
+	assert!(first.block_on(sleep_owned(&owner)).is_ok());
+
+	let err = second.block_on(sleep_owned(&owner)).unwrap_err();
+
+	let wrong = WrongRuntime { expected: Some(first.id()), current: Some(second.id()) };
+
+	assert_eq!(err.kind(), ErrorKind::InvalidInput);
+	assert_eq!(err.downcast_ref::<WrongRuntime>(), Some(&wrong));
+	assert_eq!(second.block_on(tag.check()), Err(wrong));
+
+	/* a waker resumes its task through the driver of its own runtime */
+	let mut waker = first.block_on(waker::StdWaker::new());
+
+	assert!(second.block_on(waker.wait()).is_err());
+
+	second.block_on(tag.rebind());
+
+	assert_eq!(tag.id(), second.id());
+	assert!(second.block_on(tag.check()).is_ok());
+
+	/* the tag returned by the owner is a copy, so the owner is rebound itself */
+	assert_eq!(owner.tag().id(), first.id());
+
+	second.block_on(owner.rebind());
+
+	assert_eq!(owner.tag().id(), second.id());
+	assert!(second.block_on(sleep_owned(&owner)).is_ok());
+
+	second.block_on(waker.rebind());
+	waker.waker().wake();
+
+	assert_eq!(waker.tag().id(), second.id());
+	assert!(second.block_on(waker.wait()).is_ok());
+}
+
+#[asynchronous]
+async fn open_with_timeout() -> Option<Result<File>> {
+	File::open("Cargo.toml")
+		.timeout(Duration::from_millis(50))
+		.await
+}
+
+#[test]
+fn test_fd_budget() {
+	/* every new descriptor is over a budget of zero */
+	let runtime = Runtime::builder().fd_budget(0).build().unwrap();
+
+	assert!(runtime.block_on(open_with_timeout()).is_none());
+
+	let stats = runtime.fd_budget_stats();
+
+	assert_eq!(stats.limit, Some(0));
+	assert_eq!(stats.waiting, 0);
+	assert!(stats.waits > 0);
+
+	let runtime = Runtime::builder().fd_budget(1 << 20).build().unwrap();
+
+	assert!(runtime.block_on(open_with_timeout()).unwrap().is_ok());
+	assert_eq!(runtime.fd_budget_stats().waits, 0);
+}
+
+#[asynchronous]
+async fn drop_later(file: File) -> Result<()> {
+	sleep(Duration::from_millis(10)).await?;
+	drop(file);
+
+	Ok(())
+}
+
+#[asynchronous]
+async fn exercise_fd_budget() -> Result<()> {
+	let file = File::open("Cargo.toml").await?;
+
+	assert_eq!(fd_budget_stats().await.in_use, 1);
+	assert!(open_with_timeout().await.is_none());
+
+	/* the timed out open returned its permit */
+	assert_eq!(fd_budget_stats().await.in_use, 1);
+
+	/* dropping the file wakes the waiting open */
+	let Join(file, ()) = join(File::open("Cargo.toml"), drop_later(file))
+		.await
+		.flatten()?;
+
+	assert_eq!(fd_budget_stats().await.in_use, 1);
+
+	file.close().await?;
+
+	let stats = fd_budget_stats().await;
+
+	assert_eq!(stats.in_use, 0);
+	assert_eq!(stats.waiting, 0);
+
+	Ok(())
+}
+
+#[test]
+fn test_fd_budget_permits() {
+	let runtime = Runtime::builder().fd_budget(1).build().unwrap();
+
+	runtime.block_on(exercise_fd_budget()).unwrap();
+}
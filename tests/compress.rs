@@ -0,0 +1,172 @@
+#![cfg(any(feature = "compress-gzip", feature = "compress-zstd"))]
+#![allow(warnings)]
+
+use xx_core::async_std::io::*;
+use xx_core::error::*;
+use xx_pulse::io::compress::*;
+use xx_pulse::*;
+
+/// Stores the data written to it, a little at a time. Every other write fails
+/// while `flaky` is set
+#[derive(Default)]
+struct Sink {
+	data: Vec<u8>,
+	flaky: bool,
+	calls: usize
+}
+
+#[asynchronous]
+impl Write for Sink {
+	async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		self.calls += 1;
+
+		if self.flaky && self.calls % 2 == 1 {
+			return Err(fmt_error!("Flaky write" @ ErrorKind::Interrupted));
+		}
+
+		let len = buf.len().min(1000);
+
+		self.data.extend_from_slice(&buf[0..len]);
+
+		Ok(len)
+	}
+
+	async fn flush(&mut self) -> Result<()> {
+		Ok(())
+	}
+}
+
+/// Reads the data back, a little at a time
+struct Source {
+	data: Vec<u8>,
+	pos: usize
+}
+
+#[asynchronous]
+impl Read for Source {
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let len = buf.len().min(777).min(self.data.len() - self.pos);
+
+		buf[0..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+		self.pos += len;
+
+		Ok(len)
+	}
+}
+
+/// Spans many chunks of the chunk size used below
+fn payload() -> Vec<u8> {
+	(0..300_000u32).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[asynchronous]
+async fn round_trip<E: Codec, D: Codec>(encoder: E, decoder: D, flaky: bool) -> Result<()> {
+	let data = payload();
+	let sink = Sink { flaky, ..Default::default() };
+	let mut writer = CompressWriter::with_chunk_size(sink, encoder, 0x1000)?;
+	let mut written = 0;
+
+	/* a failed write can be retried without duplicating or losing data */
+	while written < data.len() {
+		match writer.write(&data[written..]).await {
+			Ok(wrote) => written += wrote,
+			Err(_) if flaky => (),
+			Err(err) => return Err(err)
+		}
+	}
+
+	while let Err(err) = writer.flush().await {
+		if !flaky {
+			return Err(err);
+		}
+	}
+
+	writer.get_mut().flaky = false;
+	writer.finish().await?;
+
+	let compressed = writer.into_inner().data;
+
+	assert!(compressed.len() < data.len());
+
+	let source = Source { data: compressed, pos: 0 };
+	let mut reader = DecompressReader::with_chunk_size(source, decoder, 0x1000)?;
+	let mut decompressed = Vec::new();
+
+	reader.read_to_end(&mut decompressed).await?;
+
+	assert_eq!(decompressed, data);
+
+	Ok(())
+}
+
+#[cfg(feature = "compress-gzip")]
+#[main]
+#[test]
+async fn test_gzip_round_trip() -> Result<()> {
+	round_trip(GzipEncoder::default(), GzipDecoder::new(), false).await?;
+	round_trip(GzipEncoder::new(9), GzipDecoder::new(), true).await
+}
+
+#[cfg(feature = "compress-zstd")]
+#[main]
+#[test]
+async fn test_zstd_round_trip() -> Result<()> {
+	round_trip(ZstdEncoder::new(0)?, ZstdDecoder::new()?, false).await?;
+	round_trip(ZstdEncoder::new(3)?, ZstdDecoder::new()?, true).await
+}
+
+#[cfg(feature = "compress-zstd")]
+#[main]
+#[test]
+async fn test_zstd_truncated() -> Result<()> {
+	let mut writer = CompressWriter::new(Sink::default(), ZstdEncoder::new(0)?);
+
+	writer.write_all(&payload()).await?;
+	writer.finish().await?;
+
+	let mut compressed = writer.into_inner().data;
+
+	compressed.truncate(compressed.len() / 2);
+
+	let source = Source { data: compressed, pos: 0 };
+	let mut reader = DecompressReader::new(source, ZstdDecoder::new()?);
+	let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+
+	assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+	Ok(())
+}
+
+#[cfg(feature = "compress-gzip")]
+#[main]
+#[test]
+async fn test_write_after_finish() -> Result<()> {
+	let mut writer = CompressWriter::new(Sink::default(), GzipEncoder::default());
+
+	writer.write_all(b"hello world").await?;
+	writer.finish().await?;
+
+	assert!(writer.write(b"more").await.is_err());
+
+	Ok(())
+}
+
+#[cfg(feature = "compress-gzip")]
+#[main]
+#[test]
+async fn test_zero_chunk_size() -> Result<()> {
+	let err = CompressWriter::with_chunk_size(Sink::default(), GzipEncoder::default(), 0)
+		.err()
+		.unwrap();
+
+	assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+	let source = Source { data: Vec::new(), pos: 0 };
+	let err = DecompressReader::with_chunk_size(source, GzipDecoder::new(), 0)
+		.err()
+		.unwrap();
+
+	assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+	Ok(())
+}
@@ -2,6 +2,7 @@
 
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use xx_core::async_std::sync::RcNotify;
 use xx_core::coroutines::take_interrupt;
@@ -52,3 +53,123 @@ fn test_exit() {
 
 	assert!(EXITED.load(Ordering::Relaxed));
 }
+
+#[asynchronous]
+async fn stuck(notify: RcNotify) {
+	loop {
+		let _ = notify.wait().await;
+
+		take_interrupt().await;
+	}
+}
+
+#[test]
+fn test_drop_leak() {
+	#[asynchronous]
+	async fn spawn_stuck() {
+		spawn_tracked(stuck(RcNotify::new())).await;
+	}
+
+	let runtime = Runtime::builder()
+		.drop_iterations(4)
+		.drop_policy(DropPolicy::Leak)
+		.build()
+		.unwrap();
+
+	runtime.block_on(spawn_stuck());
+
+	drop(runtime);
+}
+
+#[asynchronous]
+async fn stubborn(notify: RcNotify) {
+	/* ignores a few interrupts, then exits */
+	for _ in 0..8 {
+		let _ = notify.wait().await;
+
+		take_interrupt().await;
+	}
+}
+
+#[test]
+fn test_drop_wait_over_budget() {
+	#[asynchronous]
+	async fn spawn_stubborn() {
+		spawn(stubborn(RcNotify::new())).await;
+	}
+
+	let runtime = Runtime::builder()
+		.drop_iterations(2)
+		.drop_policy(DropPolicy::Wait)
+		.build()
+		.unwrap();
+
+	runtime.block_on(spawn_stubborn());
+
+	/* keeps waiting past the budget until the task exits */
+	drop(runtime);
+}
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+#[asynchronous]
+async fn wait_for_shutdown() {
+	let reason = shutdown_signal(&ShutdownToken::new()).await.unwrap();
+
+	assert_eq!(reason, ShutdownReason::RuntimeExit);
+
+	SHUTDOWN.store(true, Ordering::Relaxed);
+}
+
+#[test]
+fn test_shutdown_signal() {
+	#[main]
+	async fn trigger() {
+		let token = ShutdownToken::new();
+		let waiter = token.clone();
+		let handle = spawn(async move { shutdown_signal(&waiter).await }).await;
+
+		token.trigger();
+
+		assert_eq!(handle.await.unwrap(), ShutdownReason::Token);
+		assert_eq!(shutdown_signal(&token).await.unwrap(), ShutdownReason::Token);
+	}
+
+	#[main]
+	async fn spawn_waiter() {
+		spawn(wait_for_shutdown()).await;
+	}
+
+	trigger();
+	spawn_waiter();
+
+	assert!(SHUTDOWN.load(Ordering::Relaxed));
+}
+
+#[asynchronous]
+async fn wait_twice() {
+	let token = ShutdownToken::new();
+	let (first, second) = (token.clone(), token.clone());
+	let first = spawn(async move { shutdown_signal(&first).await }).await;
+	let second = spawn(async move { shutdown_signal(&second).await }).await;
+
+	token.trigger();
+
+	assert_eq!(first.await.unwrap(), ShutdownReason::Token);
+	assert_eq!(second.await.unwrap(), ShutdownReason::Token);
+
+	/* no signal was sent */
+	let mut signals = shutdown_signals().await.unwrap();
+
+	assert!(matches!(
+		next_or(&mut signals, sleep(Duration::from_millis(10))).await,
+		NextOr::Other(_)
+	));
+}
+
+#[test]
+fn test_shutdown_signals_blocked_at_build() {
+	let runtime = Runtime::builder().shutdown_signals(true).build().unwrap();
+
+	runtime.block_on(wait_twice());
+}
@@ -17,3 +17,284 @@ async fn test_file() {
 	assert_eq!(len, str.len() as u64);
 	assert!(str.contains("[package]"));
 }
+
+#[main]
+#[test]
+async fn test_exists() {
+	assert!(fs::try_exists("Cargo.toml").await.unwrap());
+	assert!(!fs::try_exists("does-not-exist").await.unwrap());
+	assert!(fs::access("Cargo.toml", io::AccessMode::Read.into())
+		.await
+		.unwrap());
+}
+
+#[main]
+#[test]
+async fn test_read_limit() {
+	let data = fs::read("Cargo.toml").await.unwrap();
+	let limited = fs::read_with_limit("Cargo.toml", data.len()).await.unwrap();
+
+	assert_eq!(data, limited);
+
+	let mut file = File::open("Cargo.toml").await.unwrap();
+	let mut vec = Vec::new();
+	let mut progress = fs::ReadProgress::with_limit(16);
+
+	fs::read_to_end_resumable(&mut file, &mut vec, &mut progress)
+		.await
+		.unwrap_err();
+
+	assert_eq!(progress.read(), 16);
+	assert_eq!(vec, data[0..16]);
+}
+
+#[main]
+#[test]
+async fn test_metadata_presets() {
+	let expected = std::fs::metadata("Cargo.toml").unwrap();
+	let metadata = fs::metadata("Cargo.toml").await.unwrap();
+
+	assert!(metadata.file_type().is_file());
+	assert_eq!(metadata.try_len(), Some(expected.len()));
+	assert_eq!(metadata.modified(), Some(expected.modified().unwrap()));
+	assert!(metadata.permissions().is_some());
+
+	let size = fs::metadata_with("Cargo.toml", fs::StatxPreset::SIZE_ONLY)
+		.await
+		.unwrap();
+
+	assert_eq!(size.try_len(), Some(expected.len()));
+}
+
+#[main]
+#[test]
+async fn test_owned_op() {
+	use xx_pulse::io::owned::OwnedOp;
+
+	let expected = std::fs::read("Cargo.toml").unwrap();
+	let file = File::open("Cargo.toml").await.unwrap();
+	let mut buf = vec![0u8; expected.len()];
+
+	let op = OwnedOp::read(file.fd(), &mut buf, 0);
+	let read = op.run().await.unwrap();
+
+	assert_eq!(&buf[..read], &expected[..read]);
+}
+
+#[main]
+#[test]
+async fn test_owned_op_ext() {
+	use std::time::{Duration, Instant};
+
+	use xx_core::error::ErrorKind;
+	use xx_pulse::io::owned::{OpExt, OwnedOp, RetryPolicy};
+	use xx_pulse::net::Udp;
+
+	let expected = std::fs::read("Cargo.toml").unwrap();
+	let file = File::open("Cargo.toml").await.unwrap();
+	let mut buf = vec![0u8; expected.len()];
+
+	let read = OwnedOp::read(file.fd(), &mut buf, 0)
+		.with_timeout(Duration::from_secs(5))
+		.with_retry(RetryPolicy { retries: 3, delay: Duration::ZERO })
+		.traced("cargo")
+		.run()
+		.await
+		.unwrap();
+
+	assert_eq!(&buf[..read], &expected[..read]);
+
+	/* nothing is ever sent, so every attempt times out */
+	let socket = Udp::bind("127.0.0.1:0").await.unwrap();
+	let policy = RetryPolicy { retries: 2, delay: Duration::from_millis(10) };
+	let start = Instant::now();
+	let err = OwnedOp::recv(socket.fd(), &mut buf, Default::default())
+		.with_timeout(Duration::from_millis(20))
+		.with_retry(policy)
+		.run()
+		.await
+		.unwrap_err();
+
+	assert_eq!(err.kind(), ErrorKind::TimedOut);
+	assert!(start.elapsed() >= Duration::from_millis(80));
+}
+
+#[main]
+#[test]
+async fn test_read_virtual() {
+	let status = fs::read_virtual_to_string("/proc/self/status").await.unwrap();
+
+	assert!(status.starts_with("Name:"));
+	assert!(status.contains("\nPid:"));
+
+	/* reports a size of zero, but has data */
+	let cmdline = fs::read_virtual("/proc/self/cmdline").await.unwrap();
+
+	assert!(!cmdline.is_empty());
+}
+
+fn append(path: &std::path::Path, data: &str) {
+	let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+
+	std::io::Write::write_all(&mut file, data.as_bytes()).unwrap();
+}
+
+#[main]
+#[test]
+async fn test_tail_rotation() {
+	let dir = std::env::temp_dir().join(format!("xx-pulse-tail-{}", std::process::id()));
+	let path = dir.join("app.log");
+
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(&path, "old\n").unwrap();
+
+	let mut tail = fs::tail(&path).await.unwrap();
+
+	tail.set_poll_interval(std::time::Duration::from_millis(10));
+	append(&path, "first\r\nsec");
+
+	assert_eq!(tail.next_line().await.unwrap(), "first");
+
+	/* the rest of the old file is read before following the new one */
+	append(&path, "ond\n");
+	std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+	std::fs::write(&path, "rotated\n").unwrap();
+
+	assert_eq!(tail.next_line().await.unwrap(), "second");
+	assert_eq!(tail.next_line().await.unwrap(), "rotated");
+
+	/* truncated, so reading restarts from the beginning */
+	std::fs::write(&path, "new\n").unwrap();
+
+	assert_eq!(tail.next_line().await.unwrap(), "new");
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[main]
+#[test]
+async fn test_tail_watch() {
+	let dir = std::env::temp_dir().join(format!("xx-pulse-tail-watch-{}", std::process::id()));
+	let path = dir.join("app.log");
+
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(&path, "").unwrap();
+
+	let mut tail = fs::tail(&path).await.unwrap();
+
+	if !tail.is_watching() {
+		/* inotify is not available */
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		return;
+	}
+
+	/* changes must be reported without polling */
+	tail.set_poll_interval(std::time::Duration::from_secs(3600));
+
+	let Join(line, _) = join(tail.next_line(), async {
+		sleep(std::time::Duration::from_millis(10)).await.unwrap();
+		append(&path, "appended\n");
+	})
+	.await;
+
+	assert_eq!(line.unwrap(), "appended");
+
+	let Join(line, _) = join(tail.next_line(), async {
+		sleep(std::time::Duration::from_millis(10)).await.unwrap();
+		std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+		std::fs::write(&path, "rotated\n").unwrap();
+	})
+	.await;
+
+	assert_eq!(line.unwrap(), "rotated");
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[main]
+#[test]
+async fn test_watch() {
+	let dir = std::env::temp_dir().join(format!("xx-pulse-watch-{}", std::process::id()));
+
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let mut watcher = fs::Watcher::new().unwrap();
+	let watch = watcher
+		.add(&dir, fs::WatchKind::Create | fs::WatchKind::CloseWrite)
+		.unwrap();
+
+	std::fs::write(dir.join("created"), "data").unwrap();
+
+	let event = watcher.next_event().await.unwrap();
+
+	assert_eq!(event.watch, Some(watch));
+	assert!(event.kinds.contains(fs::WatchKind::Create));
+	assert_eq!(event.path, Some(dir.join("created")));
+
+	let event = watcher.next().await.unwrap().unwrap();
+
+	assert!(event.kinds.contains(fs::WatchKind::CloseWrite));
+
+	watcher.remove(watch).unwrap();
+
+	let event = watcher.next_event().await.unwrap();
+
+	assert!(event.kinds.contains(fs::WatchKind::Ignored));
+	assert!(watcher.remove(watch).is_err());
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[main]
+#[test]
+async fn test_prefetch() {
+	let mut file = File::open("Cargo.toml").await.unwrap();
+	let len = file.stream_len().await.unwrap();
+
+	file.prefetch(&[], false).await.unwrap();
+	file.prefetch(&[], true).await.unwrap();
+	file.prefetch(&[0..16, 32..len], false).await.unwrap();
+	file.prefetch(&[0..16, 32..len], true).await.unwrap();
+
+	/* ranges past the end of the file stop at the end */
+	file.prefetch(&[len..len + 0x100000], true).await.unwrap();
+
+	/* offsets that do not fit in a file offset are rejected */
+	assert!(file
+		.prefetch(&[u64::MAX - 1..u64::MAX], true)
+		.await
+		.is_err());
+
+	/* the file position is unchanged */
+	let mut str = String::new();
+
+	file.read_to_string(&mut str).await.unwrap();
+
+	assert_eq!(str.len() as u64, len);
+}
+
+#[main]
+#[test]
+async fn test_read_uninit() {
+	use std::mem::MaybeUninit;
+
+	let data = std::fs::read("Cargo.toml").unwrap();
+	let mut file = File::open("Cargo.toml").await.unwrap();
+	let mut buf = vec![MaybeUninit::<u8>::uninit(); data.len() + 0x100];
+
+	/* the buffer is larger than the file, so only the file's length is read */
+	let read = file.read_uninit(&mut buf).await.unwrap();
+
+	assert_eq!(read.len(), data.len());
+	assert_eq!(read, &data[..]);
+
+	/* at the end of the file */
+	assert!(file.read_uninit(&mut buf).await.unwrap().is_empty());
+
+	let read = io::read_uninit(file.fd(), &mut buf[0..16], 0).await.unwrap();
+
+	assert_eq!(read, 16);
+	/* Safety: the kernel initialized `read` bytes */
+	assert_eq!(unsafe { io::assume_init_prefix(&mut buf, read) }, &data[0..16]);
+}
@@ -0,0 +1,102 @@
+#![allow(warnings)]
+
+use std::time::Duration;
+
+use xx_core::async_std::AsyncIterator;
+use xx_core::error::*;
+use xx_pulse::*;
+
+struct Ticks {
+	left: u32,
+	delay: Duration
+}
+
+#[asynchronous]
+impl AsyncIterator for Ticks {
+	type Item = u32;
+
+	async fn next(&mut self) -> Option<u32> {
+		if self.left == 0 {
+			return None;
+		}
+
+		sleep(self.delay).await.ok()?;
+		self.left -= 1;
+
+		Some(self.left)
+	}
+}
+
+#[main]
+#[test]
+async fn test_select_iterators() -> Result<()> {
+	let mut fast = Ticks { left: 3, delay: Duration::from_millis(5) };
+	let mut slow = Ticks { left: 100, delay: Duration::from_millis(12) };
+	let mut fast_items = Vec::new();
+	let mut slow_items = 0;
+
+	loop {
+		let closed = select_many! {
+			item in fast => {
+				fast_items.push(item);
+
+				false
+			}
+
+			_ in slow => {
+				slow_items += 1;
+
+				false
+			}
+
+			on_closed => true
+		}
+		.await;
+
+		if closed {
+			break;
+		}
+	}
+
+	assert_eq!(fast_items, [2, 1, 0]);
+	assert!(slow_items < 3);
+
+	/* tasks and iterators can be mixed */
+	let item = select_many! {
+		item in slow => item,
+		_ = sleep(Duration::from_secs(5)) => 0,
+		on_closed => 0
+	}
+	.await;
+
+	assert!(item > 0);
+
+	Ok(())
+}
+
+/// An iterator that always has an item ready
+struct Repeat(u32);
+
+#[asynchronous]
+impl AsyncIterator for Repeat {
+	type Item = u32;
+
+	async fn next(&mut self) -> Option<u32> {
+		Some(self.0)
+	}
+}
+
+#[main]
+#[test]
+async fn test_merge_fair() {
+	let mut merged = merge(Repeat(1), Repeat(2));
+	let mut items = Vec::new();
+
+	for _ in 0..8 {
+		items.extend(merged.next().await);
+	}
+
+	/* neither iterator starves the other when both are always ready */
+	assert_eq!(items.iter().filter(|item| **item == 1).count(), 4);
+	assert_eq!(items.iter().filter(|item| **item == 2).count(), 4);
+}
@@ -0,0 +1,42 @@
+#![allow(warnings)]
+
+use xx_core::async_std::io::*;
+use xx_core::error::*;
+use xx_pulse::net::*;
+use xx_pulse::*;
+
+#[asynchronous]
+async fn scrape() -> Result<String> {
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let addr = listener.local_addr().await?;
+	let server = spawn(async move { metrics::serve(&listener).await }).await;
+
+	let mut client = Tcp::connect(addr).await?;
+	let mut response = String::new();
+
+	client.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await?;
+	client.read_to_string(&mut response).await?;
+
+	drop(server);
+
+	Ok(response)
+}
+
+#[test]
+fn test_metrics_endpoint() {
+	let runtime = Runtime::builder().register("metrics-test").build().unwrap();
+	let response = runtime.block_on(scrape()).unwrap();
+
+	assert!(response.starts_with("HTTP/1.1 200 OK"));
+	assert!(response.contains("xx_pulse_engine_enters_total{runtime=\"metrics-test\""));
+
+	let registered = metrics::registered_runtimes();
+
+	assert!(registered.iter().any(|runtime| runtime.name == "metrics-test"));
+
+	drop(runtime);
+
+	let registered = metrics::registered_runtimes();
+
+	assert!(!registered.iter().any(|runtime| runtime.name == "metrics-test"));
+}
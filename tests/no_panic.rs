@@ -0,0 +1,48 @@
+#![cfg(feature = "no-panic")]
+
+use std::io::SeekFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use xx_core::async_std::io::*;
+use xx_core::error::Result;
+use xx_pulse::fs::File;
+use xx_pulse::*;
+
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+fn detect_panics() {
+	let hook = std::panic::take_hook();
+
+	std::panic::set_hook(Box::new(move |info| {
+		PANICKED.store(true, Ordering::Relaxed);
+		hook(info);
+	}));
+}
+
+#[main]
+async fn exercise() -> Result<()> {
+	assert!(Interval::try_new(Duration::MAX).is_err());
+
+	let _ = Interval::new(Duration::MAX);
+
+	let mut file = File::open("Cargo.toml").await?;
+
+	assert!(file.seek(SeekFrom::Current(-1)).await.is_err());
+	assert!(file.seek(SeekFrom::Start(u64::MAX)).await.is_ok());
+	assert!(file.read(&mut [0u8; 1]).await.is_err());
+
+	let result = select(sleep(Duration::MAX), sleep(Duration::from_millis(1))).await;
+
+	assert!(matches!(result, Select::Second(..)));
+
+	Ok(())
+}
+
+#[test]
+fn test_no_panic() {
+	detect_panics();
+	assert!(exercise().is_ok());
+
+	assert!(!PANICKED.load(Ordering::Relaxed));
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "test-util")]
+#![allow(warnings)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use xx_core::os::poll::PollFlag;
+use xx_pulse::net::*;
+use xx_pulse::*;
+
+#[asynchronous]
+async fn poll_and_record(socket: Rc<DatagramSocket>, order: Rc<RefCell<Vec<usize>>>, id: usize) {
+	io::poll(socket.fd(), PollFlag::Out.into()).await.unwrap();
+
+	order.borrow_mut().push(id);
+}
+
+#[asynchronous]
+async fn race(order: Rc<RefCell<Vec<usize>>>) {
+	let socket = Rc::new(Udp::bind("127.0.0.1:0").await.unwrap());
+	let mut handles = Vec::new();
+
+	for id in 0..16 {
+		handles.push(spawn(poll_and_record(socket.clone(), order.clone(), id)).await);
+	}
+
+	for handle in handles {
+		handle.await;
+	}
+}
+
+fn run(order: ScheduleOrder) -> Vec<usize> {
+	let runtime = Runtime::builder().schedule_order(order).build().unwrap();
+	let result = Rc::new(RefCell::new(Vec::new()));
+
+	runtime.block_on(race(result.clone()));
+
+	result.take()
+}
+
+#[test]
+fn test_seeded_order() {
+	for seed in 0..8 {
+		assert_eq!(
+			run(ScheduleOrder::Seeded(seed)),
+			run(ScheduleOrder::Seeded(seed))
+		);
+	}
+}
+
+#[test]
+fn test_fifo_order() {
+	assert_eq!(run(ScheduleOrder::Fifo), (0..16).collect::<Vec<_>>());
+}
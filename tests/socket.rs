@@ -1,6 +1,11 @@
 #![allow(warnings)]
 
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use xx_core::async_std::AsyncIterator;
 use xx_core::error::*;
+use xx_core::os::poll::PollFlag;
 use xx_pulse::net::*;
 use xx_pulse::*;
 
@@ -52,3 +57,638 @@ async fn test_tcp() -> Result<()> {
 
 	Ok(())
 }
+
+#[main]
+#[test]
+async fn test_incoming() -> Result<()> {
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let mut incoming = listener.incoming();
+	let Join(accepted, client) = join(
+		incoming.next(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await;
+
+	let (_, addr) = accepted.unwrap()?;
+
+	assert_eq!(addr, client?.local_addr().await?);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_recv_timeout() -> Result<()> {
+	let server = Udp::bind("127.0.0.1:0").await?;
+	let mut buf = [0u8; 1];
+
+	let err = io::recv_timeout(
+		server.fd(),
+		&mut buf,
+		Default::default(),
+		Duration::from_millis(10)
+	)
+	.await
+	.unwrap_err();
+
+	assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_ioctl() -> Result<()> {
+	const FIONREAD: u64 = 0x541b;
+
+	let server = Udp::bind("127.0.0.1:0").await?;
+	let mut client = Udp::connect(server.local_addr().await?).await?;
+
+	client.send(&[1, 2, 3], Default::default()).await?;
+	io::poll(server.fd(), PollFlag::In.into()).await?;
+
+	let mut available = 0i32;
+
+	unsafe { io::ioctl(server.fd(), FIONREAD, &mut available).await? };
+
+	assert_eq!(available, 3);
+	assert_eq!(io::bytes_available(server.fd())?, 3);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_recv_available() -> Result<()> {
+	let mut server = Udp::bind("127.0.0.1:0").await?;
+	let mut client = Udp::connect(server.local_addr().await?).await?;
+
+	client.send(&[0; 300], Default::default()).await?;
+	client.send(&[1, 2], Default::default()).await?;
+
+	/* a zero limit leaves the datagram queued */
+	assert!(server.recv_available(0, Default::default()).await?.is_empty());
+	assert_eq!(server.recv_available(0x1000, Default::default()).await?.len(), 300);
+	assert_eq!(server.recv_available(0x1000, Default::default()).await?, [1, 2]);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_udp_builder() -> Result<()> {
+	let first = Udp::builder()
+		.reuse_port(true)
+		.recvbuf_size(0x10000)
+		.bind("127.0.0.1:0")
+		.await?;
+
+	let second = Udp::builder()
+		.reuse_port(true)
+		.bind(first.local_addr().await?)
+		.await?;
+
+	assert_eq!(first.local_addr().await?, second.local_addr().await?);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_tcp_builder_device() -> Result<()> {
+	let listener = Tcp::builder().device("lo").bind("127.0.0.1:0").await?;
+	let addr = listener.local_addr().await?;
+
+	let client = Tcp::builder().device("lo").connect(addr).await?;
+	let (server, _) = listener.accept().await?;
+
+	assert_eq!(client.local_addr().await?, server.peer_addr().await?);
+
+	let udp = Udp::builder().device("lo").connect(addr).await?;
+
+	assert_eq!(udp.peer_addr().await?, addr);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_connect_with_resolver() -> Result<()> {
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let port = listener.local_addr().await?.port();
+
+	let mut resolver = HostsResolver::new(SystemResolver);
+
+	resolver.parse_hosts("# comment\n127.0.0.1 Service.Test other.test # trailing\n")?;
+
+	assert_eq!(resolver.get("service.test"), Some(&["127.0.0.1".parse().unwrap()][..]));
+	assert!(resolver.get("missing.test").is_none());
+
+	let client = Tcp::connect_with(&resolver, &format!("service.test:{}", port)).await?;
+	let (server, _) = listener.accept().await?;
+
+	assert_eq!(client.local_addr().await?, server.peer_addr().await?);
+	assert!(Tcp::connect_with(&resolver, "service.test").await.is_err());
+
+	Ok(())
+}
+
+#[asynchronous]
+async fn answer_dns_queries(server: &mut DatagramSocket, count: usize) -> Result<()> {
+	let mut buf = [0u8; 512];
+
+	for _ in 0..count {
+		let (len, from) = server.recvfrom(&mut buf, Default::default()).await?;
+		let mut response = buf[0..len].to_vec();
+		let is_a = response[len - 3] == dns::RecordType::A as u8;
+
+		/* a response with recursion available, and one answer for A queries */
+		response[2..4].copy_from_slice(&0x8180u16.to_be_bytes());
+		response[7] = u8::from(is_a);
+
+		if is_a {
+			response.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 127, 0, 0, 1]);
+		}
+
+		server.sendto(&response, Default::default(), &from).await?;
+	}
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_dns_resolver() -> Result<()> {
+	let mut server = Udp::bind("127.0.0.1:0").await?;
+	let resolver = dns::DnsResolver::new(vec![server.local_addr().await?]);
+
+	let Join(answered, addrs) = join(
+		answer_dns_queries(&mut server, 2),
+		resolver.resolve("service.test", 80)
+	)
+	.await;
+
+	answered?;
+
+	assert_eq!(addrs?, ["127.0.0.1:80".parse::<SocketAddr>().unwrap()]);
+
+	let conf = "# comment\nnameserver 10.0.0.1\nsearch example.com\nnameserver ::1\n";
+
+	assert_eq!(dns::parse_resolv_conf(conf), [
+		"10.0.0.1:53".parse::<SocketAddr>().unwrap(),
+		"[::1]:53".parse().unwrap()
+	]);
+
+	assert!(dns::encode_query(1, "bad..name", dns::RecordType::A).is_err());
+
+	let addrs = ThreadPoolResolver.resolve("localhost", 80).await?;
+
+	assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_stun_binding() -> Result<()> {
+	let mut server = Udp::bind("127.0.0.1:0").await?;
+	let mut client = Udp::bind("127.0.0.1:0").await?;
+	let server_addr = server.local_addr().await?;
+	let client_addr = client.local_addr().await?;
+
+	let respond = async move {
+		let mut buf = [0u8; 64];
+		let (_, from) = server.recvfrom(&mut buf, Default::default()).await?;
+		let port = from.port() ^ 0x2112;
+		let SocketAddr::V4(addr) = from else {
+			panic!("expected ipv4");
+		};
+
+		let mut response = vec![0x01, 0x01, 0x00, 0x0c];
+
+		response.extend_from_slice(&buf[4..20]);
+		response.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01]);
+		response.extend_from_slice(&port.to_be_bytes());
+
+		for (octet, mask) in addr.ip().octets().iter().zip([0x21, 0x12, 0xa4, 0x42]) {
+			response.push(octet ^ mask);
+		}
+
+		server.sendto(&response, Default::default(), &from).await
+	};
+
+	let Join(mapped, _) = join(stun::binding(&mut client, server_addr), respond)
+		.await
+		.flatten()?;
+
+	assert_eq!(mapped, client_addr);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_idle_timeout() -> Result<()> {
+	use xx_pulse::io::idle::IdleTimeout;
+	use xx_pulse::io::*;
+
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((server, _), client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	let mut server = IdleTimeout::new(server, Duration::from_millis(50));
+	let mut client = IdleTimeout::new(client, Duration::from_secs(10));
+	let mut buf = [0u8; 1];
+
+	/* data arriving within the idle period completes the read */
+	let Join(read, wrote) = join(server.read(&mut buf), async {
+		sleep(Duration::from_millis(30)).await?;
+		client.write(&[1]).await
+	})
+	.await;
+
+	assert_eq!(read?, 1);
+	assert_eq!(wrote?, 1);
+
+	let err = server.read(&mut buf).await.unwrap_err();
+
+	assert_eq!(err.kind(), ErrorKind::TimedOut);
+	assert!(server.remaining().is_zero());
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_probe_eof() -> Result<()> {
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), mut client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	assert!(!server.is_peer_closed()?);
+
+	client.send(&[1], Default::default()).await?;
+	client.close().await?;
+
+	/* pending data is not consumed */
+	assert!(!server.probe_eof().await?);
+	assert_eq!(server.bytes_available()?, 1);
+
+	let mut buf = [0u8; 1];
+
+	server.recv(&mut buf, Default::default()).await?;
+
+	assert!(server.probe_eof().await?);
+	assert!(server.is_peer_closed()?);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_peer_closed_after_poll() -> Result<()> {
+	use xx_core::os::error::OsError;
+	use xx_core::os::socket::Shutdown;
+
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), mut client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	client.close().await?;
+
+	/* polling for input records the hang up, without reporting it */
+	let ready = server.poll(PollFlag::In.into()).await?;
+
+	assert!(ready.contains(PollFlag::In));
+	assert!(!ready.contains(PollFlag::RdHangUp));
+	assert!(server.is_peer_closed()?);
+
+	/* a local shutdown is not mistaken for the peer closing */
+	let Join((mut server, _), client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	server.shutdown(Shutdown::Read).await?;
+
+	let err = server.is_peer_closed().unwrap_err();
+
+	assert_eq!(err.os_error(), Some(OsError::NotConn));
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_shutdown_state() -> Result<()> {
+	use xx_core::os::error::OsError;
+	use xx_core::os::socket::Shutdown;
+
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), mut client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	let mut buf = [0u8; 1];
+
+	assert_eq!(client.shutdown_state(), None);
+
+	client.shutdown(Shutdown::Write).await?;
+
+	assert_eq!(client.shutdown_state(), Some(Shutdown::Write));
+
+	let err = client.send(&[1], Default::default()).await.unwrap_err();
+
+	assert_eq!(err.os_error(), Some(OsError::Pipe));
+
+	/* the read half is still open */
+	server.send(&[2], Default::default()).await?;
+
+	assert_eq!(client.recv(&mut buf, Default::default()).await?, 1);
+	assert_eq!(server.recv(&mut buf, Default::default()).await?, 0);
+
+	server.shutdown(Shutdown::Read).await?;
+
+	let err = server.recv(&mut buf, Default::default()).await.unwrap_err();
+
+	assert_eq!(err.os_error(), Some(OsError::NotConn));
+
+	server.shutdown(Shutdown::Write).await?;
+
+	assert_eq!(server.shutdown_state(), Some(Shutdown::Both));
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_shutdown_state_shared() -> Result<()> {
+	use xx_core::async_std::io::SplitMut;
+	use xx_core::os::error::OsError;
+	use xx_core::os::socket::Shutdown;
+
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), _client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	fn assert_send<T: Send>(_: &T) {}
+
+	/* the shared state does not make sockets !Send */
+	assert_send(&server);
+	assert_send(&listener);
+
+	let (reader, mut writer) = server.try_split()?;
+
+	/* shutting down through one half is seen by the other */
+	writer.shutdown(Shutdown::Write).await?;
+
+	assert_eq!(reader.shutdown_state(), Some(Shutdown::Write));
+
+	/* and by the socket */
+	assert_eq!(server.shutdown_state(), Some(Shutdown::Write));
+
+	let err = server.send(&[1], Default::default()).await.unwrap_err();
+
+	assert_eq!(err.os_error(), Some(OsError::Pipe));
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_accept_batch() -> Result<()> {
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let addr = listener.local_addr().await?;
+	let mut clients = Vec::new();
+
+	for _ in 0..4 {
+		clients.push(Tcp::connect(addr).await?);
+	}
+
+	let mut accepted = listener.accept_batch(3).await?;
+
+	assert_eq!(accepted.len(), 3);
+
+	accepted.extend(listener.accept_batch(3).await?);
+
+	assert_eq!(accepted.len(), 4);
+
+	for (client, (_, addr)) in clients.iter().zip(&accepted) {
+		assert_eq!(client.local_addr().await?, *addr);
+	}
+
+	assert!(listener.accept_batch(0).await?.is_empty());
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_recv_all() -> Result<()> {
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), mut client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	let mut header = [0u8; 8];
+	let Join(recvd, sent) = join(server.recv_all(&mut header, Default::default()), async {
+		for i in 0..4 {
+			client.send(&[i, i], Default::default()).await?;
+			sleep(Duration::from_millis(5)).await?;
+		}
+
+		Ok::<_, Error>(())
+	})
+	.await;
+
+	recvd?;
+	sent?;
+
+	assert_eq!(header, [0, 0, 1, 1, 2, 2, 3, 3]);
+
+	client.send(&[1], Default::default()).await?;
+	client.close().await?;
+
+	let err = server
+		.recv_all(&mut header, Default::default())
+		.await
+		.unwrap_err();
+
+	assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_bind_all() -> Result<()> {
+	let addrs: [SocketAddr; 2] = ["127.0.0.1:0".parse().unwrap(), "127.0.0.1:0".parse().unwrap()];
+	let listener = Tcp::bind_all(&addrs[..]).await?;
+	let bound = listener.local_addrs().await?;
+
+	assert_eq!(bound.len(), 2);
+
+	for addr in bound {
+		let client = Tcp::connect(addr).await?;
+		let (_, peer) = listener.accept().await?;
+
+		assert_eq!(peer, client.local_addr().await?);
+	}
+
+	Ok(())
+}
+
+#[asynchronous]
+async fn flush_repeatedly(queue: std::rc::Rc<WriteQueue>, times: usize) -> Result<()> {
+	for _ in 0..times {
+		queue.flush().await?;
+		yield_now().await;
+	}
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_write_queue_order() -> Result<()> {
+	const COUNT: u32 = 10_000;
+
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	let queue = std::rc::Rc::new(client.into_write_queue());
+
+	/* flushes race with the pushes and the background task */
+	let flusher = spawn(flush_repeatedly(queue.clone(), 100)).await;
+
+	for i in 0..COUNT {
+		queue.push(i.to_le_bytes().to_vec()).await?;
+
+		if i % 37 == 0 {
+			yield_now().await;
+		}
+
+		if i % 101 == 0 {
+			queue.flush().await?;
+		}
+	}
+
+	flusher.await?;
+	queue.flush().await?;
+
+	assert_eq!(queue.queued(), 0);
+
+	let mut buf = vec![0u8; COUNT as usize * 4];
+
+	server.recv_all(&mut buf, Default::default()).await?;
+
+	for (i, chunk) in buf.chunks_exact(4).enumerate() {
+		assert_eq!(u32::from_le_bytes(chunk.try_into().unwrap()), i as u32);
+	}
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_recv_uninit() -> Result<()> {
+	use std::mem::MaybeUninit;
+
+	let mut server = Udp::bind("127.0.0.1:0").await?;
+	let mut client = Udp::connect(server.local_addr().await?).await?;
+	let mut buf = [MaybeUninit::<u8>::uninit(); 64];
+
+	client.send(&[1, 2, 3], Default::default()).await?;
+
+	/* only the received part of the buffer is returned */
+	let recvd = server.recv_uninit(&mut buf, Default::default()).await?;
+
+	assert_eq!(recvd, [1, 2, 3]);
+	assert!(server
+		.recv_uninit(&mut [], Default::default())
+		.await?
+		.is_empty());
+
+	client.send(&[4; 5], Default::default()).await?;
+
+	let recvd = io::recv_uninit(server.fd(), &mut buf, Default::default()).await?;
+
+	assert_eq!(recvd, 5);
+	/* Safety: the kernel initialized `recvd` bytes */
+	assert_eq!(unsafe { io::assume_init_prefix(&mut buf, recvd) }, [4; 5]);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_read_to_end_resumable() -> Result<()> {
+	use xx_core::async_std::io::WriteExt;
+	use xx_pulse::impls::ReadUninitExt;
+
+	let listener = Tcp::bind("127.0.0.1:0").await?;
+	let Join((mut server, _), mut client) = join(
+		listener.accept(),
+		Tcp::connect(listener.local_addr().await?)
+	)
+	.await
+	.flatten()?;
+
+	let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+	let Join(sent, received) = join(
+		async move {
+			client.write_all(&data).await?;
+			client.close().await
+		},
+		async move {
+			let mut vec = vec![9];
+			let mut progress = fs::ReadProgress::new();
+
+			server.read_to_end_resumable(&mut vec, &mut progress).await?;
+
+			Ok::<_, Error>((vec, progress))
+		}
+	)
+	.await;
+
+	sent?;
+
+	let (vec, progress) = received?;
+
+	/* the data is appended after what was already in the buffer */
+	assert!(progress.is_done());
+	assert_eq!(progress.read(), 200_000);
+	assert_eq!(vec[0], 9);
+	assert!(vec[1..].iter().enumerate().all(|(i, byte)| *byte == i as u8));
+
+	Ok(())
+}
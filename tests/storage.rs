@@ -0,0 +1,66 @@
+#![allow(warnings)]
+
+use std::io::SeekFrom;
+
+use xx_core::async_std::io::*;
+use xx_core::error::*;
+use xx_core::os::error::OsError;
+use xx_pulse::fs::File;
+use xx_pulse::storage::*;
+use xx_pulse::*;
+
+#[main]
+#[test]
+async fn test_not_a_block_device() -> Result<()> {
+	let device = BlockDevice::from_file(File::open("Cargo.toml").await?);
+
+	/* block device ioctls are rejected for regular files */
+	assert_eq!(device.size().await.unwrap_err().os_error(), Some(OsError::NoTty));
+	assert!(device.alignment().await.is_err());
+
+	/* inverted ranges are rejected before reaching the device */
+	assert!(device.discard(0x1000..0).await.is_err());
+	assert!(device.write_zeroes(0x1000..0).await.is_err());
+
+	device.close().await
+}
+
+/// Runs against the block device at `XX_PULSE_TEST_SCRATCH_DEVICE`, such as a
+/// loop device. Its first block is overwritten
+#[main]
+#[test]
+async fn test_scratch_device() -> Result<()> {
+	let Ok(path) = std::env::var("XX_PULSE_TEST_SCRATCH_DEVICE") else {
+		return Ok(());
+	};
+
+	let mut device = BlockDevice::open(&path).await?;
+	let size = device.size().await?;
+	let alignment = device.alignment().await?;
+	let block = alignment.logical_block_size;
+
+	assert!(size >= block as u64);
+	assert!(block.is_power_of_two());
+	assert!(alignment.physical_block_size >= block);
+
+	let file = device.file_mut();
+
+	file.write_all(&vec![0xaa; block as usize]).await?;
+	file.flush().await?;
+	device.write_zeroes(0..block as u64).await?;
+
+	let file = device.file_mut();
+	let mut data = vec![0xff; block as usize];
+
+	file.seek(SeekFrom::Start(0)).await?;
+	file.read_exact(&mut data).await?;
+
+	assert!(data.iter().all(|byte| *byte == 0));
+
+	/* discard may not be supported by the device */
+	if let Err(err) = device.discard(0..block as u64).await {
+		assert_eq!(err.os_error(), Some(OsError::OpNotSupp));
+	}
+
+	device.close().await
+}
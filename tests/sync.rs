@@ -0,0 +1,185 @@
+#![allow(warnings)]
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use xx_core::error::*;
+use xx_pulse::impls::TaskExt;
+use xx_pulse::sync::*;
+use xx_pulse::*;
+
+#[main]
+#[test]
+async fn test_mutex() -> Result<()> {
+	let mutex = Rc::new(Mutex::new(0));
+	let mut tasks = Vec::new();
+
+	for _ in 0..4 {
+		let mutex = mutex.clone();
+
+		tasks.push(
+			spawn(async move {
+				let mut value = mutex.lock().await.unwrap();
+				let read = *value;
+
+				/* hold the lock across a suspension */
+				sleep(Duration::from_millis(1)).await.unwrap();
+
+				*value = read + 1;
+			})
+			.await
+		);
+	}
+
+	for task in tasks {
+		task.await;
+	}
+
+	assert_eq!(*mutex.lock().await?, 4);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_condvar() -> Result<()> {
+	let state = Rc::new((Mutex::new(false), Condvar::new()));
+	let waiter = {
+		let state = state.clone();
+
+		spawn(async move {
+			let (mutex, condvar) = &*state;
+			let ready = mutex.lock().await.unwrap();
+			let ready = condvar.wait_while(ready, |ready| !*ready).await.unwrap();
+
+			*ready
+		})
+		.await
+	};
+
+	sleep(Duration::from_millis(1)).await?;
+
+	let (mutex, condvar) = &*state;
+
+	*mutex.lock().await? = true;
+	condvar.notify_all();
+
+	assert!(waiter.await);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_condvar_timeout() -> Result<()> {
+	let mutex = Mutex::new(());
+	let condvar = Condvar::new();
+	let guard = mutex.lock().await?;
+
+	/* a cancelled wait releases the lock */
+	assert!(condvar
+		.wait(guard)
+		.timeout(Duration::from_millis(1))
+		.await
+		.is_none());
+	assert!(!mutex.is_locked());
+
+	condvar.notify_one();
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_rwlock() -> Result<()> {
+	let lock = Rc::new(RwLock::new(0));
+	let first = lock.read().await?;
+	let second = lock.read().await?;
+
+	assert_eq!(lock.reader_count(), 2);
+
+	let writer = {
+		let lock = lock.clone();
+
+		spawn(async move {
+			let mut value = lock.write().await.unwrap();
+
+			*value += 1;
+
+			/* no writer can get in between */
+			let value = RwLockWriteGuard::downgrade(value);
+
+			sleep(Duration::from_millis(1)).await.unwrap();
+
+			*value
+		})
+		.await
+	};
+
+	/* a waiting writer blocks new readers */
+	assert!(lock.try_read().is_none());
+
+	drop(first);
+	drop(second);
+
+	let read = lock.read().await?;
+
+	assert_eq!(*read, 1);
+	assert!(lock.try_write().is_none());
+	assert_eq!(writer.await, 1);
+
+	drop(read);
+
+	*lock.write().await? += 1;
+
+	assert_eq!(*lock.read().await?, 2);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_once_cell() -> Result<()> {
+	let cell = Rc::new(OnceCell::new());
+	let runs = Rc::new(std::cell::Cell::new(0));
+	let mut tasks = Vec::new();
+
+	for i in 0..4 {
+		let cell = cell.clone();
+		let runs = runs.clone();
+
+		tasks.push(
+			spawn(async move {
+				let value = cell
+					.get_or_try_init(|| async move {
+						runs.set(runs.get() + 1);
+						sleep(Duration::from_millis(1)).await?;
+
+						/* the first initializer fails, and the next one retries */
+						if i == 0 {
+							Err(fmt_error!("Initializer failed"))
+						} else {
+							Ok(i)
+						}
+					})
+					.await;
+
+				value.map(|value| *value).ok()
+			})
+			.await
+		);
+	}
+
+	let mut results = Vec::new();
+
+	for task in tasks {
+		results.push(task.await);
+	}
+
+	assert_eq!(results[0], None);
+	assert!(results[1..].iter().all(|value| *value == Some(1)));
+	assert_eq!(runs.get(), 2);
+	assert_eq!(cell.get(), Some(&1));
+
+	Ok(())
+}
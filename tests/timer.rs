@@ -3,6 +3,7 @@
 use std::time::{Duration, Instant};
 
 use xx_core::error::Result;
+use xx_pulse::impls::AsyncIteratorExt;
 use xx_pulse::*;
 
 #[main]
@@ -41,3 +42,218 @@ async fn test_async_add() {
 
 	assert_eq!(result, 110);
 }
+
+#[main]
+#[test]
+async fn test_next_timeout() -> Result<()> {
+	let mut timer = Interval::try_new(Duration::from_millis(50))?;
+
+	let err = timer
+		.next_timeout(Duration::from_millis(10))
+		.await
+		.unwrap_err();
+
+	assert_eq!(err.kind(), xx_core::error::ErrorKind::TimedOut);
+	assert!(timer
+		.next_timeout(Duration::from_millis(500))
+		.await?
+		.is_some());
+
+	match next_or(&mut timer, sleep(Duration::from_secs(1))).await {
+		NextOr::Next(item) => assert!(item.is_some()),
+		NextOr::Other(_) => panic!("interval did not tick")
+	}
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_timer_owner() -> Result<()> {
+	let owner = std::rc::Rc::new(TimerOwner::new().await);
+	let mut handles = Vec::new();
+
+	for _ in 0..3 {
+		let owner = owner.clone();
+
+		handles.push(spawn(async move { owner.sleep(Duration::from_secs(3600)).await }).await);
+	}
+
+	yield_now().await;
+
+	let start = Instant::now();
+
+	assert_eq!(owner.cancel_all(), 3);
+	assert_eq!(owner.cancel_all(), 0);
+
+	for handle in handles {
+		assert_eq!(handle.await.unwrap_err().kind(), xx_core::error::ErrorKind::Interrupted);
+	}
+
+	assert!(start.elapsed() < Duration::from_secs(1));
+
+	Ok(())
+}
+
+#[test]
+fn test_timer_owner_outlives_runtime() {
+	let runtime = Runtime::new().unwrap();
+	let owner = runtime.block_on(TimerOwner::new());
+
+	drop(runtime);
+
+	/* the driver is gone, so there is nothing to cancel */
+	assert_eq!(owner.cancel_all(), 0);
+	drop(owner);
+}
+
+#[main]
+#[test]
+async fn test_heartbeat_cancel_tick() -> Result<()> {
+	let heartbeat = heartbeat::Heartbeat::new(Duration::from_secs(60), Duration::from_secs(120))?;
+	let heartbeat = std::rc::Rc::new(heartbeat);
+	let ticker = heartbeat.clone();
+	let handle = spawn(async move { ticker.tick().await }).await;
+
+	yield_now().await;
+
+	assert_eq!(timer_stats().await.owned, 1);
+	assert!(heartbeat.cancel_tick());
+	assert_eq!(handle.await.unwrap_err().kind(), xx_core::error::ErrorKind::Interrupted);
+	assert!(!heartbeat.cancel_tick());
+
+	Ok(())
+}
+
+#[test]
+fn test_heartbeat_resolution() {
+	use heartbeat::Heartbeat;
+
+	let (idle, timeout) = (Duration::from_secs(1), Duration::from_secs(2));
+
+	assert!(Heartbeat::with_resolution(idle, timeout, Duration::ZERO).is_err());
+	assert!(Heartbeat::new(Duration::from_nanos(1), timeout).is_err());
+	assert!(Heartbeat::with_resolution(idle, timeout, Duration::from_nanos(1)).is_ok());
+}
+
+#[main]
+#[test]
+async fn test_heartbeat_ticks() -> Result<()> {
+	let (idle, timeout) = (Duration::from_secs(60), Duration::from_secs(120));
+	let resolution = Duration::from_millis(20);
+	let heartbeat = heartbeat::Heartbeat::with_resolution(idle, timeout, resolution)?;
+	let start = Instant::now();
+
+	/* the first tick is immediate */
+	for _ in 0..5 {
+		assert!(heartbeat.tick().await?.is_empty());
+	}
+
+	let elapsed = start.elapsed();
+
+	assert!(elapsed >= resolution * 4 && elapsed < resolution * 20);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_heartbeat_expiry() -> Result<()> {
+	use heartbeat::{Action, Heartbeat};
+
+	let heartbeat = Heartbeat::with_resolution(
+		Duration::from_millis(40),
+		Duration::from_millis(100),
+		Duration::from_millis(10)
+	)?;
+
+	let active = heartbeat.register();
+	let silent = heartbeat.register();
+	let start = Instant::now();
+	let mut actions = Vec::new();
+
+	while !actions.contains(&Action::Expire) {
+		for due in heartbeat.tick().await? {
+			/* the touched connection is never due */
+			assert_eq!(due.id, silent.id());
+
+			actions.push(due.action);
+		}
+
+		active.touch();
+	}
+
+	/* pinged once when idle, then expired */
+	assert_eq!(actions, [Action::Ping, Action::Expire]);
+	assert!(start.elapsed() >= Duration::from_millis(100));
+
+	/* expired connections are not reported again */
+	for _ in 0..3 {
+		assert!(heartbeat.tick().await?.is_empty());
+
+		active.touch();
+	}
+
+	assert_eq!(heartbeat.len(), 2);
+
+	drop(silent);
+
+	assert_eq!(heartbeat.len(), 1);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_timer_stats() -> Result<()> {
+	let stats = timer_stats().await;
+
+	assert_eq!(stats.pending, 0);
+	assert_eq!(stats.next_deadline, None);
+
+	let owner = std::rc::Rc::new(TimerOwner::new().await);
+	let sleeper = owner.clone();
+	let handle = spawn(async move { sleeper.sleep(Duration::from_secs(60)).await }).await;
+
+	yield_now().await;
+
+	let stats = timer_stats().await;
+
+	assert_eq!(stats.pending, 1);
+	assert_eq!(stats.owned, 1);
+
+	let until_next = stats.until_next().unwrap();
+
+	assert!(until_next > Duration::from_secs(59) && until_next <= Duration::from_secs(60));
+
+	owner.cancel_all();
+
+	assert!(handle.await.is_err());
+	assert_eq!(timer_stats().await.pending, 0);
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_for_each_budgeted() -> Result<()> {
+	let other_ran = std::rc::Rc::new(std::cell::Cell::new(false));
+	let flag = other_ran.clone();
+
+	let handle = spawn(async move {
+		yield_now().await;
+		flag.set(true);
+	})
+	.await;
+
+	let mut sum = 0u64;
+	let yields = for_each_budgeted(0..1_000_000u64, |item| sum += item).await?;
+
+	assert_eq!(sum, 999_999 * 1_000_000 / 2);
+	assert!(yields > 0);
+	assert!(other_ran.get());
+
+	handle.await;
+
+	Ok(())
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "tracing")]
+#![allow(warnings)]
+
+use std::os::fd::{AsFd, AsRawFd};
+
+use enumflags2::BitFlags;
+use xx_pulse::fs::File;
+use xx_pulse::*;
+
+/* the only test in this binary, so that the lowest free fd number is reused */
+#[main]
+#[test]
+async fn test_trace_name_released() {
+	let file = File::open("Cargo.toml").await.unwrap();
+	let fd = file.fd().as_raw_fd();
+
+	file.set_trace_name("config");
+
+	assert_eq!(io::trace_name(file.fd()).as_deref(), Some("config"));
+
+	drop(file);
+
+	let file = File::open("Cargo.toml").await.unwrap();
+
+	assert_eq!(file.fd().as_raw_fd(), fd);
+	assert_eq!(io::trace_name(file.fd()), None);
+
+	file.close().await.unwrap();
+
+	/* a label on a bare fd is not inherited by the next fd with its number */
+	let raw = io::open("Cargo.toml", BitFlags::default(), 0).await.unwrap();
+
+	io::set_trace_name(raw.as_fd(), Some("bare"));
+	drop(raw);
+
+	let file = File::open("Cargo.toml").await.unwrap();
+
+	assert_eq!(file.fd().as_raw_fd(), fd);
+	assert_eq!(io::trace_name(file.fd()), None);
+}
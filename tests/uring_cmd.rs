@@ -0,0 +1,75 @@
+#![cfg(feature = "uring-cmd")]
+#![allow(warnings)]
+
+use std::rc::Rc;
+
+use xx_core::async_std::io::*;
+use xx_pulse::fs::File;
+use xx_pulse::*;
+
+#[main]
+#[test]
+async fn test_uring_cmd_unsupported_fd() {
+	let mut file = File::open("Cargo.toml").await.unwrap();
+	let cmd = [0u8; 72];
+
+	/* regular files have no passthrough commands */
+	let result = unsafe { io::uring_cmd(file.fd(), 0, &cmd).await };
+
+	assert!(result.is_err());
+
+	let too_large = [0u8; 96];
+	let result = unsafe { io::uring_cmd(file.fd(), 0, &too_large).await };
+
+	assert!(result.is_err());
+
+	/* regular operations still work with wide entries */
+	let mut str = String::new();
+
+	file.read_to_string(&mut str).await.unwrap();
+
+	assert!(str.contains("uring-cmd"));
+}
+
+#[asynchronous]
+async fn fill_ring() {
+	let file = Rc::new(File::open("Cargo.toml").await.unwrap());
+	let mut handles = Vec::new();
+
+	/* many more wide entries than the ring holds, so it fills and wraps */
+	for i in 0..64 {
+		let file = file.clone();
+
+		handles.push(
+			spawn(async move {
+				let mut buf = [0u8; 9];
+				let read = io::read(file.fd(), &mut buf, 0).await.unwrap();
+				let cmd = [i as u8; 72];
+				let result = unsafe { io::uring_cmd(file.fd(), 0, &cmd).await };
+
+				assert!(result.is_err());
+
+				(read, buf)
+			})
+			.await
+		);
+	}
+
+	for handle in handles {
+		let (read, buf) = handle.await;
+
+		assert_eq!(read, 9);
+		assert_eq!(&buf, b"[package]");
+	}
+}
+
+#[test]
+fn test_fill_wide_ring() {
+	let runtime = Runtime::builder()
+		.sq_entries(4)
+		.cq_entries(8)
+		.build()
+		.unwrap();
+
+	runtime.block_on(fill_ring());
+}
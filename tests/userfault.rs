@@ -0,0 +1,109 @@
+#![allow(warnings)]
+
+use std::thread;
+
+use xx_core::error::*;
+use xx_core::os::syscall::*;
+use xx_core::pointer::*;
+use xx_pulse::mem::userfault::*;
+use xx_pulse::*;
+
+const PAGE_SIZE: usize = 0x1000;
+
+const PROT_READ_WRITE: i32 = 0x3;
+const MAP_PRIVATE_ANONYMOUS: i32 = 0x22;
+
+#[main]
+#[test]
+async fn test_userfault_round_trip() -> Result<()> {
+	let uffd = match UserFault::new() {
+		Ok(uffd) => uffd,
+		Err(err) => {
+			/* not supported by the kernel, or not permitted */
+			eprintln!("skipping userfault test: {:?}", err);
+
+			return Ok(());
+		}
+	};
+
+	/* Safety: mapping new anonymous memory */
+	let addr = unsafe {
+		syscall_int!(Mmap, 0, PAGE_SIZE, PROT_READ_WRITE, MAP_PRIVATE_ANONYMOUS, -1, 0)?
+	} as usize;
+	let region = ptr!(addr as *mut ());
+
+	/* Safety: the fault is handled below, before the range is unmapped */
+	unsafe { uffd.register(region, PAGE_SIZE, RegisterMode::Missing.into())? };
+
+	/* faults must come from another thread */
+	let faulting = thread::spawn(move || {
+		/* Safety: the page is mapped, and reading it faults */
+		unsafe { (addr as *const u8).add(7).read_volatile() }
+	});
+
+	let Event::PageFault { address, flags, .. } = uffd.next_event().await? else {
+		panic!("expected a page fault");
+	};
+
+	assert_eq!(address as usize & !(PAGE_SIZE - 1), addr);
+	assert!(!flags.contains(FaultFlag::Write));
+
+	let data: Vec<u8> = (0..PAGE_SIZE).map(|i| i as u8).collect();
+
+	/* Safety: the page is in the registered range */
+	let copied = unsafe { uffd.copy(region, &data, true)? };
+
+	assert_eq!(copied, PAGE_SIZE);
+	assert_eq!(faulting.join().unwrap(), 7);
+
+	/* the page is now present, so resolving it again fails */
+	/* Safety: the page is in the registered range */
+	assert!(unsafe { uffd.zero_page(region, PAGE_SIZE, true) }.is_err());
+
+	uffd.unregister(region, PAGE_SIZE)?;
+	uffd.close().await?;
+
+	/* Safety: the region is no longer used */
+	unsafe { syscall_int!(Munmap, addr, PAGE_SIZE)? };
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_userfault_unmap_event() -> Result<()> {
+	let uffd = match UserFault::with_features(Feature::EventUnmap.into()) {
+		Ok(uffd) => uffd,
+		Err(err) => {
+			eprintln!("skipping userfault test: {:?}", err);
+
+			return Ok(());
+		}
+	};
+
+	assert_eq!(uffd.features(), Feature::EventUnmap);
+
+	/* Safety: mapping new anonymous memory */
+	let addr = unsafe {
+		syscall_int!(Mmap, 0, PAGE_SIZE, PROT_READ_WRITE, MAP_PRIVATE_ANONYMOUS, -1, 0)?
+	} as usize;
+
+	/* Safety: the range is never accessed */
+	unsafe { uffd.register(ptr!(addr as *mut ()), PAGE_SIZE, RegisterMode::Missing.into())? };
+
+	/* munmap waits for the event to be read */
+	let unmapping = thread::spawn(move || {
+		/* Safety: the region is no longer used */
+		unsafe { syscall_int!(Munmap, addr, PAGE_SIZE) }.is_ok()
+	});
+
+	let Event::Unmap { start, end } = uffd.next_event().await? else {
+		panic!("expected an unmap event");
+	};
+
+	assert_eq!(start as usize, addr);
+	assert_eq!(end as usize, addr + PAGE_SIZE);
+	assert!(unmapping.join().unwrap());
+
+	uffd.close().await
+}
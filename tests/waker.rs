@@ -0,0 +1,81 @@
+#![allow(warnings)]
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use xx_core::error::*;
+use xx_pulse::waker::*;
+use xx_pulse::*;
+
+#[derive(Default)]
+struct Slot {
+	value: Option<u32>,
+	waker: Option<Waker>
+}
+
+struct Receiver(Arc<Mutex<Slot>>);
+
+impl Future for Receiver {
+	type Output = u32;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+		let mut slot = self.0.lock().unwrap();
+
+		match slot.value.take() {
+			Some(value) => Poll::Ready(value),
+			None => {
+				slot.waker = Some(cx.waker().clone());
+
+				Poll::Pending
+			}
+		}
+	}
+}
+
+#[main]
+#[test]
+async fn test_poll_std() -> Result<()> {
+	let slot = Arc::new(Mutex::new(Slot::default()));
+	let sender = slot.clone();
+
+	let thread = thread::spawn(move || {
+		thread::sleep(Duration::from_millis(20));
+
+		let mut slot = sender.lock().unwrap();
+
+		slot.value = Some(42);
+		slot.waker.take().unwrap().wake();
+	});
+
+	assert_eq!(poll_std(Receiver(slot)).await?, 42);
+
+	thread.join().unwrap();
+
+	Ok(())
+}
+
+#[main]
+#[test]
+async fn test_wake_before_wait() -> Result<()> {
+	let waker = StdWaker::new().await;
+
+	waker.waker().wake();
+
+	/* the earlier wake is remembered */
+	waker.wait().await?;
+
+	let std_waker = waker.waker();
+	let thread = thread::spawn(move || {
+		thread::sleep(Duration::from_millis(20));
+		std_waker.wake();
+	});
+
+	waker.wait().await?;
+	thread.join().unwrap();
+
+	Ok(())
+}